@@ -1,28 +1,208 @@
-use std::io;
+use std::{
+    io,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
-use crossterm::event::{self, Event, KeyCode, KeyEventKind};
-use ratatui::{DefaultTerminal, Frame};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, MouseButton, MouseEvent, MouseEventKind,
+};
+use ratatui::{
+    Frame, Terminal,
+    backend::Backend,
+    layout::Rect,
+    widgets::{Block, Clear, Paragraph, Wrap},
+};
 
 use crate::{
     bufferentry::BufferEntry,
+    cmdline::CommandLine,
+    config::Config,
+    filetree::{FILE_TREE_WIDTH, FileTree, TreeAction},
+    filewalker::FileWalker,
+    gutter::LineNumbers,
+    hexmode::HexMode,
     insertmode::InsertMode,
     mode::{EditorMode, Mode},
+    modeutil::{
+        ClickTarget, click_target, header_tabs, popup_area, refuse_read_only_buffer, screen_areas,
+    },
     navigationmode::NavigationMode,
     normalmode::NormalMode,
+    pathinput::absolute_path,
+    recent::{RecentFiles, recent_files_path},
+    scrollbar::{self, Scrollbar},
     selectmode::SelectMode,
+    session::{Session, SessionBuffer, named_session_path, project_session_path},
+    splits::{Side, SplitDirection, Splits, View},
+    statusline::StatusLine,
+    theme::{ColorSupport, Slot, Theme},
 };
 
+/// How often the screen is updated while large files are indexed.
+const INDEX_REFRESH_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Two clicks on the same spot within this time are a double click.
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+
+/// Lines scrolled per step of the mouse wheel.
+const WHEEL_LINES: isize = 3;
+
 #[derive(Default)]
 pub struct ApplicationState {
     pub buffers: Vec<BufferEntry>,
     pub current_buffer: usize,
     pub window_size: (u16, u16),
     pub ctrl_active: bool,
+    pub file_walker: FileWalker,
+    /// Columns taken by the file tree on the left, 0 if it is hidden.
+    pub sidebar_width: u16,
+    /// A short message shown in the header, e.g. the result of a save.
+    pub status_message: Option<String>,
+    /// A buffer whose file changed on disk while it had unsaved changes.
+    pub external_change: Option<usize>,
+    /// Set once the user confirmed quitting.
+    pub exit_requested: bool,
+    pub recent_files: RecentFiles,
+    /// The current buffer is written to stdout on exit, so it does not need
+    /// to be saved before quitting.
+    pub write_stdout: bool,
+    pub line_numbers: LineNumbers,
+    /// Mark rows that continue on the next row when soft wrap is on.
+    pub wrap_indicator: bool,
+    /// The views the text area is divided into. The active one shows the
+    /// current buffer.
+    pub splits: Splits,
+    pub theme: Theme,
+    pub status_line: StatusLine,
+    pub scrollbar: Scrollbar,
+    /// Keys typed so far of a sequence that is not complete yet.
+    pub pending_keys: String,
+    /// Counts up whenever a buffer is used, see `BufferEntry::last_used`.
+    pub buffer_uses: u64,
+}
+
+impl ApplicationState {
+    /// Tell the current text buffer how much room it has between the line
+    /// numbers and the scrollbar, so it can wrap and scroll to keep the
+    /// cursor visible.
+    pub fn fit_buffer_to_view(&mut self) {
+        let Some(buffer) = self.buffers.get_mut(self.current_buffer) else {
+            return;
+        };
+        if buffer.is_binary() {
+            return;
+        }
+        let gutter = self.line_numbers.gutter_width(buffer.buffer.num_lines());
+        buffer.fit_to_view(
+            self.window_size
+                .0
+                .saturating_sub(gutter + self.scrollbar.width()),
+            self.window_size.1,
+        );
+    }
+
+    /// Add a newly opened buffer and make it the current one. A file that
+    /// was open before gets its last cursor position back.
+    pub fn open_buffer(&mut self, mut buffer: BufferEntry) {
+        if let Some(path) = buffer.path.clone() {
+            if let Some((line, column)) = self.recent_files.position(&path) {
+                buffer.goto_position(line, column, self.window_size.1);
+            }
+            self.recent_files
+                .remember(&path, buffer.cursor_line, buffer.cursor_position);
+        }
+        self.buffers.push(buffer);
+        self.current_buffer = self.buffers.len() - 1;
+    }
+
+    /// Divide the active view in two, both showing the current buffer at
+    /// the same position. The new view becomes the active one.
+    pub fn split_view(&mut self, direction: SplitDirection) {
+        let Some(buffer) = self.buffers.get(self.current_buffer) else {
+            return;
+        };
+        let view = View::of(self.current_buffer, buffer);
+        self.splits.views[self.splits.active] = view.clone();
+        self.splits.active = self.splits.split(direction, view);
+    }
+
+    /// Close the active view, its neighbour takes over the room.
+    pub fn close_view(&mut self) {
+        match self.splits.close(self.splits.active) {
+            Some(next) => {
+                self.splits.active = next;
+                self.show_view(next);
+            }
+            None => self.status_message = Some("This is the only view".to_string()),
+        }
+    }
+
+    /// Make the view `id` the active one.
+    pub fn focus_view(&mut self, id: usize) {
+        if id == self.splits.active || id >= self.splits.views.len() {
+            return;
+        }
+        if let Some(buffer) = self.buffers.get(self.current_buffer) {
+            self.splits.views[self.splits.active] = View::of(self.current_buffer, buffer);
+        }
+        self.splits.active = id;
+        self.show_view(id);
+    }
+
+    /// Move the current buffer's cursor to where the view `id` had it.
+    fn show_view(&mut self, id: usize) {
+        let view = &self.splits.views[id];
+        let Some(buffer) = self.buffers.get_mut(view.buffer) else {
+            return;
+        };
+        // another view may have removed lines since
+        let view = view.clamped_to(buffer);
+        self.current_buffer = view.buffer;
+        if buffer.is_binary() {
+            return;
+        }
+        buffer.scroll_offset = view.scroll_offset;
+        buffer.horizontal_offset = view.horizontal_offset;
+        buffer.goto_position(view.cursor_line, view.cursor_position, self.window_size.1);
+    }
+
+    /// Note that the current buffer is in use.
+    pub fn touch_current_buffer(&mut self) {
+        self.buffer_uses += 1;
+        if let Some(buffer) = self.buffers.get_mut(self.current_buffer) {
+            buffer.last_used = self.buffer_uses;
+        }
+    }
+
+    /// The ids of all buffers, the most recently used first.
+    pub fn buffers_by_use(&self) -> Vec<usize> {
+        let mut ids: Vec<usize> = (0..self.buffers.len()).collect();
+        ids.sort_by_key(|id| std::cmp::Reverse(self.buffers[*id].last_used));
+        ids
+    }
+
+    /// Note where the cursor of buffer `id` is, for reopening its file later.
+    pub fn remember_position(&mut self, id: usize) {
+        if let Some(buffer) = self.buffers.get(id)
+            && let Some(path) = &buffer.path
+        {
+            self.recent_files
+                .remember(path, buffer.cursor_line, buffer.cursor_position);
+        }
+    }
+
+    /// Note the positions of all open buffers. The least recently used goes
+    /// first, so the current buffer ends up at the top of the recent files.
+    pub fn remember_open_buffers(&mut self) {
+        for id in self.buffers_by_use().into_iter().rev() {
+            self.remember_position(id);
+        }
+    }
 }
 
 #[derive(Default)]
 pub struct App {
-    exit: bool,
     current_mode: Mode,
 
     app_state: ApplicationState,
@@ -31,45 +211,205 @@ pub struct App {
     insert_mode: InsertMode,
     navigation_mode: NavigationMode,
     select_mode: SelectMode,
+    hex_mode: HexMode,
+
+    file_tree: FileTree,
+    last_checked_buffer: Option<usize>,
+    /// The part of the terminal the views share.
+    text_region: Rect,
+
+    /// Where the session is saved on exit.
+    session_path: Option<PathBuf>,
+    /// A saved session the user is asked to restore.
+    pending_session: Option<Session>,
+
+    /// Where the left mouse button was pressed in the text, the start of a
+    /// selection made by dragging.
+    drag_start: Option<(usize, usize)>,
+    /// When and where the left mouse button was last pressed.
+    last_click: Option<(Instant, u16, u16)>,
+    /// The mode a click goes back to after selecting with the mouse.
+    mode_before_select: Option<Mode>,
 }
 
 impl App {
     pub fn new() -> App {
-        let app = App {
+        App {
             ..Default::default()
-        };
-        app
+        }
     }
 
-    pub fn run(
+    /// Run the editor until the user quits. `piped` is what was read from
+    /// stdin if the command line asked for it.
+    pub fn run<B: Backend>(
         &mut self,
-        terminal: &mut DefaultTerminal,
-        file_name: Option<String>,
+        terminal: &mut Terminal<B>,
+        cmdline: CommandLine,
+        piped: Option<Vec<u8>>,
     ) -> io::Result<()> {
-        if let Some(file_name) = file_name {
-            self.app_state
-                .buffers
-                .push(BufferEntry::from_file(file_name));
-            self.app_state.current_buffer = self.app_state.buffers.len() - 1;
-            self.current_mode = Mode::Insert;
+        self.update_window_size(terminal)?;
+        self.open_command_line(cmdline, piped);
+        self.fit_mode_to_buffer();
+
+        // ratatui only sends the cells that differ from the last frame, so
+        // the screen is not cleared, and frames are only drawn after changes
+        let mut changed = true;
+        while !self.app_state.exit_requested {
+            changed |= self.update_large_files();
+            if changed {
+                self.update_window_size(terminal)?;
+                self.app_state.fit_buffer_to_view();
+                terminal.draw(|frame| self.draw(frame))?;
+            }
+
+            changed = self.handle_events()?;
+        }
+        self.save_session();
+        self.save_recent_files();
+        Ok(())
+    }
+
+    /// The contents of the current buffer, for writing them to stdout.
+    pub fn output(&self) -> Option<io::Result<Vec<u8>>> {
+        self.app_state
+            .buffers
+            .get(self.app_state.current_buffer)
+            .map(BufferEntry::contents)
+    }
+
+    fn update_window_size<B: Backend>(&mut self, terminal: &Terminal<B>) -> io::Result<()> {
+        let s = terminal.size()?;
+        self.text_region =
+            crate::modeutil::text_region(Rect::new(0, 0, s.width, s.height), &self.app_state);
+        // the modes only get to see the active view
+        let view = self
+            .app_state
+            .splits
+            .area_of(self.text_region, self.app_state.splits.active)
+            .unwrap_or(self.text_region);
+        self.app_state.window_size = (view.width, view.height);
+        Ok(())
+    }
+
+    /// Load the configuration and open the files and directory given on the
+    /// command line.
+    fn open_command_line(&mut self, cmdline: CommandLine, piped: Option<Vec<u8>>) {
+        match Config::load(cmdline.config_path.as_deref()) {
+            Ok(config) => {
+                self.app_state.file_walker.show_hidden = config.show_hidden_files;
+                self.app_state.line_numbers = config.line_numbers;
+                self.app_state.wrap_indicator = config.wrap_indicator;
+                self.app_state.status_line = config.status_line;
+                self.app_state.scrollbar = config.scrollbar;
+                let name = config.theme.as_deref().unwrap_or("dark");
+                match Theme::load(name, ColorSupport::detect()) {
+                    Ok(theme) => self.app_state.theme = theme,
+                    Err(e) => self.app_state.status_message = Some(e),
+                }
+            }
+            Err(e) => self.app_state.status_message = Some(e),
+        }
+        if let Some(path) = recent_files_path() {
+            self.app_state.recent_files = RecentFiles::load(&path);
+        }
+
+        let no_arguments =
+            cmdline.files.is_empty() && cmdline.directory.is_none() && !cmdline.read_stdin;
+        self.app_state.write_stdout = cmdline.write_stdout;
+        match &cmdline.session {
+            Some(name) => {
+                self.session_path = named_session_path(name);
+                match self.session_path.as_deref().map(Session::load) {
+                    Some(Ok(Some(session))) => self.restore_session(session),
+                    Some(Err(e)) => self.app_state.status_message = Some(e),
+                    _ => self.app_state.status_message = Some(format!("New session {}", name)),
+                }
+            }
+            None => {
+                self.session_path = std::env::current_dir()
+                    .ok()
+                    .and_then(|dir| project_session_path(&absolute_path(&dir)));
+                if no_arguments
+                    && let Some(Ok(Some(session))) = self.session_path.as_deref().map(Session::load)
+                    && !session.buffers.is_empty()
+                {
+                    self.pending_session = Some(session);
+                }
+            }
+        }
+
+        let first_file = self.app_state.buffers.len();
+        for file in cmdline.files {
+            match BufferEntry::open_or_create(file.path.clone()) {
+                Ok(mut buffer) => {
+                    buffer.read_only |= cmdline.read_only;
+                    self.app_state.open_buffer(buffer);
+                    // positions on the command line are 1-based
+                    if let Some(line) = file.line {
+                        let height = self.app_state.window_size.1;
+                        self.app_state.buffers[self.app_state.current_buffer].goto_position(
+                            line.saturating_sub(1),
+                            file.column.unwrap_or(1).saturating_sub(1),
+                            height,
+                        );
+                    }
+                    self.current_mode = Mode::Insert;
+                }
+                Err(e) => {
+                    self.app_state.status_message =
+                        Some(format!("Could not open {}: {}", file.path, e))
+                }
+            }
         }
 
-        while !self.exit {
-            let _ = terminal.clear();
-            terminal.draw(|frame| self.draw(frame))?;
+        if self.app_state.buffers.len() > first_file {
+            self.app_state.current_buffer = first_file;
+        }
 
-            let s = terminal.size().unwrap();
-            self.app_state.window_size = (s.width, s.height - crate::modeutil::TOP_BAR_HEIGHT);
+        // the piped text is what the user came to edit
+        if let Some(data) = piped {
+            self.app_state
+                .open_buffer(BufferEntry::from_bytes("stdin", data));
+            self.current_mode = Mode::Insert;
+        }
 
-            self.handle_events()?;
+        if let Some(directory) = cmdline.directory {
+            let show_hidden = self.app_state.file_walker.show_hidden;
+            self.app_state.file_walker = FileWalker::new(directory);
+            self.app_state.file_walker.show_hidden = show_hidden;
+            self.normal_mode.show_open_file();
+            self.current_mode = Mode::Normal;
         }
-        Ok(())
     }
 
     fn draw(&self, frame: &mut Frame) {
         self.with_active_mode(|mode, app_state| {
             mode.render(frame, app_state);
         });
+
+        if self.file_tree.visible {
+            let [_, body, _] = screen_areas(frame.area());
+            let dest = crate::modeutil::sidebar_area(body, &self.app_state);
+            frame.render_widget(Clear, dest);
+            self.file_tree.render(frame, dest, &self.app_state);
+        }
+
+        if let Some(id) = self.app_state.external_change {
+            self.render_external_change_popup(frame, id);
+        }
+
+        if let Some(session) = &self.pending_session {
+            self.render_restore_session_popup(frame, session);
+        }
+    }
+
+    fn toggle_file_tree(&mut self) {
+        self.file_tree.toggle(&self.app_state.file_walker);
+        self.app_state.sidebar_width = if self.file_tree.visible {
+            FILE_TREE_WIDTH
+        } else {
+            0
+        };
     }
 
     fn with_active_mode(&self, func: impl FnOnce(&dyn EditorMode, &ApplicationState)) {
@@ -78,7 +418,7 @@ impl App {
             Mode::Insert => func(&self.insert_mode, &self.app_state),
             Mode::Navigate => func(&self.navigation_mode, &self.app_state),
             Mode::Select => func(&self.select_mode, &self.app_state),
-            _ => (),
+            Mode::Hex => func(&self.hex_mode, &self.app_state),
         }
     }
 
@@ -91,46 +431,668 @@ impl App {
             Mode::Insert => func(&mut self.insert_mode, &mut self.app_state),
             Mode::Navigate => func(&mut self.navigation_mode, &mut self.app_state),
             Mode::Select => func(&mut self.select_mode, &mut self.app_state),
-            _ => panic!("Unknown mode"),
+            Mode::Hex => func(&mut self.hex_mode, &mut self.app_state),
+        }
+    }
+
+    /// Binary buffers are edited in the hex view, text buffers never are.
+    fn fit_mode_to_buffer(&mut self) {
+        let binary = self
+            .app_state
+            .buffers
+            .get(self.app_state.current_buffer)
+            .map(|b| b.is_binary());
+        self.current_mode = match (std::mem::take(&mut self.current_mode), binary) {
+            (Mode::Insert | Mode::Select, Some(true)) => Mode::Hex,
+            (Mode::Hex, Some(false)) => Mode::Insert,
+            (Mode::Hex, None) => Mode::Normal,
+            (mode, _) => mode,
+        };
+    }
+
+    /// Pick up the progress of indexing large files and drop the lines
+    /// read for them that are no longer on screen. Returns true if there was
+    /// progress to show.
+    fn update_large_files(&mut self) -> bool {
+        let height = self.app_state.window_size.1 as usize;
+        let minimap = self.app_state.scrollbar == Scrollbar::Minimap;
+        let mut progress = false;
+        for buffer in self.app_state.buffers.iter_mut() {
+            let indexing = buffer.buffer.is_indexing();
+            progress |= buffer.buffer.poll_index() || indexing != buffer.buffer.is_indexing();
+            let first = buffer.scroll_offset.saturating_sub(height);
+            let mut keep = Vec::with_capacity(2);
+            keep.push(first..buffer.scroll_offset + 2 * height);
+            // the minimap shows lines further away from the view
+            if minimap {
+                keep.push(scrollbar::minimap_range(
+                    buffer.scroll_offset,
+                    height,
+                    buffer.buffer.num_lines(),
+                    height as u16,
+                ));
+            }
+            buffer.buffer.trim_cache(&keep);
         }
+        progress
     }
 
-    fn handle_events(&mut self) -> io::Result<()> {
-        match event::read()? {
+    /// Wait for the next event and handle it. Returns false if there was
+    /// nothing that changes the screen.
+    fn handle_events(&mut self) -> io::Result<bool> {
+        // redraw regularly while large files are indexed to show the progress
+        if self
+            .app_state
+            .buffers
+            .iter()
+            .any(|b| b.buffer.is_indexing())
+            && !event::poll(INDEX_REFRESH_INTERVAL)?
+        {
+            return Ok(false);
+        }
+
+        let changed = match event::read()? {
             // it's important to check that the event is a key press event as
             // crossterm also emits key release and repeat events on Windows.
             Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
-                self.app_state.ctrl_active =
-                    key_event.modifiers.contains(event::KeyModifiers::CONTROL);
-
-                if key_event.modifiers.contains(event::KeyModifiers::CONTROL) {
-                    // change modes
-                    match key_event.code {
-                        KeyCode::Char('e') => {
-                            if self.app_state.buffers.len() > 0 {
-                                self.current_mode = Mode::Insert
-                            }
-                        }
-                        //KeyCode::Char('a') => self.current_mode = Mode::Navigate,
-                        KeyCode::Char('w') => self.current_mode = Mode::Select,
-                        KeyCode::Char('q') => self.current_mode = Mode::Normal,
-                        KeyCode::Char(_) => self
-                            .navigation_mode
-                            .handle_key_event(key_event, &mut self.app_state),
-                        _ => (),
+                self.app_state.status_message = None;
+                if let Some(session) = self.pending_session.take() {
+                    if key_event.code == KeyCode::Char('y') {
+                        self.restore_session(session);
                     }
+                } else if self.app_state.external_change.is_some() {
+                    self.handle_external_change_key(key_event);
+                } else {
+                    self.handle_key_event(key_event);
                 }
-                match key_event.code {
-                    KeyCode::Esc => self.exit = true,
-                    _ => (),
+                true
+            }
+            Event::FocusGained => {
+                for id in 0..self.app_state.buffers.len() {
+                    self.check_external_change(id);
                 }
+                true
+            }
+            Event::Mouse(mouse_event)
+                if self.pending_session.is_none() && self.app_state.external_change.is_none() =>
+            {
+                self.handle_mouse_event(mouse_event)
+            }
+            Event::Resize(..) => true,
+            _ => false,
+        };
 
-                self.with_active_mode_mut(|mode, appstate| {
-                    mode.handle_key_event(key_event, appstate);
+        self.fit_mode_to_buffer();
+        self.app_state.touch_current_buffer();
+
+        // switching to another buffer counts as focusing it
+        if self.last_checked_buffer != Some(self.app_state.current_buffer) {
+            self.last_checked_buffer = Some(self.app_state.current_buffer);
+            self.check_external_change(self.app_state.current_buffer);
+        }
+        Ok(changed)
+    }
+
+    fn handle_key_event(&mut self, key_event: KeyEvent) {
+        self.app_state.ctrl_active = key_event.modifiers.contains(event::KeyModifiers::CONTROL);
+
+        // popups keep the keys to themselves
+        let mut popup = self.file_tree.has_prompt();
+        self.with_active_mode(|mode, _| popup |= mode.has_popup());
+        if key_event.modifiers.contains(event::KeyModifiers::ALT)
+            && !popup
+            && self.handle_view_key(key_event)
+        {
+            return;
+        }
+
+        if key_event.modifiers.contains(event::KeyModifiers::CONTROL) {
+            // change modes
+            match key_event.code {
+                KeyCode::Char('e') if !self.app_state.buffers.is_empty() => {
+                    self.current_mode = Mode::Insert
+                }
+                //KeyCode::Char('a') => self.current_mode = Mode::Navigate,
+                KeyCode::Char('w') => self.enter_select_mode(),
+                KeyCode::Char('q') => self.current_mode = Mode::Normal,
+                KeyCode::Char('t') => {
+                    self.toggle_file_tree();
+                    return;
+                }
+                KeyCode::Char(_) => self
+                    .navigation_mode
+                    .handle_key_event(key_event, &mut self.app_state),
+                _ => (),
+            }
+        }
+        // the hex view closes its prompts on Esc itself
+        let hex_prompt = matches!(self.current_mode, Mode::Hex) && self.hex_mode.has_popup();
+        if key_event.code == KeyCode::Esc
+            && !self.file_tree.focused
+            && !hex_prompt
+            && !matches!(self.current_mode, Mode::Normal)
+        {
+            self.current_mode = Mode::Normal;
+            return;
+        }
+
+        if self.file_tree.focused && !self.app_state.ctrl_active {
+            if self
+                .file_tree
+                .handle_key_event(key_event, &mut self.app_state)
+                == TreeAction::OpenedFile
+            {
+                self.current_mode = Mode::Insert;
+            }
+            return;
+        }
+
+        self.with_active_mode_mut(|mode, appstate| {
+            mode.handle_key_event(key_event, appstate);
+        });
+    }
+
+    /// Click to switch buffers or place the cursor, drag to select and
+    /// scroll with the wheel. Returns false for events that change nothing.
+    fn handle_mouse_event(&mut self, mouse_event: MouseEvent) -> bool {
+        let mut used_up = false;
+        self.with_active_mode_mut(|mode, app_state| {
+            used_up = mode.handle_mouse_event(mouse_event, app_state)
+        });
+        if used_up {
+            return false;
+        }
+
+        let (x, y) = (mouse_event.column, mouse_event.row);
+        // the header is the same in all modes, the text is only shown in some
+        let shows_text = matches!(
+            self.current_mode,
+            Mode::Insert | Mode::Navigate | Mode::Select
+        );
+        match mouse_event.kind {
+            MouseEventKind::Down(MouseButton::Left) if y < self.text_region.y => {
+                let mut mode_name = "";
+                self.with_active_mode(|mode, _| mode_name = mode.mode_name());
+                let tabs = header_tabs(&self.app_state, self.text_region.right(), mode_name);
+                if y == 1
+                    && let Some(tab) = tabs
+                        .iter()
+                        .find(|tab| (tab.x..tab.x + tab.width).contains(&x))
+                {
+                    self.app_state.current_buffer = tab.buffer;
+                }
+                true
+            }
+            MouseEventKind::Down(MouseButton::Left) if shows_text => {
+                let double_click = self.last_click.is_some_and(|(time, last_x, last_y)| {
+                    (last_x, last_y) == (x, y) && time.elapsed() < DOUBLE_CLICK_INTERVAL
                 });
+                self.last_click = Some((Instant::now(), x, y));
+                if !self.focus_view_at(x, y) {
+                    return false;
+                }
+                self.file_tree.focused = false;
+                let height = self.app_state.window_size.1;
+                let target = self.click_target(x, y);
+                let buffer = &mut self.app_state.buffers[self.app_state.current_buffer];
+                buffer.clear_selection();
+                match target {
+                    ClickTarget::Text(line, position) => {
+                        buffer.goto_position(line, position, height);
+                        self.drag_start = Some((buffer.cursor_line, buffer.cursor_position));
+                        if double_click {
+                            buffer.select_word();
+                        }
+                    }
+                    ClickTarget::Line(line) => {
+                        buffer.goto_position(line, 0, height);
+                        self.drag_start = None;
+                    }
+                }
+                if double_click && matches!(target, ClickTarget::Text(..)) {
+                    self.enter_select_mode();
+                } else if matches!(self.current_mode, Mode::Select) {
+                    // the selection is gone, so is the reason for the mode
+                    self.current_mode = match self.mode_before_select.take() {
+                        Some(mode @ (Mode::Insert | Mode::Navigate)) => mode,
+                        _ => Mode::Insert,
+                    };
+                }
+                true
+            }
+            MouseEventKind::Drag(MouseButton::Left) if shows_text => {
+                let Some(start) = self.drag_start else {
+                    return false;
+                };
+                let area = self
+                    .app_state
+                    .splits
+                    .area_of(self.text_region, self.app_state.splits.active)
+                    .unwrap_or(self.text_region);
+                let height = self.app_state.window_size.1;
+                // dragging past the top or bottom scrolls
+                let buffer = &mut self.app_state.buffers[self.app_state.current_buffer];
+                if y < area.y {
+                    buffer.scroll_by(-1, height);
+                } else if y >= area.bottom() {
+                    buffer.scroll_by(1, height);
+                }
+                let x = x.clamp(area.x, area.right().saturating_sub(1));
+                let y = y.clamp(area.y, area.bottom().saturating_sub(1));
+                if let ClickTarget::Text(line, position) = self.click_target(x, y) {
+                    let buffer = &mut self.app_state.buffers[self.app_state.current_buffer];
+                    buffer.goto_position(line, position, height);
+                    buffer.selection_start = Some(start);
+                    buffer.extend_selection_to_cursor();
+                    self.enter_select_mode();
+                }
+                true
             }
-            _ => {}
+            MouseEventKind::Up(MouseButton::Left) => {
+                self.drag_start = None;
+                false
+            }
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollDown if shows_text => {
+                if !self.focus_view_at(x, y) {
+                    return false;
+                }
+                let lines = if mouse_event.kind == MouseEventKind::ScrollUp {
+                    -WHEEL_LINES
+                } else {
+                    WHEEL_LINES
+                };
+                let height = self.app_state.window_size.1;
+                self.app_state.buffers[self.app_state.current_buffer].scroll_by(lines, height);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Switch to the select mode, remembering the mode a click goes back to.
+    fn enter_select_mode(&mut self) {
+        if !matches!(self.current_mode, Mode::Select) {
+            self.mode_before_select = Some(std::mem::replace(&mut self.current_mode, Mode::Select));
+        }
+    }
+
+    /// Make the view at `x`, `y` the active one. Returns false if there is
+    /// no view with a buffer there.
+    fn focus_view_at(&mut self, x: u16, y: u16) -> bool {
+        let position = ratatui::layout::Position::new(x, y);
+        let Some((id, _)) = self
+            .app_state
+            .splits
+            .areas(self.text_region)
+            .into_iter()
+            .find(|(_, area)| area.contains(position))
+        else {
+            return false;
         };
-        Ok(())
+        self.app_state.focus_view(id);
+        self.app_state
+            .buffers
+            .get(self.app_state.current_buffer)
+            .is_some()
+    }
+
+    /// What the click at `x`, `y` into the active view points at.
+    fn click_target(&self, x: u16, y: u16) -> ClickTarget {
+        let area = self
+            .app_state
+            .splits
+            .area_of(self.text_region, self.app_state.splits.active)
+            .unwrap_or(self.text_region);
+        let buffer = &self.app_state.buffers[self.app_state.current_buffer];
+        let position = View::of(self.app_state.current_buffer, buffer);
+        click_target(area, &self.app_state, buffer, &position, x, y)
+    }
+
+    /// Alt + key splits, closes, resizes and moves between views. Returns
+    /// false for keys that have nothing to do with views.
+    fn handle_view_key(&mut self, key_event: KeyEvent) -> bool {
+        let splits = &mut self.app_state.splits;
+        let side = match key_event.code {
+            KeyCode::Char('v') => {
+                self.app_state.split_view(SplitDirection::Vertical);
+                return true;
+            }
+            KeyCode::Char('h') => {
+                self.app_state.split_view(SplitDirection::Horizontal);
+                return true;
+            }
+            KeyCode::Char('x') => {
+                self.app_state.close_view();
+                return true;
+            }
+            KeyCode::Char('+') | KeyCode::Char('=') => {
+                splits.resize(splits.active, true);
+                return true;
+            }
+            KeyCode::Char('-') => {
+                splits.resize(splits.active, false);
+                return true;
+            }
+            KeyCode::Left => Side::Left,
+            KeyCode::Right => Side::Right,
+            KeyCode::Up => Side::Up,
+            KeyCode::Down => Side::Down,
+            _ => return false,
+        };
+        if let Some(id) = splits.neighbour(self.text_region, splits.active, side) {
+            self.app_state.focus_view(id);
+        }
+        true
+    }
+
+    /// Reload the buffer if its file changed on disk. If the buffer has
+    /// unsaved changes itself, ask the user what to do instead.
+    fn check_external_change(&mut self, id: usize) {
+        let Some(buffer) = self.app_state.buffers.get_mut(id) else {
+            return;
+        };
+        if !buffer.changed_on_disk() {
+            return;
+        }
+
+        if buffer.modified {
+            self.app_state.external_change = Some(id);
+            return;
+        }
+
+        self.app_state.status_message = Some(match buffer.reload() {
+            Ok(()) => format!("Reloaded {}, it changed on disk", buffer.display_name()),
+            Err(e) => format!("Could not reload {}: {}", buffer.display_name(), e),
+        });
+    }
+
+    fn handle_external_change_key(&mut self, key_event: KeyEvent) {
+        let Some(id) = self.app_state.external_change.take() else {
+            return;
+        };
+        if key_event.code == KeyCode::Char('o') && refuse_read_only_buffer(&mut self.app_state, id)
+        {
+            // the other options are still open
+            self.app_state.external_change = Some(id);
+            return;
+        }
+        let buffer = &mut self.app_state.buffers[id];
+
+        let result = match key_event.code {
+            KeyCode::Char('o') => buffer
+                .write_to_file()
+                .map(|_| format!("Wrote {}", buffer.display_name())),
+            KeyCode::Char('r') => buffer
+                .reload()
+                .map(|_| format!("Reloaded {}", buffer.display_name())),
+            KeyCode::Char('d') => buffer.diff_against_disk().map(|diff| {
+                self.app_state.buffers.push(diff);
+                self.app_state.current_buffer = self.app_state.buffers.len() - 1;
+                self.last_checked_buffer = Some(self.app_state.current_buffer);
+                "Showing changes, save again to decide".to_string()
+            }),
+            KeyCode::Char('c') | KeyCode::Esc => {
+                // don't ask again until the file changes once more
+                buffer.acknowledge_disk_state();
+                Ok("Kept the buffer, the file on disk is newer".to_string())
+            }
+            _ => {
+                // keep asking until the user picked one of the options
+                self.app_state.external_change = Some(id);
+                return;
+            }
+        };
+
+        self.app_state.status_message = Some(result.unwrap_or_else(|e| e.to_string()));
+    }
+
+    fn render_external_change_popup(&self, frame: &mut Frame, id: usize) {
+        let area = popup_area(frame.area(), 60, 30);
+        let text = format!(
+            "{} was changed on disk and has unsaved changes.\n\n\
+             o: overwrite the file with the buffer\n\
+             r: reload the file, dropping the changes\n\
+             d: show the differences\n\
+             c: cancel",
+            self.app_state.buffers[id].display_name()
+        );
+        frame.render_widget(Clear, area);
+        frame.render_widget(
+            Paragraph::new(text).wrap(Wrap { trim: false }).block(
+                Block::bordered()
+                    .title("File changed on disk")
+                    .style(self.app_state.theme.style(Slot::Popup)),
+            ),
+            area,
+        );
+    }
+
+    /// The open files, cursors and layout, for restoring them later.
+    /// Buffers without a file can not be restored and are left out.
+    fn session(&self) -> Session {
+        let mut session = Session {
+            file_tree: self.file_tree.visible,
+            ..Default::default()
+        };
+        self.with_active_mode(|mode, _| session.mode = mode.mode_name().to_string());
+
+        for (id, buffer) in self.app_state.buffers.iter().enumerate() {
+            let Some(path) = &buffer.path else {
+                continue;
+            };
+            if id == self.app_state.current_buffer {
+                session.current_buffer = session.buffers.len();
+            }
+            session.buffers.push(SessionBuffer {
+                path: path.clone(),
+                name: buffer.name.clone(),
+                cursor_line: buffer.cursor_line,
+                cursor_position: buffer.cursor_position,
+                scroll_offset: buffer.scroll_offset,
+            });
+        }
+        session
+    }
+
+    /// Save the session, unless there is nothing to restore from it. If all
+    /// buffers were closed, the old session is removed.
+    fn save_session(&self) {
+        let session = self.session();
+        let Some(path) = &self.session_path else {
+            return;
+        };
+        if self.app_state.buffers.is_empty() {
+            let _ = std::fs::remove_file(path);
+        } else if !session.buffers.is_empty() {
+            let _ = session.save(path);
+        }
+    }
+
+    fn save_recent_files(&mut self) {
+        self.app_state.remember_open_buffers();
+        if let Some(path) = recent_files_path() {
+            let _ = self.app_state.recent_files.save(&path);
+        }
+    }
+
+    fn restore_session(&mut self, session: Session) {
+        let first_id = self.app_state.buffers.len();
+        let mut missing = Vec::new();
+
+        for (id, saved) in session.buffers.into_iter().enumerate() {
+            match BufferEntry::from_file(&saved.path) {
+                Ok(mut buffer) => {
+                    buffer.name = saved.name;
+                    buffer.scroll_offset = saved.scroll_offset;
+                    buffer.goto_position(
+                        saved.cursor_line,
+                        saved.cursor_position,
+                        self.app_state.window_size.1,
+                    );
+                    if id == session.current_buffer {
+                        self.app_state.current_buffer = self.app_state.buffers.len();
+                    }
+                    self.app_state.buffers.push(buffer);
+                }
+                Err(_) => missing.push(saved.path.to_string_lossy().to_string()),
+            }
+        }
+
+        if !missing.is_empty() {
+            self.app_state.status_message = Some(format!("Could not open {}", missing.join(", ")));
+        }
+        if session.file_tree != self.file_tree.visible {
+            self.toggle_file_tree();
+        }
+        if self.app_state.buffers.len() > first_id {
+            self.current_mode = match session.mode.as_str() {
+                "INSERT" => Mode::Insert,
+                "HEX" => Mode::Hex,
+                _ => Mode::Normal,
+            };
+        }
+    }
+
+    fn render_restore_session_popup(&self, frame: &mut Frame, session: &Session) {
+        let area = popup_area(frame.area(), 60, 30);
+        let text = format!(
+            "Restore the last session in this directory with {} open file(s)?\n\n\
+             y: restore\n\
+             any other key: start empty",
+            session.buffers.len()
+        );
+        frame.render_widget(Clear, area);
+        frame.render_widget(
+            Paragraph::new(text).wrap(Wrap { trim: false }).block(
+                Block::bordered()
+                    .title("Session")
+                    .style(self.app_state.theme.style(Slot::Popup)),
+            ),
+            area,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::KeyModifiers;
+
+    use super::*;
+    use crate::testutil::TempDir;
+
+    fn click(x: u16, y: u16) -> MouseEvent {
+        MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column: x,
+            row: y,
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+
+    fn changed_on_disk(dir: &TempDir) -> App {
+        let path = dir.join("file.txt");
+        std::fs::write(&path, "old\n").unwrap();
+        let mut app = App::new();
+        let mut buffer = BufferEntry::from_file(&path).unwrap();
+        buffer.modified = true;
+        app.app_state.buffers.push(buffer);
+        // a different size is noticed even within the same second
+        std::fs::write(&path, "newer\n").unwrap();
+        app.check_external_change(0);
+        assert_eq!(app.app_state.external_change, Some(0));
+        app
+    }
+
+    #[test]
+    pub fn cancelled_external_changes_are_not_asked_again() {
+        let dir = TempDir::new("external_cancel");
+        let mut app = changed_on_disk(&dir);
+
+        app.handle_external_change_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        assert_eq!(app.app_state.external_change, None);
+        app.check_external_change(0);
+        assert_eq!(app.app_state.external_change, None);
+    }
+
+    #[test]
+    pub fn read_only_buffers_do_not_overwrite_external_changes() {
+        let dir = TempDir::new("external_read_only");
+        let mut app = changed_on_disk(&dir);
+        app.app_state.buffers[0].read_only = true;
+
+        app.handle_external_change_key(KeyEvent::new(KeyCode::Char('o'), KeyModifiers::NONE));
+        assert_eq!(app.app_state.external_change, Some(0));
+        assert_eq!(
+            std::fs::read_to_string(dir.join("file.txt")).unwrap(),
+            "newer\n"
+        );
+    }
+
+    #[test]
+    pub fn recent_files_start_with_the_current_buffer() {
+        let mut app_state = ApplicationState::default();
+        for name in ["/a.rs", "/b.rs", "/c.rs"] {
+            app_state.buffers.push(BufferEntry {
+                path: Some(PathBuf::from(name)),
+                ..Default::default()
+            });
+        }
+        for id in [2, 0, 1] {
+            app_state.current_buffer = id;
+            app_state.touch_current_buffer();
+        }
+
+        app_state.remember_open_buffers();
+        let paths: Vec<_> = app_state
+            .recent_files
+            .files
+            .iter()
+            .map(|f| f.path.to_str().unwrap())
+            .collect();
+        assert_eq!(paths, ["/b.rs", "/a.rs", "/c.rs"]);
+    }
+
+    #[test]
+    pub fn view_keys_do_not_reach_past_a_popup() {
+        let mut app = App::new();
+        app.app_state.buffers.push(BufferEntry {
+            modified: true,
+            ..Default::default()
+        });
+        let alt = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::ALT);
+
+        // asks before closing the buffer
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::NONE));
+        app.handle_key_event(alt('v'));
+        assert_eq!(app.app_state.splits.views.len(), 1);
+
+        app.handle_key_event(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        app.handle_key_event(alt('v'));
+        assert_eq!(app.app_state.splits.views.len(), 2);
+    }
+
+    #[test]
+    pub fn clicks_do_not_reach_buffers_behind_a_popup() {
+        let mut app = App::new();
+        for name in ["one", "two"] {
+            app.app_state.buffers.push(BufferEntry {
+                name: name.to_string(),
+                modified: true,
+                ..Default::default()
+            });
+        }
+        app.text_region = Rect::new(0, 3, 80, 20);
+        let tabs = header_tabs(&app.app_state, 80, "NORMAL");
+        let close = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::NONE);
+
+        // asks before closing "one", which must stay the buffer it closes
+        app.handle_key_event(close);
+        assert!(!app.handle_mouse_event(click(tabs[1].x, 1)));
+        assert_eq!(app.app_state.current_buffer, 0);
+
+        // cancelled, clicks work again
+        app.handle_key_event(close);
+        assert!(app.handle_mouse_event(click(tabs[1].x, 1)));
+        assert_eq!(app.app_state.current_buffer, 1);
     }
 }
@@ -1,9 +1,148 @@
+use std::{
+    cell::{Cell, OnceCell, RefCell},
+    fs::File,
+    io::{BufRead, BufReader, Seek, SeekFrom, Write},
+    ops::Range,
+    path::Path,
+    sync::mpsc::{Receiver, Sender, TryRecvError},
+};
+
 pub type Line = Vec<char>;
 
-/// A buffer represents the contents of a file as a vec of lines
+/// Files larger than this are read from disk on demand instead of loading
+/// them as a whole.
+pub const LARGE_FILE_SIZE: u64 = 32 * 1024 * 1024;
+
+/// Pages with lines of a large file that were read from disk are dropped
+/// again once more than this many are kept around.
+const MAX_CACHED_PAGES: usize = 40;
+
+/// Lines per page. Inserting or removing a line only moves the lines of its
+/// page, a page is split once it grows to twice this.
+const PAGE_LINES: usize = 256;
+
+/// Marks a line that is kept in memory, the other bits are its index in
+/// `Buffer::loaded`. Other lines are the position where they start in the
+/// file.
+const LOADED: u64 = 1 << 63;
+
+/// Bytes the background indexer reads at once.
+const INDEX_CHUNK_SIZE: usize = 1 << 20;
+
+/// How lines are terminated when the buffer is written.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    /// The line ending of the first line in `data`.
+    pub fn detect(data: &[u8]) -> LineEnding {
+        match data.iter().position(|&b| b == b'\n') {
+            Some(end) if end > 0 && data[end - 1] == b'\r' => LineEnding::CrLf,
+            _ => LineEnding::Lf,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::CrLf => "CRLF",
+        }
+    }
+
+    fn as_bytes(self) -> &'static [u8] {
+        match self {
+            LineEnding::Lf => b"\n",
+            LineEnding::CrLf => b"\r\n",
+        }
+    }
+}
 
+/// A run of consecutive lines. Lines of large files stay on disk until they
+/// are shown or edited.
+#[derive(Default)]
+struct Page {
+    /// Where each line starts in the file, or `LOADED` and its index in
+    /// `Buffer::loaded`.
+    lines: Vec<u64>,
+    /// The lines of the page read from disk while it is on screen or close
+    /// to it. Loaded lines are left empty here.
+    cache: OnceCell<Vec<Line>>,
+}
+
+/// The file the lines of a large buffer are read from.
+struct LazySource {
+    /// The file and the position it was read up to.
+    reader: RefCell<(BufReader<File>, u64)>,
+    /// Delivers where lines start while the file is indexed in the
+    /// background, `None` once the whole file was indexed.
+    index: Option<Receiver<Vec<u64>>>,
+    /// How many pages are currently cached.
+    cached: Cell<usize>,
+}
+
+/// The lines changed since the last `Buffer::take_changes`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Changes {
+    /// The changed and inserted lines, where they are now.
+    pub lines: Range<usize>,
+    /// How far the lines after them moved, negative if lines were removed.
+    pub moved: isize,
+}
+
+impl Changes {
+    /// Everything, for new contents.
+    pub fn all() -> Changes {
+        Changes {
+            lines: 0..usize::MAX,
+            moved: 0,
+        }
+    }
+
+    /// Add that line `index` was edited (`inserted` 0), inserted (1) or
+    /// removed (-1).
+    fn add(&mut self, index: usize, inserted: isize) {
+        if *self == Changes::all() {
+            return;
+        }
+        let lines = &mut self.lines;
+        // changed lines below `index` move along
+        if lines.start > index {
+            lines.start = lines.start.saturating_add_signed(inserted);
+        }
+        if lines.end > index {
+            lines.end = lines.end.saturating_add_signed(inserted);
+        }
+        lines.start = lines.start.min(index);
+        lines.end = lines.end.max(index + usize::from(inserted >= 0));
+        self.moved += inserted;
+    }
+}
+
+/// What is derived from the lines and kept up to date with
+/// `Buffer::take_changes`. Each is told about all changes.
+#[derive(Clone, Copy)]
+pub enum Listener {
+    Highlighter,
+    WrappedRows,
+}
+
+/// A buffer represents the contents of a file as a sequence of lines
 pub struct Buffer {
-    pub lines: Vec<Line>,
+    pages: Vec<Page>,
+    /// The index of the first line of each page.
+    page_starts: Vec<usize>,
+    /// The lines that are kept in memory, because they were edited or did
+    /// not come from a large file.
+    loaded: Vec<Line>,
+    /// Slots of `loaded` that can be used again.
+    free: Vec<usize>,
+    source: Option<LazySource>,
+    /// The lines changed since the last `take_changes`, for each listener.
+    changes: [Cell<Option<Changes>>; 2],
 }
 
 fn string_to_line(s: &str) -> Line {
@@ -12,61 +151,426 @@ fn string_to_line(s: &str) -> Line {
 
 impl From<String> for Buffer {
     fn from(s: String) -> Buffer {
-        let lines = s.lines().map(|s| string_to_line(s)).collect::<Vec<Line>>();
-        Buffer { lines: lines }
+        let mut buffer = Buffer::empty_buffer();
+        for line in s.lines() {
+            buffer.add_line(line);
+        }
+        buffer
     }
 }
 
 impl From<Vec<String>> for Buffer {
     fn from(lines: Vec<String>) -> Buffer {
-        Buffer {
-            lines: lines.iter().map(|s| string_to_line(s)).collect(),
+        let mut buffer = Buffer::empty_buffer();
+        for line in &lines {
+            buffer.add_line(line);
+        }
+        buffer
+    }
+}
+
+/// Find the lines in `file` from `offset` on and send where they start in
+/// batches, until the end of the file or until nobody listens anymore.
+fn index_lines(file: File, offset: u64, sender: Sender<Vec<u64>>) -> std::io::Result<()> {
+    let mut reader = BufReader::with_capacity(INDEX_CHUNK_SIZE, file);
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut line_start = offset;
+    let mut pos = offset;
+
+    loop {
+        let chunk = reader.fill_buf()?;
+        if chunk.is_empty() {
+            break;
+        }
+
+        let mut batch = Vec::new();
+        for (i, _) in chunk.iter().enumerate().filter(|(_, b)| **b == b'\n') {
+            batch.push(line_start);
+            line_start = pos + i as u64 + 1;
+        }
+        let len = chunk.len();
+        pos += len as u64;
+        reader.consume(len);
+
+        if !batch.is_empty() && sender.send(batch).is_err() {
+            return Ok(());
+        }
+    }
+
+    // the last line may lack a line break
+    if line_start < pos {
+        let _ = sender.send(vec![line_start]);
+    }
+    Ok(())
+}
+
+/// Decode a line read from disk like `BufRead::lines` would, but without
+/// failing on invalid UTF-8.
+fn bytes_to_line(bytes: &[u8]) -> Line {
+    let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+    String::from_utf8_lossy(bytes).chars().collect()
+}
+
+impl LazySource {
+    fn new(file: File, index: Option<Receiver<Vec<u64>>>) -> LazySource {
+        LazySource {
+            reader: RefCell::new((BufReader::new(file), 0)),
+            index,
+            cached: Cell::new(0),
         }
     }
+
+    /// The bytes of the line starting at `start`, without the line break.
+    fn read_line(&self, start: u64) -> std::io::Result<Vec<u8>> {
+        let (reader, pos) = &mut *self.reader.borrow_mut();
+        // lines are mostly read in order, this keeps what was read ahead
+        reader.seek_relative(start as i64 - *pos as i64)?;
+        *pos = start;
+        let mut data = Vec::new();
+        let read = reader.read_until(b'\n', &mut data);
+        *pos += data.len() as u64;
+        read?;
+        if data.last() == Some(&b'\n') {
+            data.pop();
+        }
+        Ok(data)
+    }
+}
+
+impl Page {
+    fn is_cached(&self) -> bool {
+        self.cache.get().is_some()
+    }
 }
 
 impl Buffer {
     pub fn new() -> Buffer {
-        let mut b = Buffer { lines: Vec::new() };
-        b.lines.push(string_to_line(""));
+        let mut b = Buffer::empty_buffer();
+        b.add_line("");
         b
     }
 
     pub fn empty_buffer() -> Buffer {
-        Buffer { lines: Vec::new() }
+        Buffer {
+            pages: Vec::new(),
+            page_starts: Vec::new(),
+            loaded: Vec::new(),
+            free: Vec::new(),
+            source: None,
+            changes: [
+                Cell::new(Some(Changes::all())),
+                Cell::new(Some(Changes::all())),
+            ],
+        }
+    }
+
+    /// Open a large file without reading it into memory. The lines are
+    /// found by a background thread, `poll_index` picks up its progress.
+    /// Lines are read from disk when they are needed.
+    pub fn open_lazy(path: &Path) -> std::io::Result<Buffer> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let file = File::open(path)?;
+        // a cloned handle would share the read position with `file`
+        let index_file = File::open(path)?;
+        std::thread::spawn(move || index_lines(index_file, 0, sender));
+
+        let mut buffer = Buffer::empty_buffer();
+        buffer.source = Some(LazySource::new(file, Some(receiver)));
+
+        // wait for the first lines, so there is something to show right away
+        if let Some(batch) = buffer.index().and_then(|index| index.recv().ok()) {
+            buffer.append_on_disk(batch);
+        }
+        buffer.poll_index();
+        Ok(buffer)
+    }
+
+    fn index(&self) -> Option<&Receiver<Vec<u64>>> {
+        self.source.as_ref().and_then(|s| s.index.as_ref())
+    }
+
+    /// Add lines of the file at the end, filling up the last page first.
+    fn append_on_disk(&mut self, starts: Vec<u64>) {
+        let mut starts = starts.into_iter().peekable();
+        while starts.peek().is_some() {
+            let num_lines = self.num_lines();
+            let page = match self.pages.last_mut() {
+                Some(page) if page.lines.len() < PAGE_LINES => page,
+                _ => {
+                    self.page_starts.push(num_lines);
+                    self.pages.push(Page::default());
+                    self.pages.last_mut().unwrap()
+                }
+            };
+            // the cache would miss the new lines
+            if page.cache.take().is_some()
+                && let Some(source) = &self.source
+            {
+                source.cached.set(source.cached.get() - 1);
+            }
+            let room = PAGE_LINES - page.lines.len();
+            page.lines.extend(starts.by_ref().take(room));
+        }
+    }
+
+    /// Add the lines the background indexer found since the last call.
+    /// Returns true if there were any.
+    pub fn poll_index(&mut self) -> bool {
+        let mut found = false;
+        while let Some(index) = self.index() {
+            match index.try_recv() {
+                Ok(batch) => {
+                    self.append_on_disk(batch);
+                    found = true;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    if let Some(source) = &mut self.source {
+                        source.index = None;
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    /// Wait until the whole file was indexed.
+    pub fn finish_index(&mut self) {
+        while let Some(batch) = self.index().map(|index| index.recv()) {
+            match batch {
+                Ok(batch) => self.append_on_disk(batch),
+                Err(_) => {
+                    if let Some(source) = &mut self.source {
+                        source.index = None;
+                    }
+                }
+            }
+        }
+        if self.num_lines() == 0 {
+            self.add_line("");
+        }
+    }
+
+    /// True while the lines of a large file are still being found.
+    pub fn is_indexing(&self) -> bool {
+        self.index().is_some()
+    }
+
+    /// True if the lines are read from disk on demand.
+    pub fn is_lazy(&self) -> bool {
+        self.source.is_some()
+    }
+
+    /// Forget the lines read from disk that are not in one of the `keep`
+    /// ranges, once there are too many of them.
+    pub fn trim_cache(&mut self, keep: &[Range<usize>]) {
+        let Some(source) = &self.source else {
+            return;
+        };
+        if source.cached.get() <= MAX_CACHED_PAGES {
+            return;
+        }
+
+        for (page, &first) in self.pages.iter_mut().zip(&self.page_starts) {
+            let lines = first..first + page.lines.len();
+            if !keep
+                .iter()
+                .any(|keep| lines.start < keep.end && keep.start < lines.end)
+            {
+                page.cache.take();
+            }
+        }
+        let cached = self.pages.iter().filter(|page| page.is_cached()).count();
+        source.cached.set(cached);
     }
 
     pub fn num_lines(&self) -> usize {
-        self.lines.len()
+        match (self.page_starts.last(), self.pages.last()) {
+            (Some(first), Some(page)) => first + page.lines.len(),
+            _ => 0,
+        }
+    }
+
+    /// The page line `index` is on and its position on the page.
+    fn locate(&self, index: usize) -> Option<(usize, usize)> {
+        if index >= self.num_lines() {
+            return None;
+        }
+        let page = self.page_starts.partition_point(|&first| first <= index) - 1;
+        Some((page, index - self.page_starts[page]))
     }
 
     pub fn line_at(&self, index: usize) -> Option<&Line> {
-        self.lines.get(index)
+        let (page, i) = self.locate(index)?;
+        let page = &self.pages[page];
+        let entry = page.lines[i];
+        if entry & LOADED != 0 {
+            return self.loaded.get((entry & !LOADED) as usize);
+        }
+
+        let source = self.source.as_ref()?;
+        let cache = page.cache.get_or_init(|| {
+            source.cached.set(source.cached.get() + 1);
+            page.lines
+                .iter()
+                .map(|&entry| match entry & LOADED {
+                    0 => source
+                        .read_line(entry)
+                        .map(|data| bytes_to_line(&data))
+                        // a file that shrunk behind our back shows empty lines
+                        .unwrap_or_default(),
+                    _ => Line::new(),
+                })
+                .collect()
+        });
+        cache.get(i)
+    }
+
+    /// The lines starting at `index`.
+    pub fn lines_from(&self, index: usize) -> impl Iterator<Item = &Line> {
+        (index..self.num_lines()).filter_map(|i| self.line_at(i))
     }
 
     pub fn line_at_mut(&mut self, index: usize) -> Option<&mut Line> {
-        self.lines.get_mut(index)
+        let (page, i) = self.locate(index)?;
+        self.changed(index, 0);
+        let mut entry = self.pages[page].lines[i];
+        // an edited line has to stay in memory
+        if entry & LOADED == 0 {
+            let line = self.line_at(index).cloned().unwrap_or_default();
+            entry = self.store(line);
+            let page = &mut self.pages[page];
+            page.lines[i] = entry;
+            if let Some(cache) = page.cache.get_mut() {
+                cache[i] = Line::new();
+            }
+        }
+        self.loaded.get_mut((entry & !LOADED) as usize)
+    }
+
+    /// Note that line `index` was edited (`inserted` 0), inserted (1) or
+    /// removed (-1).
+    fn changed(&self, index: usize, inserted: isize) {
+        for slot in &self.changes {
+            let mut changes = slot.take().unwrap_or(Changes {
+                lines: index..index,
+                moved: 0,
+            });
+            changes.add(index, inserted);
+            slot.set(Some(changes));
+        }
     }
 
+    /// The lines that changed since `listener` last asked, if any.
+    pub fn take_changes(&self, listener: Listener) -> Option<Changes> {
+        self.changes[listener as usize].take()
+    }
+
+    /// Keep `line` in memory, returning the entry for it.
+    fn store(&mut self, line: Line) -> u64 {
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.loaded[id] = line;
+                id
+            }
+            None => {
+                self.loaded.push(line);
+                self.loaded.len() - 1
+            }
+        };
+        LOADED | id as u64
+    }
+
+    #[allow(dead_code)]
     pub fn insert_line_at(&mut self, index: usize, line: Line) {
-        if index > self.lines.len() {
-            self.lines.push(line);
-            return;
+        let index = index.min(self.num_lines());
+        self.changed(index, 1);
+        let entry = self.store(line);
+
+        if self.pages.is_empty() {
+            self.pages.push(Page::default());
+            self.page_starts.push(0);
         }
-        self.lines.insert(index, line);
+        // a line after the last one goes to the last page
+        let (page, i) = self.locate(index).unwrap_or_else(|| {
+            let last = self.pages.len() - 1;
+            (last, index - self.page_starts[last])
+        });
+        self.pages[page].lines.insert(i, entry);
+        if let Some(cache) = self.pages[page].cache.get_mut() {
+            cache.insert(i, Line::new());
+        }
+        for first in &mut self.page_starts[page + 1..] {
+            *first += 1;
+        }
+
+        if self.pages[page].lines.len() >= 2 * PAGE_LINES {
+            self.split_page(page);
+        }
+    }
+
+    fn split_page(&mut self, page: usize) {
+        let lines = self.pages[page].lines.split_off(PAGE_LINES);
+        let cache = match self.pages[page].cache.get_mut() {
+            Some(cache) => {
+                if let Some(source) = &self.source {
+                    source.cached.set(source.cached.get() + 1);
+                }
+                OnceCell::from(cache.split_off(PAGE_LINES))
+            }
+            None => OnceCell::new(),
+        };
+        self.pages.insert(page + 1, Page { lines, cache });
+        self.page_starts
+            .insert(page + 1, self.page_starts[page] + PAGE_LINES);
     }
 
     pub fn remove_line_at(&mut self, index: usize) {
-        self.lines.remove(index);
+        let Some((page, i)) = self.locate(index) else {
+            return;
+        };
+        self.changed(index, -1);
+        let entry = self.pages[page].lines.remove(i);
+        if entry & LOADED != 0 {
+            let id = (entry & !LOADED) as usize;
+            self.loaded[id] = Line::new();
+            self.free.push(id);
+        }
+        if let Some(cache) = self.pages[page].cache.get_mut() {
+            cache.remove(i);
+        }
+        for first in &mut self.page_starts[page + 1..] {
+            *first -= 1;
+        }
+
+        if self.pages[page].lines.is_empty() {
+            if self.pages.remove(page).is_cached()
+                && let Some(source) = &self.source
+            {
+                source.cached.set(source.cached.get() - 1);
+            }
+            self.page_starts.remove(page);
+        }
     }
 
-    pub fn break_line_at(&mut self, line_index: usize, char_index: usize) {
-        let line = self.lines.remove(line_index);
-        let (left, right) = line.split_at(char_index);
-        let new_left = left.to_vec();
+    /// Take line `index` out of the buffer.
+    fn take_line(&mut self, index: usize) -> Line {
+        let line = match self.locate(index) {
+            Some((page, i)) if self.pages[page].lines[i] & LOADED != 0 => {
+                let id = (self.pages[page].lines[i] & !LOADED) as usize;
+                std::mem::take(&mut self.loaded[id])
+            }
+            _ => self.line_at(index).cloned().unwrap_or_default(),
+        };
+        self.remove_line_at(index);
+        line
+    }
 
-        self.lines.insert(line_index, new_left);
-        self.lines.insert(line_index + 1, right.to_vec());
+    pub fn break_line_at(&mut self, line_index: usize, char_index: usize) {
+        let mut left = self.take_line(line_index);
+        let right = left.split_off(char_index);
+        self.insert_line_at(line_index, left);
+        self.insert_line_at(line_index + 1, right);
     }
 
     pub fn line_char_length(&self, cursor_line: usize) -> Option<usize> {
@@ -77,15 +581,56 @@ impl Buffer {
     }
 
     pub fn merge_lines(&mut self, cursor_line_1: usize, cursor_line_2: usize) {
-        let mut merged_line = self.lines[cursor_line_1].clone();
-        merged_line.extend(self.lines[cursor_line_2].clone());
-        self.lines.remove(cursor_line_1);
-        self.lines.remove(cursor_line_1); // we remove line index 1 twice, since the original second line is now at index 1
-        self.lines.insert(cursor_line_1, merged_line);
+        // the second line first, so the first one stays where it is
+        let second_line = self.take_line(cursor_line_2);
+        let mut merged_line = self.take_line(cursor_line_1);
+        merged_line.extend(second_line);
+        self.insert_line_at(cursor_line_1, merged_line);
     }
 
     pub(crate) fn add_line(&mut self, line: &str) {
-        self.lines.push(line.chars().collect());
+        self.insert_line_at(self.num_lines(), string_to_line(line));
+    }
+
+    /// Write all lines to `out`, each followed by `ending`. Lines that are
+    /// still on disk are copied byte for byte. Returns where each line
+    /// starts in the output.
+    pub fn write_to(&self, out: &mut impl Write, ending: LineEnding) -> std::io::Result<Vec<u64>> {
+        let mut starts = Vec::with_capacity(self.num_lines());
+        let mut pos = 0;
+
+        for &entry in self.pages.iter().flat_map(|page| &page.lines) {
+            starts.push(pos);
+            let data = match (entry & LOADED, &self.source) {
+                (0, Some(source)) => {
+                    let mut data = source.read_line(entry)?;
+                    // the line break of a CRLF file is read with the line
+                    if data.last() == Some(&b'\r') {
+                        data.pop();
+                    }
+                    data
+                }
+                (0, None) => Vec::new(),
+                _ => {
+                    let line = &self.loaded[(entry & !LOADED) as usize];
+                    line.iter().collect::<String>().into_bytes()
+                }
+            };
+            out.write_all(&data)?;
+            out.write_all(ending.as_bytes())?;
+            pos += (data.len() + ending.as_bytes().len()) as u64;
+        }
+        Ok(starts)
+    }
+
+    /// Read the lines from `file` from now on, after the buffer was written
+    /// to it by `write_to` with the result `starts`. Frees all loaded lines.
+    pub fn reattach(&mut self, file: File, starts: &[u64]) -> std::io::Result<()> {
+        let mut buffer = Buffer::empty_buffer();
+        buffer.source = Some(LazySource::new(file, None));
+        buffer.append_on_disk(starts.to_vec());
+        *self = buffer;
+        Ok(())
     }
 }
 
@@ -98,6 +643,7 @@ impl Default for Buffer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testutil::TempDir;
 
     fn assert_line_equals(l1: &Line, l2: &str) {
         assert_eq!(l1.iter().collect::<String>().as_str(), l2);
@@ -107,7 +653,7 @@ mod tests {
         if let Some(l2) = l2 {
             assert_line_equals(l1.unwrap(), l2);
         } else {
-            assert!(false);
+            panic!("expected a line");
         }
     }
 
@@ -132,9 +678,9 @@ mod tests {
     #[test]
     pub fn can_get_line_at() {
         let b = Buffer::from("a\nb\nc".to_string());
-        assert_line_equals_optional(b.line_at(0), Some(&"a".to_string()));
-        assert_line_equals_optional(b.line_at(1), Some(&"b".to_string()));
-        assert_line_equals_optional(b.line_at(2), Some(&"c".to_string()));
+        assert_line_equals_optional(b.line_at(0), Some("a"));
+        assert_line_equals_optional(b.line_at(1), Some("b"));
+        assert_line_equals_optional(b.line_at(2), Some("c"));
     }
 
     #[test]
@@ -147,36 +693,206 @@ mod tests {
     pub fn can_insert_line_at() {
         let mut b = Buffer::from("a\nb\nc".to_string());
         b.insert_line_at(1, string_to_line("d"));
-        assert_line_equals_optional(b.line_at(0), Some(&"a".to_string()));
-        assert_line_equals_optional(b.line_at(1), Some(&"d".to_string()));
-        assert_line_equals_optional(b.line_at(2), Some(&"b".to_string()));
-        assert_line_equals_optional(b.line_at(3), Some(&"c".to_string()));
+        assert_line_equals_optional(b.line_at(0), Some("a"));
+        assert_line_equals_optional(b.line_at(1), Some("d"));
+        assert_line_equals_optional(b.line_at(2), Some("b"));
+        assert_line_equals_optional(b.line_at(3), Some("c"));
+    }
+
+    #[test]
+    pub fn changes_are_tracked_from_the_first_changed_line() {
+        let mut b = Buffer::from("a\nb\nc\nd".to_string());
+        assert_eq!(b.take_changes(Listener::Highlighter), Some(Changes::all()));
+        assert_eq!(b.take_changes(Listener::Highlighter), None);
+        assert_eq!(b.take_changes(Listener::WrappedRows), Some(Changes::all()));
+
+        b.line_at_mut(2).unwrap().push('!');
+        b.merge_lines(1, 2);
+        // "bc" replaced two lines, "d" moved up
+        let changes = Changes {
+            lines: 1..2,
+            moved: -1,
+        };
+        assert_eq!(b.take_changes(Listener::Highlighter), Some(changes.clone()));
+        // the other listener still collects them
+        b.line_at_mut(2).unwrap().push('!');
+        assert_eq!(
+            b.take_changes(Listener::WrappedRows),
+            Some(Changes {
+                lines: 1..3,
+                moved: -1,
+            })
+        );
     }
 
     #[test]
     pub fn can_remove_line_at() {
         let mut b = Buffer::from("a\nb\nc".to_string());
         b.remove_line_at(1);
-        assert_line_equals_optional(b.line_at(0), Some(&"a".to_string()));
-        assert_line_equals_optional(b.line_at(1), Some(&"c".to_string()));
+        assert_line_equals_optional(b.line_at(0), Some("a"));
+        assert_line_equals_optional(b.line_at(1), Some("c"));
     }
 
     #[test]
     pub fn can_change_line_at() {
         let mut b = Buffer::from("a\nb\nc".to_string());
-        b.line_at_mut(1).map(|l| *l = string_to_line("d"));
-        assert_line_equals_optional(b.line_at(0), Some(&"a".to_string()));
-        assert_line_equals_optional(b.line_at(1), Some(&"d".to_string()));
-        assert_line_equals_optional(b.line_at(2), Some(&"c".to_string()));
+        if let Some(l) = b.line_at_mut(1) {
+            *l = string_to_line("d");
+        }
+        assert_line_equals_optional(b.line_at(0), Some("a"));
+        assert_line_equals_optional(b.line_at(1), Some("d"));
+        assert_line_equals_optional(b.line_at(2), Some("c"));
     }
 
     #[test]
     pub fn can_break_line_at() {
         let mut b = Buffer::from("a\nboo\nc".to_string());
         b.break_line_at(1, 1);
-        assert_line_equals_optional(b.line_at(0), Some(&"a".to_string()));
-        assert_line_equals_optional(b.line_at(1), Some(&"b".to_string()));
-        assert_line_equals_optional(b.line_at(2), Some(&"oo".to_string()));
-        assert_line_equals_optional(b.line_at(3), Some(&"c".to_string()));
+        assert_line_equals_optional(b.line_at(0), Some("a"));
+        assert_line_equals_optional(b.line_at(1), Some("b"));
+        assert_line_equals_optional(b.line_at(2), Some("oo"));
+        assert_line_equals_optional(b.line_at(3), Some("c"));
+    }
+
+    fn write_lazy_file(dir: &TempDir, contents: &str) -> std::path::PathBuf {
+        let path = dir.join("lazy");
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    pub fn lazy_buffer_reads_lines_on_demand() {
+        let dir = TempDir::new("lazy_read");
+        let path = write_lazy_file(&dir, "one\r\ntwo\n\nfour");
+        let mut b = Buffer::open_lazy(&path).unwrap();
+        b.finish_index();
+        assert!(!b.is_indexing());
+        assert_eq!(b.num_lines(), 4);
+        assert_line_equals_optional(b.line_at(0), Some("one"));
+        assert_line_equals_optional(b.line_at(2), Some(""));
+        assert_line_equals_optional(b.line_at(3), Some("four"));
+    }
+
+    #[test]
+    pub fn lazy_buffer_can_be_edited_and_written() {
+        let dir = TempDir::new("lazy_edit");
+        let path = write_lazy_file(&dir, "a\nboo\nc\n");
+        let mut b = Buffer::open_lazy(&path).unwrap();
+        b.finish_index();
+        b.break_line_at(1, 1);
+        b.line_at_mut(0).unwrap().push('!');
+        b.merge_lines(2, 3);
+
+        let mut out = Vec::new();
+        let starts = b.write_to(&mut out, LineEnding::Lf).unwrap();
+        assert_eq!(String::from_utf8(out.clone()).unwrap(), "a!\nb\nooc\n");
+        assert_eq!(starts, vec![0, 3, 5]);
+
+        // the written file becomes the new source of the lines
+        std::fs::write(&path, &out).unwrap();
+        b.reattach(File::open(&path).unwrap(), &starts).unwrap();
+        assert_line_equals_optional(b.line_at(2), Some("ooc"));
+    }
+
+    #[test]
+    pub fn only_the_pages_of_the_lines_taken_are_read() {
+        let contents: String = (0..4 * PAGE_LINES).map(|i| format!("{}\n", i)).collect();
+        let dir = TempDir::new("lazy_take");
+        let path = write_lazy_file(&dir, &contents);
+        let mut b = Buffer::open_lazy(&path).unwrap();
+        b.finish_index();
+        // the lines cross from the second page to the third
+        assert_eq!(b.lines_from(2 * PAGE_LINES - 10).take(20).count(), 20);
+        assert_eq!(b.source.as_ref().unwrap().cached.get(), 2);
+        assert_line_equals_optional(
+            b.line_at(2 * PAGE_LINES),
+            Some(&(2 * PAGE_LINES).to_string()),
+        );
+    }
+
+    #[test]
+    pub fn unedited_lines_are_written_byte_for_byte() {
+        let dir = TempDir::new("lazy_bytes");
+        let path = dir.join("lazy");
+        std::fs::write(&path, b"caf\xe9\nb\n").unwrap();
+        let mut b = Buffer::open_lazy(&path).unwrap();
+        b.finish_index();
+        assert_line_equals_optional(b.line_at(0), Some("caf\u{fffd}"));
+        b.line_at_mut(1).unwrap().push('!');
+
+        let mut out = Vec::new();
+        b.write_to(&mut out, LineEnding::Lf).unwrap();
+        assert_eq!(out, b"caf\xe9\nb!\n");
+    }
+
+    #[test]
+    pub fn edited_lines_are_kept_beside_the_file() {
+        let contents: String = (0..3 * PAGE_LINES).map(|i| format!("{}\n", i)).collect();
+        let dir = TempDir::new("lazy_side");
+        let path = write_lazy_file(&dir, &contents);
+        let mut b = Buffer::open_lazy(&path).unwrap();
+        b.finish_index();
+        let last_page = b.pages[2].lines.clone();
+
+        b.line_at_mut(1).unwrap().push('!');
+        b.break_line_at(0, 0);
+        b.remove_line_at(3);
+        // "1!" and the "0" that was broken off an empty line
+        assert_eq!(b.loaded.iter().filter(|line| !line.is_empty()).count(), 2);
+        // the other pages were not touched
+        assert_eq!(b.pages[2].lines, last_page);
+        assert_eq!(b.page_starts, [0, PAGE_LINES, 2 * PAGE_LINES]);
+        assert_line_equals_optional(b.line_at(1), Some("0"));
+        assert_line_equals_optional(b.line_at(2), Some("1!"));
+        assert_line_equals_optional(b.line_at(3), Some("3"));
+    }
+
+    #[test]
+    pub fn full_pages_are_split() {
+        let mut b = Buffer::new();
+        for i in 0..3 * PAGE_LINES {
+            b.insert_line_at(i, string_to_line(&i.to_string()));
+        }
+        assert_eq!(b.num_lines(), 3 * PAGE_LINES + 1);
+        assert!(b.pages.iter().all(|page| page.lines.len() < 2 * PAGE_LINES));
+        for i in (0..3 * PAGE_LINES).step_by(97) {
+            assert_line_equals_optional(b.line_at(i), Some(&i.to_string()));
+        }
+
+        while b.num_lines() > 1 {
+            b.remove_line_at(0);
+        }
+        assert_eq!(b.pages.len(), 1);
+        assert_line_equals_optional(b.line_at(0), Some(""));
+    }
+
+    #[test]
+    pub fn lazy_crlf_lines_keep_their_line_ending() {
+        let dir = TempDir::new("lazy_crlf");
+        let path = write_lazy_file(&dir, "a\r\nb\r\n");
+        let mut b = Buffer::open_lazy(&path).unwrap();
+        b.finish_index();
+        assert_line_equals_optional(b.line_at(1), Some("b"));
+        b.line_at_mut(0).unwrap().push('!');
+
+        let mut out = Vec::new();
+        let starts = b.write_to(&mut out, LineEnding::CrLf).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "a!\r\nb\r\n");
+        assert_eq!(starts, vec![0, 4]);
+    }
+
+    #[test]
+    pub fn cache_is_trimmed_outside_the_view() {
+        let num_lines = (MAX_CACHED_PAGES + 1) * PAGE_LINES;
+        let contents: String = (0..num_lines).map(|i| format!("{}\n", i)).collect();
+        let dir = TempDir::new("lazy_trim");
+        let path = write_lazy_file(&dir, &contents);
+        let mut b = Buffer::open_lazy(&path).unwrap();
+        b.finish_index();
+        assert_eq!(b.lines_from(0).count(), num_lines);
+
+        b.trim_cache(&[5..10, num_lines - 3..num_lines]);
+        assert_eq!(b.source.as_ref().unwrap().cached.get(), 2);
+        assert_line_equals_optional(b.line_at(num_lines - 1), Some(&(num_lines - 1).to_string()));
     }
 }
@@ -1,15 +1,62 @@
-use std::io::{BufRead, BufReader, Read};
+use std::{
+    cell::{Ref, RefCell},
+    io::{BufRead, BufReader, BufWriter, Read, Seek, Write},
+    path::{Path, PathBuf},
+};
 
-use crate::buffer::Buffer;
+use crate::{
+    buffer::{Buffer, Changes, LARGE_FILE_SIZE, LineEnding, Listener},
+    columns::{WrappedLine, char_width, display_column, wrap_line},
+    diff::diff_lines,
+    diskstate::DiskState,
+    highlight::Highlighter,
+    pathinput::absolute_path,
+};
+
+/// The screen rows of each line with soft wrap, for the scrollbar.
+#[derive(Default)]
+pub struct RowCache {
+    /// The text width the rows were counted for.
+    width: u16,
+    rows: Vec<u32>,
+}
 
 #[derive(Default)]
 pub struct BufferEntry {
+    /// A name chosen by the user. If empty, the name is derived from `path`.
     pub name: String,
+    /// The absolute path the buffer is saved to, `None` for untitled buffers.
+    pub path: Option<PathBuf>,
     pub buffer: Buffer,
     pub cursor_line: usize,
     pub cursor_position: usize,
     pub modified: bool,
+    /// Set for files that can not be written and on request, refuses edits.
+    pub read_only: bool,
     pub scroll_offset: usize,
+    /// The first screen column shown of every line, for lines wider than
+    /// the screen.
+    pub horizontal_offset: usize,
+    /// Long lines flow onto further screen rows instead of scrolling
+    /// sideways.
+    pub soft_wrap: bool,
+    /// Tabs, trailing spaces and invisible characters are shown as glyphs.
+    pub show_whitespace: bool,
+    /// When the buffer was last the current one, for listing buffers by use.
+    pub last_used: u64,
+    /// The columns available for the text, as of the last frame.
+    pub text_width: u16,
+    /// The state of the file when it was last loaded or saved.
+    pub disk_state: Option<DiskState>,
+    /// The contents of a binary file, edited in the hex view instead of
+    /// `buffer`. `cursor_position` is then the offset of the selected byte
+    /// and `scroll_offset` the first row shown.
+    pub binary: Option<Vec<u8>>,
+    pub highlighter: Highlighter,
+    /// Taken from the file when it is loaded and kept when it is saved.
+    pub line_ending: LineEnding,
+    /// See `wrapped_rows`.
+    pub row_cache: RefCell<RowCache>,
 
     pub selection_start: Option<(usize, usize)>, // line + char
     pub selection_end: Option<(usize, usize)>,   // line + char
@@ -19,11 +66,23 @@ impl BufferEntry {
     fn empty() -> BufferEntry {
         BufferEntry {
             name: String::new(),
+            path: None,
             buffer: Buffer::empty_buffer(),
             cursor_line: 0,
             cursor_position: 0,
             modified: false,
+            read_only: false,
             scroll_offset: 0,
+            horizontal_offset: 0,
+            soft_wrap: false,
+            show_whitespace: false,
+            last_used: 0,
+            text_width: 0,
+            disk_state: None,
+            binary: None,
+            highlighter: Highlighter::default(),
+            line_ending: LineEnding::Lf,
+            row_cache: RefCell::default(),
             selection_start: None,
             selection_end: None,
         }
@@ -38,27 +97,24 @@ impl BufferEntry {
         let current_line = self.buffer.line_at(self.cursor_line).unwrap();
 
         // if the cursor is already at the end of the line, do nothing
-        if self.cursor_position >= current_line.len() {
-            if self.cursor_line <= self.buffer.num_lines() - 1 {
-                self.cursor_line += 1;
-                self.cursor_position = 0;
-                return;
-            }
+        if self.cursor_position >= current_line.len() && self.cursor_line < self.buffer.num_lines()
+        {
+            self.cursor_line += 1;
+            self.cursor_position = 0;
+            return;
         }
 
         // start position is the next non whitespace character:
         let mut pos = self.cursor_position;
         while pos < current_line.len() {
-            if !current_line.iter().nth(pos).unwrap().is_whitespace() {
+            if !current_line.get(pos).unwrap().is_whitespace() {
                 break;
             }
             pos += 1;
         }
-        self.cursor_position = pos;
 
-        let mut pos = self.cursor_position;
         while pos < current_line.len() {
-            if current_line.iter().nth(pos).unwrap().is_whitespace() {
+            if current_line.get(pos).unwrap().is_whitespace() {
                 break;
             }
             pos += 1;
@@ -70,27 +126,23 @@ impl BufferEntry {
         let current_line = self.buffer.line_at(self.cursor_line).unwrap();
 
         // if the cursor is already at the start of the line, do nothing
-        if self.cursor_position == 0 {
-            if self.cursor_line > 0 {
-                self.cursor_line -= 1;
-                self.cursor_position = self.buffer.line_char_length(self.cursor_line).unwrap();
-                return;
-            }
+        if self.cursor_position == 0 && self.cursor_line > 0 {
+            self.cursor_line -= 1;
+            self.cursor_position = self.buffer.line_char_length(self.cursor_line).unwrap();
+            return;
         }
 
         // start position is the next non whitespace character:
         let mut pos = self.cursor_position;
         while pos > 0 {
-            if !current_line.iter().nth(pos - 1).unwrap().is_whitespace() {
+            if !current_line.get(pos - 1).unwrap().is_whitespace() {
                 break;
             }
             pos -= 1;
         }
-        self.cursor_position = pos;
 
-        let mut pos = self.cursor_position;
         while pos > 0 {
-            if current_line.iter().nth(pos - 1).unwrap().is_whitespace() {
+            if current_line.get(pos - 1).unwrap().is_whitespace() {
                 break;
             }
             pos -= 1;
@@ -103,7 +155,7 @@ impl BufferEntry {
     }
 
     pub fn goto_line_end(&mut self) {
-        if let Some(_) = self.buffer.line_at(self.cursor_line) {
+        if self.buffer.line_at(self.cursor_line).is_some() {
             self.cursor_position = self.buffer.line_char_length(self.cursor_line).unwrap();
         }
     }
@@ -179,7 +231,10 @@ impl BufferEntry {
     /// the line. Ideally, this would move to the closest grapheme given the
     /// previous cursor position.
     pub fn move_cursor_up(&mut self, screen_height: u16) {
-        if self.cursor_line > 0 {
+        if self.soft_wrap {
+            self.move_cursor_by_row(true);
+            self.update_scroll_position(screen_height);
+        } else if self.cursor_line > 0 {
             self.cursor_line -= 1;
             self.update_scroll_position(screen_height);
         }
@@ -189,7 +244,10 @@ impl BufferEntry {
     ///
     /// If the cursor is already at the last line, this does nothing.
     pub fn move_cursor_down(&mut self, screen_height: u16) {
-        if self.cursor_line < self.buffer.num_lines() - 1 {
+        if self.soft_wrap {
+            self.move_cursor_by_row(false);
+            self.update_scroll_position(screen_height);
+        } else if self.cursor_line < self.buffer.num_lines() - 1 {
             self.cursor_line += 1;
             self.update_scroll_position(screen_height);
         }
@@ -210,19 +268,186 @@ impl BufferEntry {
             self.cursor_line = self.buffer.num_lines() - 1;
         }
 
-        let on_screen_cursor_y = self.cursor_line as i32 - self.scroll_offset as i32;
-        if on_screen_cursor_y > screen_height as i32 {
-            self.scroll_offset = (on_screen_cursor_y - screen_height as i32) as usize;
+        if let Some(pos) = self.buffer.line_char_length(self.cursor_line)
+            && self.cursor_position > pos
+        {
+            self.goto_line_end();
         }
-        let on_screen_cursor_y = self.cursor_line as i32 - self.scroll_offset as i32;
-        if on_screen_cursor_y < 0 {
-            self.scroll_offset = 0;
+
+        if self.soft_wrap {
+            self.update_wrapped_scroll_position(screen_height);
+            return;
         }
 
-        if let Some(pos) = self.buffer.line_char_length(self.cursor_line) {
-            if self.cursor_position > pos {
-                self.goto_line_end();
+        // the cursor goes on the last of the `screen_height` rows at most
+        if self.cursor_line >= self.scroll_offset + screen_height as usize {
+            self.scroll_offset = (self.cursor_line + 1).saturating_sub(screen_height as usize);
+        }
+        if self.cursor_line < self.scroll_offset {
+            self.scroll_offset = self.cursor_line;
+        }
+    }
+
+    /// Like `update_scroll_position`, but counting the screen rows of
+    /// wrapped lines instead of lines.
+    fn update_wrapped_scroll_position(&mut self, screen_height: u16) {
+        if self.cursor_line < self.scroll_offset {
+            self.scroll_offset = self.cursor_line;
+        }
+
+        // find the topmost line that still shows the cursor row
+        let cursor_row = self
+            .wrapped_line(self.cursor_line)
+            .row_of(self.cursor_position);
+        let mut rows_above = (screen_height as usize).saturating_sub(cursor_row + 1);
+        let mut top = self.cursor_line;
+        while top > self.scroll_offset {
+            let rows = self.wrapped_line(top - 1).rows.len();
+            if rows > rows_above {
+                break;
             }
+            rows_above -= rows;
+            top -= 1;
+        }
+        self.scroll_offset = top;
+    }
+
+    /// The screen rows of the line at `index`, a single one unless soft
+    /// wrap is on.
+    pub fn wrapped_line(&self, index: usize) -> WrappedLine {
+        self.wrapped_line_in(index, self.text_width)
+    }
+
+    /// The screen rows of each line with soft wrap, updated for the lines
+    /// that changed since the last call. `None` without soft wrap and for
+    /// large files, wrapping all of those would read the whole file.
+    pub fn wrapped_rows(&self) -> Option<Ref<'_, [u32]>> {
+        if !self.soft_wrap || self.buffer.is_lazy() {
+            return None;
+        }
+        let mut cache = self.row_cache.borrow_mut();
+        let mut changes = self.buffer.take_changes(Listener::WrappedRows);
+        let num_lines = self.buffer.num_lines();
+        if cache.width != self.text_width {
+            cache.width = self.text_width;
+            changes = Some(Changes::all());
+        }
+
+        if let Some(changes) = changes {
+            let rows_of = |index| self.wrapped_line(index).rows.len() as u32;
+            // the lines from `changes.lines.end` on were there before, moved
+            let old_end = changes
+                .lines
+                .end
+                .saturating_add_signed(-changes.moved)
+                .min(cache.rows.len());
+            let start = changes.lines.start.min(old_end);
+            let new_end = changes.lines.end.min(num_lines);
+            cache
+                .rows
+                .splice(start..old_end, (start..new_end).map(rows_of));
+            if cache.rows.len() != num_lines {
+                cache.rows = (0..num_lines).map(rows_of).collect();
+            }
+        }
+        drop(cache);
+        Some(Ref::map(self.row_cache.borrow(), |cache| {
+            cache.rows.as_slice()
+        }))
+    }
+
+    /// Like `wrapped_line`, for a view `text_width` columns wide.
+    pub fn wrapped_line_in(&self, index: usize, text_width: u16) -> WrappedLine {
+        match self.buffer.line_at(index) {
+            // the last column is left free for the wrap indicator
+            Some(line) if self.soft_wrap => {
+                wrap_line(line, (text_width as usize).saturating_sub(1))
+            }
+            _ => WrappedLine::unwrapped(),
+        }
+    }
+
+    /// Move the cursor to the screen row above or below, keeping its
+    /// column on screen where possible.
+    fn move_cursor_by_row(&mut self, up: bool) {
+        let Some(line) = self.buffer.line_at(self.cursor_line) else {
+            return;
+        };
+        let wrapped = self.wrapped_line(self.cursor_line);
+        let row = wrapped.row_of(self.cursor_position);
+        let screen_x = wrapped.row_indent(row) + display_column(line, self.cursor_position)
+            - display_column(line, wrapped.rows[row]);
+
+        let (target_line, target_row) = if up {
+            if row > 0 {
+                (self.cursor_line, row - 1)
+            } else if self.cursor_line > 0 {
+                let above = self.cursor_line - 1;
+                (above, self.wrapped_line(above).rows.len() - 1)
+            } else {
+                return;
+            }
+        } else if row + 1 < wrapped.rows.len() {
+            (self.cursor_line, row + 1)
+        } else if self.cursor_line + 1 < self.buffer.num_lines() {
+            (self.cursor_line + 1, 0)
+        } else {
+            return;
+        };
+
+        let Some(line) = self.buffer.line_at(target_line) else {
+            return;
+        };
+        let wrapped = self.wrapped_line(target_line);
+        let start = wrapped.rows[target_row];
+        let end = wrapped.row_end(target_row, line.len());
+        let target_column =
+            display_column(line, start) + screen_x.saturating_sub(wrapped.row_indent(target_row));
+        // the end of a row that continues belongs to the next row
+        let last = if end < line.len() { end - 1 } else { end };
+        let position = (start..last)
+            .find(|i| display_column(line, i + 1) > target_column)
+            .unwrap_or(last);
+
+        self.cursor_line = target_line;
+        self.cursor_position = position;
+    }
+
+    /// Adapt to the room on screen, `text_width` columns and rows up to
+    /// `screen_height`, and scroll so the cursor stays visible.
+    pub fn fit_to_view(&mut self, text_width: u16, screen_height: u16) {
+        self.text_width = text_width;
+        self.update_scroll_position(screen_height);
+        if self.soft_wrap {
+            self.horizontal_offset = 0;
+        } else {
+            self.scroll_to_cursor_column();
+        }
+    }
+
+    /// Scroll sideways so the cursor is within `text_width` columns. The
+    /// first and last column are kept free for the indicators of a line
+    /// continuing off-screen.
+    fn scroll_to_cursor_column(&mut self) {
+        let Some(line) = self.buffer.line_at(self.cursor_line) else {
+            return;
+        };
+        let column = display_column(line, self.cursor_position);
+        let cursor_width = line
+            .get(self.cursor_position)
+            .map_or(1, |c| char_width(*c, column).max(1));
+
+        let width = self.text_width as usize;
+        if width < 4 {
+            self.horizontal_offset = column;
+            return;
+        }
+        let first_free = self.horizontal_offset + usize::from(self.horizontal_offset > 0);
+        if column < first_free {
+            self.horizontal_offset = column.saturating_sub(1);
+        }
+        if column + cursor_width > self.horizontal_offset + width - 1 {
+            self.horizontal_offset = column + cursor_width + 1 - width;
         }
     }
 
@@ -230,7 +455,6 @@ impl BufferEntry {
     ///
     /// If the cursor is not at the start of the line, this function moves the cursor
     /// left by one grapheme and adjusts the byte position accordingly.
-
     pub fn move_cursor_left(&mut self) {
         if self.cursor_position > 0 {
             self.cursor_position -= 1;
@@ -256,19 +480,339 @@ impl BufferEntry {
         }
     }
 
-    pub(crate) fn from_file(file_name: String) -> BufferEntry {
+    pub(crate) fn from_file(file_name: impl AsRef<Path>) -> std::io::Result<BufferEntry> {
         let mut buffer = BufferEntry::empty();
-        buffer.name = file_name.clone();
+        let path = absolute_path(file_name.as_ref());
 
         //load data from file:
-        let file = std::fs::File::open(file_name.clone()).unwrap();
+        let mut file = std::fs::File::open(&path)?;
+
+        let start = read_start(&mut file)?;
+        let large = file.metadata()?.len() > LARGE_FILE_SIZE;
+        buffer.line_ending = LineEnding::detect(&start);
+        if is_binary_data(&start) {
+            // the hex view holds all bytes in memory
+            if large {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "binary files over 32 MiB can not be opened",
+                ));
+            }
+            buffer.binary = Some(std::fs::read(&path)?);
+        } else if large {
+            buffer.buffer = Buffer::open_lazy(&path)?;
+        } else {
+            let reader = BufReader::new(file);
+            for line in reader.lines() {
+                match line {
+                    Ok(line) => buffer.buffer.add_line(line.as_str()),
+                    // invalid UTF-8 further into the file
+                    Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+                        buffer.buffer = Buffer::empty_buffer();
+                        buffer.binary = Some(std::fs::read(&path)?);
+                        break;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+
+        // an empty file still needs a line to put the cursor on
+        if buffer.buffer.num_lines() == 0 {
+            buffer.buffer = Buffer::new();
+        }
+
+        buffer.disk_state = DiskState::of(&path).ok();
+        buffer.read_only = !is_writable(&path);
+        buffer.soft_wrap = is_prose(&path);
+        buffer.show_whitespace = is_whitespace_sensitive(&path);
+        buffer.highlighter = Highlighter::for_path(&path);
+        buffer.path = Some(path);
+        Ok(buffer)
+    }
+
+    /// Returns true if another program changed the file since it was last
+    /// loaded or saved.
+    pub fn changed_on_disk(&self) -> bool {
+        match (&self.disk_state, &self.path) {
+            (Some(state), Some(path)) => state.has_changed(path),
+            _ => false,
+        }
+    }
+
+    /// Replace the contents with the file on disk, keeping the cursor as
+    /// close to its position as the new contents allow.
+    pub fn reload(&mut self) -> std::io::Result<()> {
+        let loaded = BufferEntry::from_file(self.file_path()?)?;
+        self.buffer = loaded.buffer;
+        self.binary = loaded.binary;
+        self.line_ending = loaded.line_ending;
+        self.disk_state = loaded.disk_state;
+        self.modified = false;
+        self.clear_selection();
+
+        self.cursor_line = self
+            .cursor_line
+            .min(self.buffer.num_lines().saturating_sub(1));
+        self.cursor_position = self
+            .cursor_position
+            .min(self.buffer.line_char_length(self.cursor_line).unwrap_or(0));
+        self.scroll_offset = self.scroll_offset.min(self.cursor_line);
+        Ok(())
+    }
 
-        let reader = BufReader::new(file);
-        for line in reader.lines() {
-            buffer.buffer.add_line(line.unwrap().as_str());
+    /// A read-only buffer showing how the buffer differs from the file on disk.
+    pub fn diff_against_disk(&self) -> std::io::Result<BufferEntry> {
+        if self.buffer.is_lazy() {
+            return Err(std::io::Error::other("the file is too large to compare"));
         }
+        if self.is_binary() {
+            return Err(std::io::Error::other("binary files can not be compared"));
+        }
+        let on_disk = std::fs::read_to_string(self.file_path()?)?;
+        let old: Vec<&str> = on_disk.lines().collect();
+        let lines: Vec<String> = self
+            .buffer
+            .lines_from(0)
+            .map(|l| l.iter().collect())
+            .collect();
+        let new: Vec<&str> = lines.iter().map(String::as_str).collect();
+
+        let diff: Vec<String> = diff_lines(&old, &new).iter().map(|l| l.to_text()).collect();
+        Ok(BufferEntry {
+            name: format!("{} (disk -> buffer)", self.display_name()),
+            buffer: Buffer::from(diff),
+            read_only: true,
+            ..Default::default()
+        })
+    }
+
+    /// Open `file_name`, or create a new empty buffer for it if the file does
+    /// not exist yet.
+    pub(crate) fn open_or_create(file_name: impl AsRef<Path>) -> std::io::Result<BufferEntry> {
+        if file_name.as_ref().exists() {
+            BufferEntry::from_file(file_name)
+        } else {
+            Ok(BufferEntry {
+                path: Some(absolute_path(file_name.as_ref())),
+                ..Default::default()
+            })
+        }
+    }
+
+    /// An unnamed buffer holding `data` that did not come from a file, e.g.
+    /// what was piped into mu.
+    pub fn from_bytes(name: &str, data: Vec<u8>) -> BufferEntry {
+        let mut entry = BufferEntry {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        entry.line_ending = LineEnding::detect(&data);
+        if is_binary_data(&data) {
+            entry.binary = Some(data);
+        } else {
+            entry.buffer = Buffer::from(String::from_utf8_lossy(&data).to_string());
+            if entry.buffer.num_lines() == 0 {
+                entry.buffer = Buffer::new();
+            }
+        }
+        entry
+    }
+
+    /// The contents as they are written to a file.
+    pub fn contents(&self) -> std::io::Result<Vec<u8>> {
+        if let Some(bytes) = &self.binary {
+            return Ok(bytes.clone());
+        }
+        let mut out = Vec::new();
+        self.buffer.write_to(&mut out, self.line_ending)?;
+        Ok(out)
+    }
+
+    /// Move the cursor to the given 0-based line and column, clamped to the
+    /// buffer contents.
+    pub fn goto_position(&mut self, line: usize, column: usize, screen_height: u16) {
+        self.cursor_line = line;
+        self.cursor_position = column;
+        self.update_scroll_position(screen_height);
+    }
+
+    /// Scroll by `lines` without moving the cursor, unless it would leave
+    /// the screen.
+    pub fn scroll_by(&mut self, lines: isize, screen_height: u16) {
+        let last = self.buffer.num_lines().saturating_sub(1);
+        self.scroll_offset = self.scroll_offset.saturating_add_signed(lines).min(last);
+
+        let height = (screen_height as usize).max(1);
+        let mut bottom = (self.scroll_offset + height - 1).min(last);
+        if self.soft_wrap {
+            bottom = self.scroll_offset;
+            let mut rows = self.wrapped_line(bottom).rows.len();
+            while bottom < last {
+                rows += self.wrapped_line(bottom + 1).rows.len();
+                if rows > height {
+                    break;
+                }
+                bottom += 1;
+            }
+        }
+        let line = self.cursor_line.clamp(self.scroll_offset, bottom);
+        if line != self.cursor_line {
+            self.cursor_line = line;
+            self.cursor_position = self
+                .cursor_position
+                .min(self.buffer.line_char_length(line).unwrap_or(0));
+        }
+    }
+
+    /// Binary buffers are shown and edited as hex instead of text.
+    pub fn is_binary(&self) -> bool {
+        self.binary.is_some()
+    }
+
+    /// An untitled buffer has never been given a path to save to.
+    pub fn is_untitled(&self) -> bool {
+        self.path.is_none()
+    }
+
+    fn file_path(&self) -> std::io::Result<&Path> {
+        self.path
+            .as_deref()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "buffer has no file"))
+    }
+
+    /// The name of this buffer on its own, without looking at other buffers.
+    /// Use `display_names` to tell buffers with the same file name apart.
+    pub fn display_name(&self) -> String {
+        if !self.name.is_empty() {
+            return self.name.clone();
+        }
+        match &self.path {
+            Some(path) => path
+                .file_name()
+                .unwrap_or(path.as_os_str())
+                .to_string_lossy()
+                .to_string(),
+            None => "untitled".to_string(),
+        }
+    }
+
+    /// Change the path the buffer is saved to. Does not touch the disk.
+    /// The buffer becomes writable, saving a copy of a read-only file is
+    /// fine.
+    pub fn set_path(&mut self, path: &Path) {
+        self.path = Some(absolute_path(path));
+        self.highlighter = Highlighter::for_path(path);
+        self.read_only = false;
+        // the buffer was never saved to the new path
+        self.disk_state = None;
+    }
+
+    /// Write the buffer to `path` and make that its file. If writing fails,
+    /// the buffer keeps its old path.
+    pub fn save_as(&mut self, path: &Path) -> std::io::Result<()> {
+        let old_path = self.path.clone();
+        let (read_only, disk_state) = (self.read_only, self.disk_state.take());
+        self.set_path(path);
+        let written = self.write_to_file();
+        if written.is_err() {
+            self.highlighter = old_path
+                .as_deref()
+                .map(Highlighter::for_path)
+                .unwrap_or_default();
+            (self.path, self.read_only, self.disk_state) = (old_path, read_only, disk_state);
+        }
+        written
+    }
+
+    /// Accept the file on disk as it is now, so it no longer counts as
+    /// changed on disk.
+    pub fn acknowledge_disk_state(&mut self) {
+        self.disk_state = self
+            .path
+            .as_deref()
+            .and_then(|path| DiskState::of(path).ok());
+    }
+
+    /// Update the path after the file was moved on disk to `path`.
+    pub fn file_moved(&mut self, path: &Path) {
+        self.path = Some(absolute_path(path));
+        self.highlighter = Highlighter::for_path(path);
+        self.disk_state = DiskState::of(path).ok();
+    }
+
+    /// Returns true if `path` is an existing file other than the one of
+    /// this buffer.
+    pub fn would_replace(&self, path: &Path) -> bool {
+        path.exists() && self.path.as_deref() != Some(absolute_path(path).as_path())
+    }
+
+    /// Rename the file of this buffer on disk. A buffer that was never
+    /// saved only changes its path. Existing files are not replaced.
+    pub fn rename_file(&mut self, path: &Path) -> std::io::Result<()> {
+        if self.would_replace(path) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                "the file already exists",
+            ));
+        }
+        match self.path.as_deref().filter(|p| p.exists()) {
+            Some(old_path) => {
+                std::fs::rename(old_path, path)?;
+                self.file_moved(path);
+            }
+            None => self.set_path(path),
+        }
+        Ok(())
+    }
+
+    /// Persist the buffer as it is to its file.
+    pub fn write_to_file(&mut self) -> std::io::Result<()> {
+        let path = self.file_path()?.to_path_buf();
+
+        if let Some(bytes) = &self.binary {
+            std::fs::write(&path, bytes)?;
+        } else if self.buffer.is_lazy() {
+            self.write_large_file(&path)?;
+        } else {
+            let mut file = BufWriter::new(std::fs::File::create(&path)?);
+            self.buffer.write_to(&mut file, self.line_ending)?;
+            file.flush()?;
+        }
+
+        self.modified = false;
+        self.disk_state = DiskState::of(self.file_path()?).ok();
+        Ok(())
+    }
+
+    /// Lines of a large file that were not edited are copied from the file
+    /// itself, so the new contents go to a temporary file first, which then
+    /// replaces the old one.
+    fn write_large_file(&mut self, path: &Path) -> std::io::Result<()> {
+        self.buffer.finish_index();
+
+        let mut temp_name = path.file_name().unwrap_or_default().to_owned();
+        temp_name.push(".mu-save");
+        let temp_path = path.with_file_name(temp_name);
+
+        let result = (|| {
+            let mut file = BufWriter::new(std::fs::File::create(&temp_path)?);
+            let starts = self.buffer.write_to(&mut file, self.line_ending)?;
+            file.flush()?;
+            if let Ok(metadata) = std::fs::metadata(path) {
+                std::fs::set_permissions(&temp_path, metadata.permissions())?;
+            }
+            std::fs::rename(&temp_path, path)?;
+            Ok(starts)
+        })();
 
-        buffer
+        match result {
+            Ok(starts) => self.buffer.reattach(std::fs::File::open(path)?, &starts),
+            Err(e) => {
+                let _ = std::fs::remove_file(&temp_path);
+                Err(e)
+            }
+        }
     }
 
     pub fn extend_selection_to_cursor(&mut self) {
@@ -277,11 +821,132 @@ impl BufferEntry {
         }
         self.selection_end = Some((self.cursor_line, self.cursor_position));
     }
+
+    /// Select the word under the cursor and put the cursor after it.
+    pub fn select_word(&mut self) {
+        let Some(line) = self.buffer.line_at(self.cursor_line) else {
+            return;
+        };
+        let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+        if !line.get(self.cursor_position).is_some_and(is_word) {
+            return;
+        }
+        let start = line[..self.cursor_position]
+            .iter()
+            .rposition(|c| !is_word(c))
+            .map_or(0, |i| i + 1);
+        let end = line[self.cursor_position..]
+            .iter()
+            .position(|c| !is_word(c))
+            .map_or(line.len(), |i| self.cursor_position + i);
+        self.selection_start = Some((self.cursor_line, start));
+        self.selection_end = Some((self.cursor_line, end));
+        self.cursor_position = end;
+    }
+
+    /// The number of selected characters, a line break counts as one.
+    pub fn selection_len(&self) -> Option<usize> {
+        let (start, end) = (self.selection_start?, self.selection_end?);
+        let (start, end) = (start.min(end), start.max(end));
+        if start.0 == end.0 {
+            return Some(end.1 - start.1);
+        }
+        let line_len = |line| self.buffer.line_char_length(line).unwrap_or(0);
+        let middle: usize = (start.0 + 1..end.0).map(|line| line_len(line) + 1).sum();
+        Some(line_len(start.0).saturating_sub(start.1) + 1 + middle + end.1)
+    }
+}
+
+/// How much of a file is looked at to tell text from binary files.
+const BINARY_CHECK_SIZE: usize = 8192;
+
+/// The start of `file`, to tell text from binary files and find the line
+/// ending. Rewinds the file afterwards.
+fn read_start(file: &mut std::fs::File) -> std::io::Result<Vec<u8>> {
+    let mut start = Vec::with_capacity(BINARY_CHECK_SIZE);
+    (&mut *file)
+        .take(BINARY_CHECK_SIZE as u64)
+        .read_to_end(&mut start)?;
+    file.seek(std::io::SeekFrom::Start(0))?;
+    Ok(start)
+}
+
+/// Whether `data` contains NUL bytes or is not valid UTF-8.
+fn is_binary_data(data: &[u8]) -> bool {
+    data.contains(&0)
+        || match std::str::from_utf8(data) {
+            Ok(_) => false,
+            // a character cut off at the end of a sample is fine
+            Err(e) => e.error_len().is_some(),
+        }
+}
+
+/// Markdown and plain text read better with soft wrap.
+fn is_prose(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| matches!(e, "md" | "markdown" | "txt"))
+}
+
+/// Files where tabs and spaces mean different things.
+fn is_whitespace_sensitive(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    matches!(name, "Makefile" | "makefile" | "GNUmakefile")
+        || path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| matches!(e, "mk" | "yml" | "yaml"))
+}
+
+/// Whether this process may write to the existing file at `path`. Checks by
+/// opening the file, as the permission bits alone do not tell whether they
+/// apply to the current user.
+fn is_writable(path: &Path) -> bool {
+    std::fs::OpenOptions::new().append(true).open(path).is_ok()
+}
+
+/// The names shown for `buffers`. Buffers without a name of their own are
+/// labeled with the shortest suffix of their path that tells them apart from
+/// all other buffers, e.g. "src/mod.rs" and "tests/mod.rs".
+pub fn display_names(buffers: &[BufferEntry]) -> Vec<String> {
+    let suffix = |path: &Path, len: usize| -> PathBuf {
+        let components: Vec<_> = path.components().collect();
+        components[components.len().saturating_sub(len)..]
+            .iter()
+            .collect()
+    };
+
+    buffers
+        .iter()
+        .map(|buffer| {
+            let Some(path) = buffer.path.as_deref().filter(|_| buffer.name.is_empty()) else {
+                return buffer.display_name();
+            };
+
+            let num_components = path.components().count();
+            let mut len = 1;
+            while len < num_components
+                && buffers.iter().any(|other| {
+                    other.name.is_empty()
+                        && other
+                            .path
+                            .as_deref()
+                            .is_some_and(|o| o != path && suffix(o, len) == suffix(path, len))
+                })
+            {
+                len += 1;
+            }
+            suffix(path, len).to_string_lossy().to_string()
+        })
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::buffer::Line;
+    use crate::{buffer::Line, testutil::TempDir};
 
     use super::*;
 
@@ -440,12 +1105,12 @@ mod tests {
         let mut b = BufferEntry::default();
         inject_string(&mut b, "fnord");
         inject_string(&mut b, "bar");
-        assert_eq!(b.buffer.lines.len(), 3);
+        assert_eq!(b.buffer.num_lines(), 3);
         assert_eq!(b.cursor_line, 2);
         b.cursor_line = 1;
         b.goto_line_start();
         b.remove_character(0);
-        assert_eq!(b.buffer.lines.len(), 2);
+        assert_eq!(b.buffer.num_lines(), 2);
         assert_eq!(b.cursor_line, 0);
         // should be right after the fnord!
         assert_eq!(b.cursor_position, 5);
@@ -454,6 +1119,369 @@ mod tests {
         assert_line_equals(ln, "fnordbar");
     }
 
+    #[test]
+    pub fn write_to_file_persists_lines_and_clears_modified() {
+        let dir = TempDir::new("write");
+        let file_name = dir.join("file");
+        let mut b = BufferEntry::default();
+        b.set_path(&file_name);
+        inject_string(&mut b, "fnord");
+        b.write_to_file().unwrap();
+
+        assert!(!b.modified);
+        assert_eq!(std::fs::read_to_string(&file_name).unwrap(), "fnord\n\n");
+    }
+
+    #[test]
+    pub fn crlf_files_keep_their_line_endings() {
+        let dir = TempDir::new("crlf");
+        let file_name = dir.join("file");
+        std::fs::write(&file_name, "one\r\ntwo\r\n").unwrap();
+        let mut b = BufferEntry::from_file(&file_name).unwrap();
+        assert_eq!(b.line_ending, LineEnding::CrLf);
+        assert_eq!(b.buffer.line_char_length(0), Some(3));
+
+        b.add_character('!');
+        b.write_to_file().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&file_name).unwrap(),
+            "!one\r\ntwo\r\n"
+        );
+    }
+
+    #[test]
+    pub fn piped_data_round_trips() {
+        let b = BufferEntry::from_bytes("stdin", b"one\ntwo\n".to_vec());
+        assert!(b.is_untitled());
+        assert_eq!(b.display_name(), "stdin");
+        assert_eq!(b.buffer.num_lines(), 2);
+        assert_eq!(b.contents().unwrap(), b"one\ntwo\n");
+
+        let b = BufferEntry::from_bytes("stdin", Vec::new());
+        assert_eq!(b.buffer.num_lines(), 1);
+
+        let b = BufferEntry::from_bytes("stdin", vec![0, 1, 2]);
+        assert_eq!(b.contents().unwrap(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    pub fn open_or_create_yields_empty_buffer_for_missing_file() {
+        let b = BufferEntry::open_or_create("/nonexistent/mu/file.txt").unwrap();
+        assert_eq!(b.path, Some(PathBuf::from("/nonexistent/mu/file.txt")));
+        assert_eq!(b.display_name(), "file.txt");
+        assert_eq!(b.buffer.num_lines(), 1);
+        assert!(!b.modified);
+    }
+
+    #[test]
+    pub fn large_file_is_saved_through_a_temporary_file() {
+        let dir = TempDir::new("large");
+        let file_name = dir.join("file");
+        std::fs::write(&file_name, "one\ntwo\nthree\n").unwrap();
+        let mut b = BufferEntry {
+            buffer: Buffer::open_lazy(&file_name).unwrap(),
+            path: Some(file_name.clone()),
+            ..Default::default()
+        };
+        b.goto_position(1, 3, 40);
+        b.add_character('!');
+        b.write_to_file().unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&file_name).unwrap(),
+            "one\ntwo!\nthree\n"
+        );
+        assert!(b.buffer.is_lazy());
+        assert!(b.diff_against_disk().is_err());
+    }
+
+    #[test]
+    pub fn binary_files_are_detected() {
+        let dir = TempDir::new("binary");
+        let file_name = dir.join("file");
+        std::fs::write(&file_name, b"ELF\x00\x01\xff").unwrap();
+        let mut b = BufferEntry::from_file(&file_name).unwrap();
+        assert_eq!(b.binary, Some(b"ELF\x00\x01\xff".to_vec()));
+
+        b.binary.as_mut().unwrap()[0] = b'X';
+        b.write_to_file().unwrap();
+        assert_eq!(std::fs::read(&file_name).unwrap(), b"XLF\x00\x01\xff");
+
+        // invalid UTF-8 after the part that is checked up front
+        let mut contents = "a\n".repeat(BINARY_CHECK_SIZE).into_bytes();
+        contents.push(0xff);
+        std::fs::write(&file_name, &contents).unwrap();
+        assert!(BufferEntry::from_file(&file_name).unwrap().is_binary());
+
+        std::fs::write(&file_name, "grüße\n").unwrap();
+        assert!(!BufferEntry::from_file(&file_name).unwrap().is_binary());
+    }
+
+    #[test]
+    pub fn large_binary_files_are_refused() {
+        let dir = TempDir::new("large_binary");
+        let file_name = dir.join("file");
+        // all zeros, without writing them
+        let file = std::fs::File::create(&file_name).unwrap();
+        file.set_len(LARGE_FILE_SIZE + 1).unwrap();
+
+        let error = BufferEntry::from_file(&file_name).err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+    }
+
+    #[test]
+    pub fn goto_position_clamps_to_buffer() {
+        let mut b = BufferEntry::default();
+        inject_string(&mut b, "fnord");
+        b.goto_position(0, 3, 40);
+        assert_eq!((b.cursor_line, b.cursor_position), (0, 3));
+        b.goto_position(10, 10, 40);
+        assert_eq!((b.cursor_line, b.cursor_position), (1, 0));
+    }
+
+    #[test]
+    pub fn scrolling_takes_the_cursor_along() {
+        let text: String = (0..100).map(|i| format!("line {}\n", i)).collect();
+        let mut b = BufferEntry::from_bytes("t", text.into_bytes());
+        b.goto_position(5, 6, 10);
+        b.scroll_by(3, 10);
+        assert_eq!((b.scroll_offset, b.cursor_line), (3, 5));
+        b.scroll_by(20, 10);
+        assert_eq!(
+            (b.scroll_offset, b.cursor_line, b.cursor_position),
+            (23, 23, 6)
+        );
+        b.scroll_by(-20, 10);
+        assert_eq!((b.scroll_offset, b.cursor_line), (3, 12));
+        b.scroll_by(-20, 10);
+        assert_eq!(b.scroll_offset, 0);
+        b.scroll_by(1000, 10);
+        assert_eq!(b.scroll_offset, 99);
+    }
+
+    #[test]
+    pub fn double_click_selects_a_word() {
+        let mut b = BufferEntry::from_bytes("t", b"let some_name = 1;".to_vec());
+        b.cursor_position = 6;
+        b.select_word();
+        assert_eq!(b.selection_start, Some((0, 4)));
+        assert_eq!(b.selection_end, Some((0, 13)));
+        assert_eq!(b.cursor_position, 13);
+
+        b.clear_selection();
+        b.cursor_position = 3;
+        b.select_word();
+        assert_eq!(b.selection_start, None);
+    }
+
+    #[test]
+    pub fn horizontal_offset_follows_the_cursor() {
+        let mut b = BufferEntry::from_bytes("t", b"0123456789abcdefghij".to_vec());
+        b.goto_line_end();
+        b.fit_to_view(10, 40);
+        assert_eq!(b.horizontal_offset, 12);
+
+        b.cursor_position = 12;
+        b.fit_to_view(10, 40);
+        assert_eq!(b.horizontal_offset, 11);
+
+        b.goto_line_start();
+        b.fit_to_view(10, 40);
+        assert_eq!(b.horizontal_offset, 0);
+    }
+
+    #[test]
+    pub fn horizontal_offset_counts_tab_columns() {
+        let mut b = BufferEntry::from_bytes("t", b"\t\t\tx".to_vec());
+        b.goto_line_end();
+        b.fit_to_view(10, 40);
+        // the cursor after x is at column 13
+        assert_eq!(b.horizontal_offset, 5);
+    }
+
+    #[test]
+    pub fn cursor_moves_by_screen_row_when_wrapped() {
+        let mut b = BufferEntry::from_bytes("t", b"the quick brown fox\nend".to_vec());
+        b.soft_wrap = true;
+        b.fit_to_view(11, 40);
+        b.cursor_position = 2;
+
+        // "the quick " "brown fox" "end"
+        b.move_cursor_down(40);
+        assert_eq!((b.cursor_line, b.cursor_position), (0, 12));
+        b.move_cursor_down(40);
+        assert_eq!((b.cursor_line, b.cursor_position), (1, 2));
+        b.move_cursor_up(40);
+        b.move_cursor_up(40);
+        assert_eq!((b.cursor_line, b.cursor_position), (0, 2));
+    }
+
+    #[test]
+    pub fn wrapped_rows_follow_the_edits() {
+        let mut b = BufferEntry::from_bytes("t", b"aaaa bbbb cccc\ndddd\neeee".to_vec());
+        assert!(b.wrapped_rows().is_none());
+        b.soft_wrap = true;
+        b.fit_to_view(6, 40);
+        assert_eq!(*b.wrapped_rows().unwrap(), [3, 1, 1]);
+
+        b.goto_position(1, 4, 40);
+        for c in " ffff gggg".chars() {
+            b.add_character(c);
+        }
+        b.buffer.remove_line_at(0);
+        assert_eq!(*b.wrapped_rows().unwrap(), [3, 1]);
+
+        b.fit_to_view(40, 40);
+        assert_eq!(*b.wrapped_rows().unwrap(), [1, 1]);
+    }
+
+    #[test]
+    pub fn wrapped_rows_scroll_the_view() {
+        let mut b = BufferEntry::from_bytes("t", b"aaaa bbbb cccc\ndddd\neeee".to_vec());
+        b.soft_wrap = true;
+        b.fit_to_view(6, 40);
+
+        // line 0 takes three rows, the cursor row on line 2 is the fifth
+        b.goto_position(2, 0, 3);
+        assert_eq!(b.scroll_offset, 1);
+        b.goto_position(0, 0, 3);
+        assert_eq!(b.scroll_offset, 0);
+        b.goto_position(2, 0, 4);
+        assert_eq!(b.scroll_offset, 1);
+        b.goto_position(0, 0, 5);
+        b.goto_position(2, 0, 5);
+        assert_eq!(b.scroll_offset, 0);
+    }
+
+    #[test]
+    pub fn cursor_stays_on_screen_past_the_bottom() {
+        let text: String = (0..100).map(|i| format!("line {}\n", i)).collect();
+        let mut b = BufferEntry::from_bytes("t", text.into_bytes());
+        for _ in 0..40 {
+            b.move_cursor_down(35);
+            assert!(b.cursor_line - b.scroll_offset < 35);
+        }
+        assert_eq!((b.cursor_line, b.scroll_offset), (40, 6));
+    }
+
+    #[test]
+    pub fn reload_picks_up_external_change_and_keeps_cursor() {
+        let dir = TempDir::new("reload");
+        let file_name = dir.join("file");
+        std::fs::write(&file_name, "one\ntwo\nthree\n").unwrap();
+        let mut b = BufferEntry::from_file(&file_name).unwrap();
+        b.goto_position(2, 3, 40);
+        assert!(!b.changed_on_disk());
+
+        std::fs::write(&file_name, "one\ntwo\nthree!\nfour\n").unwrap();
+        assert!(b.changed_on_disk());
+
+        b.reload().unwrap();
+        assert!(!b.changed_on_disk());
+        assert_eq!(b.buffer.num_lines(), 4);
+        assert_eq!((b.cursor_line, b.cursor_position), (2, 3));
+
+        std::fs::write(&file_name, "one\n").unwrap();
+        b.reload().unwrap();
+        assert_eq!((b.cursor_line, b.cursor_position), (0, 3));
+    }
+
+    #[test]
+    pub fn diff_against_disk_shows_changed_lines() {
+        let dir = TempDir::new("diffdisk");
+        let file_name = dir.join("file");
+        std::fs::write(&file_name, "one\ntwo\n").unwrap();
+        let mut b = BufferEntry::from_file(&file_name).unwrap();
+        b.cursor_line = 1;
+        b.goto_line_end();
+        b.add_character('!');
+
+        let diff = b.diff_against_disk().unwrap();
+        assert!(diff.read_only);
+        assert_line_equals(diff.buffer.line_at(0).unwrap(), "  one");
+        assert_line_equals(diff.buffer.line_at(1).unwrap(), "- two");
+        assert_line_equals(diff.buffer.line_at(2).unwrap(), "+ two!");
+    }
+
+    #[test]
+    pub fn display_names_use_shortest_unique_suffix() {
+        let buffer = |path: &str| BufferEntry {
+            path: Some(PathBuf::from(path)),
+            ..Default::default()
+        };
+        let mut buffers = vec![
+            buffer("/home/me/project/src/mod.rs"),
+            buffer("/home/me/project/tests/mod.rs"),
+            buffer("/home/me/project/src/main.rs"),
+            BufferEntry::default(),
+        ];
+        assert_eq!(
+            display_names(&buffers),
+            vec!["src/mod.rs", "tests/mod.rs", "main.rs", "untitled"]
+        );
+
+        buffers[1].name = "scratch".to_string();
+        assert_eq!(display_names(&buffers)[0], "mod.rs");
+        assert_eq!(display_names(&buffers)[1], "scratch");
+    }
+
+    #[test]
+    pub fn writable_files_are_not_read_only() {
+        let dir = TempDir::new("writable");
+        let file_name = dir.join("file");
+        std::fs::write(&file_name, "fnord\n").unwrap();
+        assert!(!BufferEntry::from_file(&file_name).unwrap().read_only);
+
+        // a directory can not be opened for writing
+        assert!(!is_writable(dir.path()));
+    }
+
+    #[test]
+    pub fn set_path_does_not_touch_the_disk() {
+        let mut b = BufferEntry {
+            read_only: true,
+            ..Default::default()
+        };
+        assert!(b.is_untitled());
+        b.set_path(Path::new("/nonexistent/mu/renamed.txt"));
+        assert!(!b.is_untitled());
+        assert!(!Path::new("/nonexistent/mu/renamed.txt").exists());
+        assert_eq!(b.display_name(), "renamed.txt");
+        assert!(!b.read_only);
+    }
+
+    #[test]
+    pub fn rename_file_moves_file_on_disk() {
+        let dir = TempDir::new("rename");
+        let old = dir.join("old");
+        let new = dir.join("new");
+        std::fs::write(&old, "fnord\n").unwrap();
+        let mut b = BufferEntry::from_file(&old).unwrap();
+
+        b.rename_file(&new).unwrap();
+        assert!(!old.exists());
+        assert_eq!(std::fs::read_to_string(&new).unwrap(), "fnord\n");
+        assert_eq!(b.path, Some(absolute_path(&new)));
+        assert!(!b.changed_on_disk());
+    }
+
+    #[test]
+    pub fn rename_file_keeps_existing_files() {
+        let dir = TempDir::new("keep");
+        let old = dir.join("old");
+        let new = dir.join("new");
+        std::fs::write(&old, "fnord\n").unwrap();
+        std::fs::write(&new, "precious\n").unwrap();
+        let mut b = BufferEntry::from_file(&old).unwrap();
+
+        assert!(b.rename_file(&new).is_err());
+        assert_eq!(std::fs::read_to_string(&new).unwrap(), "precious\n");
+        assert_eq!(b.path, Some(absolute_path(&old)));
+        let mut untitled = BufferEntry::default();
+        assert!(untitled.rename_file(&new).is_err());
+        assert!(untitled.is_untitled());
+    }
+
     #[test]
     pub fn clear_selection_clears_selection() {
         let mut b = BufferEntry::default();
@@ -465,6 +1493,17 @@ mod tests {
         assert_eq!(b.selection_end, None);
     }
 
+    #[test]
+    pub fn selection_length_counts_line_breaks() {
+        let mut b = BufferEntry::from_bytes("", b"one\ntwo\nthree\n".to_vec());
+        assert_eq!(b.selection_len(), None);
+        b.selection_start = Some((2, 3));
+        b.selection_end = Some((0, 1));
+        assert_eq!(b.selection_len(), Some(10));
+        b.selection_end = Some((2, 1));
+        assert_eq!(b.selection_len(), Some(2));
+    }
+
     #[test]
     pub fn add_character_to_selection() {
         let mut b = BufferEntry::default();
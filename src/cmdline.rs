@@ -0,0 +1,250 @@
+use std::path::{Path, PathBuf};
+
+pub const USAGE: &str = "\
+Usage: mu [OPTIONS] [+LINE] [FILE[:LINE[:COLUMN]]]... | [DIRECTORY]
+       command | mu [OPTIONS] -
+
+Opens each FILE in its own buffer. Files that do not exist yet are opened
+as new, empty buffers. A DIRECTORY opens the file finder rooted there.
+A single - opens the text piped into mu as an unnamed buffer.
+Started without arguments, mu offers to restore the session it saved when
+it was last closed in the current directory.
+
+Options:
+  +LINE               Put the cursor on LINE in the next file
+  -r, --read-only     Open all files read-only
+  -c, --config PATH   Use the configuration file at PATH
+  -s, --session NAME  Restore the session NAME and save it there on exit
+  -o, --stdout        Write the current buffer to stdout on exit
+  -V, --version       Print version information and exit
+  -h, --help          Print this help and exit";
+
+/// A file to open, with an optional 1-based cursor position.
+#[derive(Debug, Default, PartialEq)]
+pub struct FileArg {
+    pub path: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+/// The parsed command line arguments.
+#[derive(Debug, Default, PartialEq)]
+pub struct CommandLine {
+    pub files: Vec<FileArg>,
+    /// A directory to open the file finder in.
+    pub directory: Option<PathBuf>,
+    pub read_only: bool,
+    pub config_path: Option<PathBuf>,
+    /// A named session to restore and save to instead of the project session.
+    pub session: Option<String>,
+    /// Open what is piped into stdin as a buffer ("-").
+    pub read_stdin: bool,
+    /// Write the current buffer to stdout when mu exits.
+    pub write_stdout: bool,
+    pub show_version: bool,
+    pub show_help: bool,
+}
+
+/// Split "file:line:col" or "file:line" into its parts. A path that exists
+/// as given is never split, so files with colons in their names still work.
+fn parse_file_arg(arg: &str) -> FileArg {
+    if !Path::new(arg).exists() {
+        let mut parts = arg.rsplitn(3, ':');
+        let last = parts.next().and_then(|p| p.parse::<usize>().ok());
+        let middle = parts.next();
+        let first = parts.next();
+
+        match (first, middle, last) {
+            (Some(path), Some(line), Some(column)) if line.parse::<usize>().is_ok() => {
+                return FileArg {
+                    path: path.to_string(),
+                    line: line.parse().ok(),
+                    column: Some(column),
+                };
+            }
+            (None, Some(path), Some(line)) => {
+                return FileArg {
+                    path: path.to_string(),
+                    line: Some(line),
+                    column: None,
+                };
+            }
+            (Some(first), Some(middle), Some(line)) => {
+                return FileArg {
+                    path: format!("{}:{}", first, middle),
+                    line: Some(line),
+                    column: None,
+                };
+            }
+            _ => (),
+        }
+    }
+
+    FileArg {
+        path: arg.to_string(),
+        ..Default::default()
+    }
+}
+
+impl CommandLine {
+    /// Parse the arguments, excluding the program name.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<CommandLine, String> {
+        let mut cmdline = CommandLine::default();
+        let mut args = args.into_iter();
+        let mut pending_line: Option<usize> = None;
+        let mut only_files = false;
+
+        while let Some(arg) = args.next() {
+            if !only_files && arg == "-" {
+                cmdline.read_stdin = true;
+                continue;
+            }
+
+            if !only_files && arg.starts_with('-') && arg.len() > 1 {
+                match arg.as_str() {
+                    "--" => only_files = true,
+                    "-r" | "--read-only" => cmdline.read_only = true,
+                    "-c" | "--config" => {
+                        let path = args
+                            .next()
+                            .ok_or_else(|| format!("{} expects a path", arg))?;
+                        cmdline.config_path = Some(PathBuf::from(path));
+                    }
+                    "-s" | "--session" => {
+                        let name = args
+                            .next()
+                            .ok_or_else(|| format!("{} expects a name", arg))?;
+                        if name.is_empty() || name.starts_with('.') || name.contains('/') {
+                            return Err(format!("invalid session name '{}'", name));
+                        }
+                        cmdline.session = Some(name);
+                    }
+                    "-o" | "--stdout" => cmdline.write_stdout = true,
+                    "-V" | "--version" => cmdline.show_version = true,
+                    "-h" | "--help" => cmdline.show_help = true,
+                    _ => return Err(format!("unknown option '{}'", arg)),
+                }
+                continue;
+            }
+
+            if !only_files && let Some(line) = arg.strip_prefix('+') {
+                let line = line
+                    .parse::<usize>()
+                    .map_err(|_| format!("invalid line number '{}'", arg))?;
+                pending_line = Some(line);
+                continue;
+            }
+
+            if Path::new(&arg).is_dir() {
+                if cmdline.directory.is_some() {
+                    return Err("only one directory can be opened".to_string());
+                }
+                cmdline.directory = Some(PathBuf::from(arg));
+                continue;
+            }
+
+            let mut file = parse_file_arg(&arg);
+            if let Some(line) = pending_line.take() {
+                file.line = Some(line);
+            }
+            cmdline.files.push(file);
+        }
+
+        if let Some(line) = pending_line {
+            return Err(format!("+{} is not followed by a file", line));
+        }
+        Ok(cmdline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<CommandLine, String> {
+        CommandLine::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    pub fn opens_all_files() {
+        let cmdline = parse(&["a.rs", "b.rs"]).unwrap();
+        assert_eq!(cmdline.files.len(), 2);
+        assert_eq!(cmdline.files[1].path, "b.rs");
+    }
+
+    #[test]
+    pub fn plus_line_applies_to_next_file() {
+        let cmdline = parse(&["a.rs", "+42", "b.rs"]).unwrap();
+        assert_eq!(cmdline.files[0].line, None);
+        assert_eq!(cmdline.files[1].line, Some(42));
+    }
+
+    #[test]
+    pub fn parses_file_line_column() {
+        let cmdline = parse(&["src/main.rs:42:7", "lib.rs:3"]).unwrap();
+        assert_eq!(
+            cmdline.files[0],
+            FileArg {
+                path: "src/main.rs".to_string(),
+                line: Some(42),
+                column: Some(7)
+            }
+        );
+        assert_eq!(cmdline.files[1].path, "lib.rs");
+        assert_eq!(cmdline.files[1].line, Some(3));
+        assert_eq!(cmdline.files[1].column, None);
+    }
+
+    #[test]
+    pub fn colon_without_number_is_part_of_the_path() {
+        let cmdline = parse(&["notes:draft"]).unwrap();
+        assert_eq!(cmdline.files[0].path, "notes:draft");
+        assert_eq!(cmdline.files[0].line, None);
+    }
+
+    #[test]
+    pub fn parses_flags() {
+        let cmdline = parse(&["-r", "--config", "mu.conf", "-s", "work", "-V", "--help"]).unwrap();
+        assert!(cmdline.read_only);
+        assert_eq!(cmdline.session, Some("work".to_string()));
+        assert_eq!(cmdline.config_path, Some(PathBuf::from("mu.conf")));
+        assert!(cmdline.show_version);
+        assert!(cmdline.show_help);
+    }
+
+    #[test]
+    pub fn directory_opens_finder() {
+        let dir = std::env::temp_dir();
+        let cmdline = parse(&[dir.to_str().unwrap()]).unwrap();
+        assert_eq!(cmdline.directory, Some(dir));
+        assert!(cmdline.files.is_empty());
+    }
+
+    #[test]
+    pub fn rejects_unknown_options_and_bad_lines() {
+        assert!(parse(&["--frobnicate"]).is_err());
+        assert!(parse(&["a.rs", "+42"]).is_err());
+        assert!(parse(&["--session", "../escape"]).is_err());
+        assert!(parse(&["+abc", "a.rs"]).is_err());
+        assert!(parse(&["--config"]).is_err());
+    }
+
+    #[test]
+    pub fn dash_reads_stdin() {
+        let cmdline = parse(&["-o", "-"]).unwrap();
+        assert!(cmdline.read_stdin);
+        assert!(cmdline.write_stdout);
+        assert!(cmdline.files.is_empty());
+
+        let cmdline = parse(&["--", "-"]).unwrap();
+        assert!(!cmdline.read_stdin);
+        assert_eq!(cmdline.files[0].path, "-");
+    }
+
+    #[test]
+    pub fn double_dash_ends_options() {
+        let cmdline = parse(&["--", "-r"]).unwrap();
+        assert!(!cmdline.read_only);
+        assert_eq!(cmdline.files[0].path, "-r");
+    }
+}
@@ -0,0 +1,346 @@
+use ratatui::text::Span;
+
+/// Tabs advance to the next multiple of this many columns.
+pub const TAB_WIDTH: usize = 4;
+
+/// Characters that take no room on screen, but change the text.
+pub fn is_zero_width(c: char) -> bool {
+    matches!(c, '\u{200b}'..='\u{200f}' | '\u{2060}' | '\u{feff}')
+}
+
+/// The number of screen columns `c` takes when it starts at `column`.
+/// Zero-width characters get a column, so the cursor can be put on them.
+pub fn char_width(c: char, column: usize) -> usize {
+    if c == '\t' {
+        return TAB_WIDTH - column % TAB_WIDTH;
+    }
+    if is_zero_width(c) {
+        return 1;
+    }
+    let mut bytes = [0; 4];
+    Span::raw(&*c.encode_utf8(&mut bytes)).width()
+}
+
+/// The screen column the character at `index` starts at.
+pub fn display_column(line: &[char], index: usize) -> usize {
+    line.iter()
+        .take(index)
+        .fold(0, |column, c| column + char_width(*c, column))
+}
+
+/// The index of the character shown at screen `column`, the length of the
+/// line for columns past its end.
+pub fn index_at_column(line: &[char], column: usize) -> usize {
+    let mut start = 0;
+    for (index, c) in line.iter().enumerate() {
+        start += char_width(*c, start);
+        if start > column {
+            return index;
+        }
+    }
+    line.len()
+}
+
+/// The part of a line that fits into `width` columns starting at column
+/// `offset`, with tabs expanded to spaces and zero-width characters shown
+/// as a space.
+#[derive(Debug, PartialEq)]
+pub struct VisibleLine {
+    pub text: String,
+    /// The index in the line of each character of `text`.
+    pub indices: Vec<usize>,
+    /// Whether the line has content left of the visible part.
+    pub more_left: bool,
+    /// Whether the line has content right of the visible part.
+    pub more_right: bool,
+}
+
+pub fn visible_part(line: &[char], offset: usize, width: usize) -> VisibleLine {
+    let mut visible = VisibleLine {
+        text: String::new(),
+        indices: Vec::new(),
+        more_left: false,
+        more_right: false,
+    };
+    let end_of_view = offset + width;
+    let mut column = 0;
+    for (index, c) in line.iter().enumerate() {
+        let char_end = column + char_width(*c, column);
+        if char_end <= offset {
+            visible.more_left |= char_end > column;
+        } else if column >= end_of_view {
+            visible.more_right = true;
+            break;
+        } else if column < offset || char_end > end_of_view || *c == '\t' || is_zero_width(*c) {
+            // tabs and wide characters cut by the edge of the view are
+            // shown as the spaces they cover
+            visible.more_left |= column < offset;
+            visible.more_right |= char_end > end_of_view;
+            let shown = char_end.min(end_of_view) - column.max(offset);
+            visible.text.extend(std::iter::repeat_n(' ', shown));
+            visible.indices.extend(std::iter::repeat_n(index, shown));
+        } else {
+            visible.text.push(*c);
+            visible.indices.push(index);
+        }
+        column = char_end;
+    }
+    visible
+}
+
+/// How a character replaced by `show_whitespace` is styled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Whitespace {
+    Shown,
+    /// Whitespace at the end of the line.
+    Trailing,
+}
+
+/// Replace tabs, trailing spaces, non-breaking spaces and zero-width
+/// characters in `visible`, a part of `line`, by glyphs. Returns how each
+/// character of the text is to be styled.
+pub fn show_whitespace(visible: &mut VisibleLine, line: &[char]) -> Vec<Option<Whitespace>> {
+    let trailing_start = line
+        .iter()
+        .rposition(|c| !c.is_whitespace())
+        .map_or(0, |i| i + 1);
+    let mut text = String::new();
+    let mut marks = Vec::new();
+    let mut previous = None;
+    for (shown, &index) in visible.text.chars().zip(&visible.indices) {
+        let c = line[index];
+        let glyph = match c {
+            // a tab covers several columns, the arrow marks its start
+            '\t' if previous != Some(index) => Some('→'),
+            '\t' => Some(' '),
+            ' ' if index >= trailing_start => Some('·'),
+            '\u{a0}' | '\u{202f}' => Some('⍽'),
+            c if is_zero_width(c) => Some('‸'),
+            _ => None,
+        };
+        text.push(glyph.unwrap_or(shown));
+        marks.push(if index >= trailing_start {
+            Some(Whitespace::Trailing)
+        } else {
+            glyph.map(|_| Whitespace::Shown)
+        });
+        previous = Some(index);
+    }
+    visible.text = text;
+    marks
+}
+
+/// The glyph shown at the end of every line when whitespace is shown.
+pub const LINE_END: char = '¬';
+
+/// A line broken into screen rows for soft wrapping.
+#[derive(Debug, PartialEq)]
+pub struct WrappedLine {
+    /// The index of the first character of each row.
+    pub rows: Vec<usize>,
+    /// The columns continuation rows are indented by, to line up with the
+    /// indentation of the first row.
+    pub indent: usize,
+}
+
+impl WrappedLine {
+    /// A line shown in a single row.
+    pub fn unwrapped() -> WrappedLine {
+        WrappedLine {
+            rows: vec![0],
+            indent: 0,
+        }
+    }
+
+    /// The row showing the character at `index`. The end of the line
+    /// belongs to the last row.
+    pub fn row_of(&self, index: usize) -> usize {
+        self.rows.partition_point(|start| *start <= index) - 1
+    }
+
+    /// The index after the last character of `row`.
+    pub fn row_end(&self, row: usize, line_length: usize) -> usize {
+        self.rows.get(row + 1).copied().unwrap_or(line_length)
+    }
+
+    /// The indentation of `row` on screen.
+    pub fn row_indent(&self, row: usize) -> usize {
+        if row > 0 { self.indent } else { 0 }
+    }
+}
+
+/// Break a line into rows of at most `width` columns, at the last
+/// whitespace that fits. Words longer than a row are broken anywhere.
+/// Whitespace at a break stays at the end of the row, even if it does not
+/// fit.
+pub fn wrap_line(line: &[char], width: usize) -> WrappedLine {
+    let leading_whitespace = line.iter().take_while(|c| c.is_whitespace()).count();
+    let indent = display_column(line, leading_whitespace);
+    // deeply indented lines would leave no room for the text
+    let indent = if indent * 2 <= width { indent } else { 0 };
+    let mut wrapped = WrappedLine {
+        rows: vec![0],
+        indent,
+    };
+    if width == 0 {
+        return wrapped;
+    }
+
+    let mut row_start_column = 0;
+    let mut row_width = width;
+    // the index and column after the last whitespace seen in the row
+    let mut break_after: Option<(usize, usize)> = None;
+    let mut column = 0;
+    for (index, c) in line.iter().enumerate() {
+        let char_end = column + char_width(*c, column);
+        let row_start = *wrapped.rows.last().unwrap();
+        if char_end > row_start_column + row_width && index > row_start && !c.is_whitespace() {
+            let (start, start_column) = break_after
+                .take()
+                .filter(|(start, _)| *start > row_start)
+                .unwrap_or((index, column));
+            wrapped.rows.push(start);
+            row_start_column = start_column;
+            row_width = width - indent;
+
+            // the rest of a long word still has to be broken
+            if char_end > row_start_column + row_width && index > start {
+                wrapped.rows.push(index);
+                row_start_column = column;
+            }
+        }
+        if c.is_whitespace() {
+            break_after = Some((index + 1, char_end));
+        }
+        column = char_end;
+    }
+    wrapped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    #[test]
+    pub fn tabs_advance_to_the_next_stop() {
+        assert_eq!(char_width('\t', 0), 4);
+        assert_eq!(char_width('\t', 5), 3);
+        assert_eq!(display_column(&chars("a\tb"), 2), 4);
+        assert_eq!(display_column(&chars("日本x"), 2), 4);
+    }
+
+    #[test]
+    pub fn columns_map_back_to_characters() {
+        let line = chars("a\t日b");
+        assert_eq!(index_at_column(&line, 0), 0);
+        assert_eq!(index_at_column(&line, 3), 1);
+        assert_eq!(index_at_column(&line, 5), 2);
+        assert_eq!(index_at_column(&line, 6), 3);
+        assert_eq!(index_at_column(&line, 20), 4);
+    }
+
+    #[test]
+    pub fn short_line_is_shown_completely() {
+        let visible = visible_part(&chars("a\tb"), 0, 10);
+        assert_eq!(visible.text, "a   b");
+        assert_eq!(visible.indices, vec![0, 1, 1, 1, 2]);
+        assert!(!visible.more_left);
+        assert!(!visible.more_right);
+    }
+
+    #[test]
+    pub fn long_line_is_cut_on_both_sides() {
+        let visible = visible_part(&chars("0123456789"), 3, 4);
+        assert_eq!(visible.text, "3456");
+        assert!(visible.more_left);
+        assert!(visible.more_right);
+    }
+
+    #[test]
+    pub fn lines_wrap_at_word_boundaries() {
+        let wrapped = wrap_line(&chars("the quick brown fox"), 10);
+        // "the quick " "brown fox"
+        assert_eq!(wrapped.rows, vec![0, 10]);
+        assert_eq!(wrapped.row_of(9), 0);
+        assert_eq!(wrapped.row_of(10), 1);
+        assert_eq!(wrapped.row_of(19), 1);
+        assert_eq!(wrapped.row_end(0, 19), 10);
+    }
+
+    #[test]
+    pub fn long_words_are_broken_anywhere() {
+        let wrapped = wrap_line(&chars("abcdefghij"), 4);
+        assert_eq!(wrapped.rows, vec![0, 4, 8]);
+
+        let wrapped = wrap_line(&chars("ab cdefghijkl"), 4);
+        assert_eq!(wrapped.rows, vec![0, 3, 7, 11]);
+    }
+
+    #[test]
+    pub fn continuation_rows_keep_the_indentation() {
+        let wrapped = wrap_line(&chars("    one two three"), 12);
+        // "    one two " "three"
+        assert_eq!(wrapped.rows, vec![0, 12]);
+        assert_eq!(wrapped.indent, 4);
+        assert_eq!(wrapped.row_indent(0), 0);
+        assert_eq!(wrapped.row_indent(1), 4);
+
+        // too deep to keep
+        let wrapped = wrap_line(&chars("        one two"), 12);
+        assert_eq!(wrapped.indent, 0);
+    }
+
+    #[test]
+    pub fn short_lines_are_not_wrapped() {
+        assert_eq!(wrap_line(&chars("short"), 10), WrappedLine::unwrapped());
+        assert_eq!(wrap_line(&chars(""), 10), WrappedLine::unwrapped());
+    }
+
+    #[test]
+    pub fn wide_characters_cut_by_the_edge_become_spaces() {
+        // 日 takes columns 0-1, 本 2-3
+        let visible = visible_part(&chars("日本x"), 1, 2);
+        assert_eq!(visible.text, "  ");
+        assert!(visible.more_left);
+        assert!(visible.more_right);
+
+        let visible = visible_part(&chars("日本x"), 2, 3);
+        assert_eq!(visible.text, "本x");
+    }
+
+    #[test]
+    pub fn zero_width_characters_take_a_column() {
+        let line = chars("a\u{200b}b");
+        assert_eq!(display_column(&line, 2), 2);
+        assert_eq!(visible_part(&line, 0, 10).text, "a b");
+    }
+
+    #[test]
+    pub fn whitespace_is_replaced_by_glyphs() {
+        let line = chars("\tx\u{a0}y\u{feff} \t");
+        let mut visible = visible_part(&line, 0, 20);
+        let marks = show_whitespace(&mut visible, &line);
+        assert_eq!(visible.text, "→   x⍽y‸·→  ");
+
+        use Whitespace::*;
+        let shown = Some(Shown);
+        let trailing = Some(Trailing);
+        assert_eq!(
+            marks,
+            vec![
+                shown, shown, shown, shown, None, shown, None, shown, trailing, trailing, trailing,
+                trailing
+            ]
+        );
+
+        // spaces inside the line are left alone
+        let line = chars("a b");
+        let mut visible = visible_part(&line, 0, 20);
+        assert_eq!(show_whitespace(&mut visible, &line), vec![None; 3]);
+        assert_eq!(visible.text, "a b");
+    }
+}
@@ -0,0 +1,150 @@
+use std::path::{Path, PathBuf};
+
+use crate::{gutter::LineNumbers, scrollbar::Scrollbar, statusline::StatusLine};
+
+/// User settings, read from a simple "key = value" file. Lines starting
+/// with '#' are comments.
+#[derive(Debug, Default, PartialEq)]
+pub struct Config {
+    /// Whether the file finder and file tree show hidden files on startup.
+    pub show_hidden_files: bool,
+    /// How lines are numbered: off, absolute, relative or hybrid.
+    pub line_numbers: LineNumbers,
+    /// Whether wrapped lines are marked at the end of each row.
+    pub wrap_indicator: bool,
+    /// The name of a built-in theme or of a file in the themes directory.
+    pub theme: Option<String>,
+    /// The segments of the status line, see `StatusLine::parse`.
+    pub status_line: StatusLine,
+    /// What is shown at the right edge of a view: off, on or minimap.
+    pub scrollbar: Scrollbar,
+}
+
+/// The directory mu reads its configuration from, following the XDG base
+/// directory spec.
+pub fn config_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("mu"))
+}
+
+/// The directory mu keeps its data in, following the XDG base directory
+/// spec.
+pub fn data_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share"))
+        })
+        .map(|dir| dir.join("mu"))
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "yes" | "on" => Ok(true),
+        "false" | "no" | "off" => Ok(false),
+        _ => Err(format!("'{}' expects true or false, got '{}'", key, value)),
+    }
+}
+
+impl Config {
+    pub fn parse(contents: &str) -> Result<Config, String> {
+        let mut config = Config::default();
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("line {}: expected 'key = value'", number + 1));
+            };
+            let (key, value) = (key.trim(), value.trim());
+
+            match key {
+                "show_hidden_files" => config.show_hidden_files = parse_bool(key, value)?,
+                "wrap_indicator" => config.wrap_indicator = parse_bool(key, value)?,
+                "theme" => config.theme = Some(value.to_string()),
+                "status_line" => {
+                    config.status_line = StatusLine::parse(value)
+                        .map_err(|e| format!("line {}: {}", number + 1, e))?
+                }
+                "scrollbar" => {
+                    config.scrollbar = Scrollbar::parse(value).ok_or_else(|| {
+                        format!("'{}' expects off, on or minimap, got '{}'", key, value)
+                    })?
+                }
+                "line_numbers" => {
+                    config.line_numbers = LineNumbers::parse(value).ok_or_else(|| {
+                        format!(
+                            "'{}' expects off, absolute, relative or hybrid, got '{}'",
+                            key, value
+                        )
+                    })?
+                }
+                _ => return Err(format!("line {}: unknown setting '{}'", number + 1, key)),
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Load the configuration from `path`, or from the default location if
+    /// no path is given. A missing default file yields the default settings.
+    pub fn load(path: Option<&Path>) -> Result<Config, String> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match config_dir() {
+                Some(dir) => (dir.join("config"), false),
+                None => return Ok(Config::default()),
+            },
+        };
+
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => {
+                Config::parse(&contents).map_err(|e| format!("{}: {}", path.to_string_lossy(), e))
+            }
+            Err(_) if !required => Ok(Config::default()),
+            Err(e) => Err(format!("{}: {}", path.to_string_lossy(), e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn parses_settings_and_comments() {
+        let config = Config::parse(
+            "# my settings\n\n\
+             show_hidden_files = yes\n\
+             line_numbers = hybrid\n\
+             wrap_indicator = on\n\
+             theme = light\n\
+             scrollbar = minimap\n",
+        )
+        .unwrap();
+        assert!(config.show_hidden_files);
+        assert_eq!(config.line_numbers, LineNumbers::Hybrid);
+        assert!(config.wrap_indicator);
+        assert_eq!(config.theme.as_deref(), Some("light"));
+        assert_eq!(config.scrollbar, Scrollbar::Minimap);
+    }
+
+    #[test]
+    pub fn rejects_unknown_settings_and_bad_values() {
+        assert!(Config::parse("frobnicate = 1").is_err());
+        assert!(Config::parse("show_hidden_files = maybe").is_err());
+        assert!(Config::parse("show_hidden_files").is_err());
+        assert!(Config::parse("line_numbers = roman").is_err());
+        assert!(Config::parse("scrollbar = left").is_err());
+    }
+
+    #[test]
+    pub fn missing_explicit_config_is_an_error() {
+        assert!(Config::load(Some(Path::new("/nonexistent/mu/config"))).is_err());
+    }
+}
@@ -0,0 +1,113 @@
+/// One line of a line based diff.
+#[derive(Debug, PartialEq)]
+pub enum DiffLine<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+impl DiffLine<'_> {
+    /// Render the line like a unified diff does.
+    pub fn to_text(&self) -> String {
+        match self {
+            DiffLine::Same(l) => format!("  {}", l),
+            DiffLine::Removed(l) => format!("- {}", l),
+            DiffLine::Added(l) => format!("+ {}", l),
+        }
+    }
+}
+
+/// Above this many cells the LCS table gets too big and the differing middle
+/// part is reported as removed and added as a whole.
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+/// Compute a line diff that turns `old` into `new`.
+pub fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<DiffLine<'a>> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    let mut result: Vec<DiffLine> = old[..prefix].iter().map(|l| DiffLine::Same(l)).collect();
+
+    if old_mid.len() * new_mid.len() > MAX_LCS_CELLS {
+        result.extend(old_mid.iter().map(|l| DiffLine::Removed(l)));
+        result.extend(new_mid.iter().map(|l| DiffLine::Added(l)));
+    } else {
+        // lcs[i][j] is the length of the longest common subsequence of
+        // old_mid[i..] and new_mid[j..]
+        let (n, m) = (old_mid.len(), new_mid.len());
+        let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i][j] = if old_mid[i] == new_mid[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && old_mid[i] == new_mid[j] {
+                result.push(DiffLine::Same(old_mid[i]));
+                i += 1;
+                j += 1;
+            } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+                result.push(DiffLine::Removed(old_mid[i]));
+                i += 1;
+            } else {
+                result.push(DiffLine::Added(new_mid[j]));
+                j += 1;
+            }
+        }
+    }
+
+    result.extend(old[old.len() - suffix..].iter().map(|l| DiffLine::Same(l)));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn identical_input_has_no_changes() {
+        let lines = ["a", "b"];
+        assert_eq!(
+            diff_lines(&lines, &lines),
+            vec![DiffLine::Same("a"), DiffLine::Same("b")]
+        );
+    }
+
+    #[test]
+    pub fn detects_added_removed_and_changed_lines() {
+        let old = ["a", "b", "c", "d"];
+        let new = ["a", "x", "c", "d", "e"];
+        assert_eq!(
+            diff_lines(&old, &new),
+            vec![
+                DiffLine::Same("a"),
+                DiffLine::Removed("b"),
+                DiffLine::Added("x"),
+                DiffLine::Same("c"),
+                DiffLine::Same("d"),
+                DiffLine::Added("e"),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn to_text_prefixes_lines() {
+        assert_eq!(DiffLine::Removed("foo").to_text(), "- foo");
+        assert_eq!(DiffLine::Added("foo").to_text(), "+ foo");
+        assert_eq!(DiffLine::Same("foo").to_text(), "  foo");
+    }
+}
@@ -0,0 +1,88 @@
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    path::Path,
+    time::SystemTime,
+};
+
+use crate::buffer::LARGE_FILE_SIZE;
+
+/// What a file looked like on disk when a buffer was loaded from or saved
+/// to it. Used to notice when another program changed the file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiskState {
+    pub modified: Option<SystemTime>,
+    pub size: u64,
+    /// `None` for large files, hashing them would take too long.
+    pub hash: Option<u64>,
+}
+
+fn hash_bytes(bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl DiskState {
+    /// Read the current state of the file at `path`.
+    pub fn of(path: &Path) -> std::io::Result<DiskState> {
+        let metadata = std::fs::metadata(path)?;
+        let hash = if metadata.len() > LARGE_FILE_SIZE {
+            None
+        } else {
+            Some(hash_bytes(&std::fs::read(path)?))
+        };
+        Ok(DiskState {
+            modified: metadata.modified().ok(),
+            size: metadata.len(),
+            hash,
+        })
+    }
+
+    /// Returns true if the file at `path` no longer matches this state. The
+    /// contents are only hashed if the modification time or size changed,
+    /// so a plain `touch` is not reported as a change, except for large files
+    /// that are not hashed. A file that can not be read (e.g. because it was
+    /// deleted) is not reported either.
+    pub fn has_changed(&self, path: &Path) -> bool {
+        let Ok(metadata) = std::fs::metadata(path) else {
+            return false;
+        };
+        if metadata.modified().ok() == self.modified && metadata.len() == self.size {
+            return false;
+        }
+        let Some(hash) = self.hash else {
+            return true;
+        };
+        std::fs::read(path)
+            .map(|contents| hash_bytes(&contents) != hash)
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test]
+    pub fn detects_changed_contents_but_not_touch() {
+        let dir = TempDir::new("diskstate");
+        let path = dir.join("file");
+        fs::write(&path, "fnord\n").unwrap();
+        let state = DiskState::of(&path).unwrap();
+        assert!(!state.has_changed(&path));
+
+        // same contents, new modification time
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        fs::write(&path, "fnord\n").unwrap();
+        assert!(!state.has_changed(&path));
+
+        fs::write(&path, "fnord\nbar\n").unwrap();
+        assert!(state.has_changed(&path));
+
+        fs::remove_file(&path).unwrap();
+        assert!(!state.has_changed(&path));
+    }
+}
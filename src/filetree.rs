@@ -0,0 +1,590 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::Rect,
+    text::Line,
+    widgets::{Block, List, Paragraph},
+};
+
+use crate::{
+    app::ApplicationState, bufferentry::BufferEntry, filewalker::FileWalker,
+    pathinput::absolute_path, theme::Slot,
+};
+
+/// Width of the file tree panel in columns, including its border.
+pub const FILE_TREE_WIDTH: u16 = 30;
+
+/// A single visible line of the tree.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TreeRow {
+    /// Path relative to the walker root.
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub depth: usize,
+}
+
+/// An operation on the selected entry that waits for user input.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum TreePrompt {
+    #[default]
+    None,
+    /// Create a new file, or a directory if the name ends with '/'.
+    Create(String),
+    Rename(String),
+    ConfirmDelete,
+}
+
+/// The result of a key press in the file tree.
+#[derive(Debug, PartialEq)]
+pub enum TreeAction {
+    None,
+    /// A file was opened into a buffer, the caller should switch to editing.
+    OpenedFile,
+    /// The tree gave the focus back to the buffer view.
+    Unfocus,
+}
+
+/// A toggleable side panel that shows the directory tree of the working
+/// directory.
+#[derive(Default)]
+pub struct FileTree {
+    pub visible: bool,
+    pub focused: bool,
+    expanded: HashSet<PathBuf>,
+    rows: Vec<TreeRow>,
+    selected: usize,
+    prompt: TreePrompt,
+    status: Option<String>,
+}
+
+/// The part of the path of `buffer` below `path`, an absolute file or
+/// directory. Empty for `path` itself, `None` for buffers elsewhere.
+fn path_below<'a>(buffer: &'a BufferEntry, path: &Path) -> Option<&'a Path> {
+    buffer.path.as_deref()?.strip_prefix(path).ok()
+}
+
+/// Returns true if `buffer` shows the file at `path` (relative to the walker root).
+fn shows_file(buffer: &BufferEntry, walker: &FileWalker, path: &Path) -> bool {
+    buffer.path.as_deref() == Some(absolute_path(&walker.full_path(path)).as_path())
+}
+
+impl FileTree {
+    /// Cycle through hidden -> shown and focused -> hidden. If the tree is
+    /// shown but not focused, it only receives the focus.
+    pub fn toggle(&mut self, walker: &FileWalker) {
+        if !self.visible {
+            self.visible = true;
+            self.focused = true;
+            self.refresh(walker);
+        } else if !self.focused {
+            self.focused = true;
+        } else {
+            self.visible = false;
+            self.focused = false;
+        }
+    }
+
+    /// True while a name or the deletion is asked for.
+    pub fn has_prompt(&self) -> bool {
+        self.prompt != TreePrompt::None
+    }
+
+    /// Rebuild the visible rows from disk.
+    pub fn refresh(&mut self, walker: &FileWalker) {
+        self.rows.clear();
+        self.add_rows(walker, Path::new(""), 0);
+        self.selected = self.selected.min(self.rows.len().saturating_sub(1));
+    }
+
+    fn add_rows(&mut self, walker: &FileWalker, dir: &Path, depth: usize) {
+        for entry in walker.list_dir(dir) {
+            let expand = entry.is_dir && self.expanded.contains(&entry.path);
+            self.rows.push(TreeRow {
+                path: entry.path.clone(),
+                is_dir: entry.is_dir,
+                depth,
+            });
+            if expand {
+                self.add_rows(walker, &entry.path, depth + 1);
+            }
+        }
+    }
+
+    fn selected_row(&self) -> Option<&TreeRow> {
+        self.rows.get(self.selected)
+    }
+
+    /// The directory new entries are created in: the selected directory, or
+    /// the parent of the selected file.
+    fn target_dir(&self) -> PathBuf {
+        match self.selected_row() {
+            Some(row) if row.is_dir => row.path.clone(),
+            Some(row) => row.path.parent().map(Path::to_path_buf).unwrap_or_default(),
+            None => PathBuf::new(),
+        }
+    }
+
+    fn set_expanded(&mut self, walker: &FileWalker, expanded: bool) {
+        if let Some(row) = self.selected_row().filter(|r| r.is_dir) {
+            let path = row.path.clone();
+            if expanded {
+                self.expanded.insert(path);
+            } else {
+                self.expanded.remove(&path);
+            }
+            self.refresh(walker);
+        }
+    }
+
+    fn open_selected(&mut self, app_state: &mut ApplicationState) -> TreeAction {
+        let Some(row) = self.selected_row().cloned() else {
+            return TreeAction::None;
+        };
+
+        if row.is_dir {
+            let expanded = !self.expanded.contains(&row.path);
+            self.set_expanded(&app_state.file_walker, expanded);
+            return TreeAction::None;
+        }
+
+        let existing = app_state
+            .buffers
+            .iter()
+            .position(|b| shows_file(b, &app_state.file_walker, &row.path));
+
+        match existing {
+            Some(id) => app_state.current_buffer = id,
+            None => {
+                let path = app_state.file_walker.full_path(&row.path);
+                match BufferEntry::from_file(path) {
+                    Ok(buffer) => app_state.open_buffer(buffer),
+                    Err(e) => {
+                        self.status = Some(e.to_string());
+                        return TreeAction::None;
+                    }
+                }
+            }
+        }
+        self.focused = false;
+        TreeAction::OpenedFile
+    }
+
+    fn apply_prompt(&mut self, app_state: &mut ApplicationState) {
+        let root = app_state.file_walker.root().to_path_buf();
+        let result = match std::mem::take(&mut self.prompt) {
+            TreePrompt::None => Ok(()),
+            TreePrompt::Create(name) => {
+                let path = self.target_dir().join(name.trim());
+                let created = if name.ends_with('/') {
+                    fs::create_dir_all(root.join(&path))
+                } else {
+                    path.parent()
+                        .map_or(Ok(()), |p| fs::create_dir_all(root.join(p)))
+                        .and_then(|_| fs::File::create_new(root.join(&path)).map(|_| ()))
+                };
+                if created.is_ok()
+                    && let Some(parent) = path.parent()
+                {
+                    self.expanded.insert(parent.to_path_buf());
+                }
+                created
+            }
+            TreePrompt::Rename(name) => match self.selected_row().cloned() {
+                Some(row) => {
+                    let new_path = row
+                        .path
+                        .parent()
+                        .map(|p| p.join(name.trim()))
+                        .unwrap_or_else(|| PathBuf::from(name.trim()));
+                    let renamed = fs::rename(root.join(&row.path), root.join(&new_path));
+                    if renamed.is_ok() {
+                        let walker = &app_state.file_walker;
+                        let old_path = absolute_path(&walker.full_path(&row.path));
+                        let new_path = absolute_path(&walker.full_path(&new_path));
+                        // a renamed directory takes the files open below it along
+                        for buffer in &mut app_state.buffers {
+                            let moved = path_below(buffer, &old_path).map(|rest| {
+                                match rest.as_os_str().is_empty() {
+                                    true => new_path.clone(),
+                                    false => new_path.join(rest),
+                                }
+                            });
+                            if let Some(moved) = moved {
+                                buffer.file_moved(&moved);
+                            }
+                        }
+                    }
+                    renamed
+                }
+                None => Ok(()),
+            },
+            TreePrompt::ConfirmDelete => match self.selected_row().cloned() {
+                Some(row) => {
+                    let deleted = if row.is_dir {
+                        fs::remove_dir_all(root.join(&row.path))
+                    } else {
+                        fs::remove_file(root.join(&row.path))
+                    };
+                    if deleted.is_ok() {
+                        let path = absolute_path(&app_state.file_walker.full_path(&row.path));
+                        // the text of open files is kept, saving it writes
+                        // the file again
+                        for buffer in &mut app_state.buffers {
+                            if path_below(buffer, &path).is_some() {
+                                buffer.modified = true;
+                                buffer.disk_state = None;
+                            }
+                        }
+                    }
+                    deleted
+                }
+                None => Ok(()),
+            },
+        };
+
+        self.status = result.err().map(|e| e.to_string());
+        self.refresh(&app_state.file_walker);
+    }
+
+    fn handle_prompt_key(&mut self, key_event: KeyEvent, app_state: &mut ApplicationState) {
+        if self.prompt == TreePrompt::ConfirmDelete {
+            match key_event.code {
+                KeyCode::Char('y') => self.apply_prompt(app_state),
+                _ => self.prompt = TreePrompt::None,
+            }
+            return;
+        }
+
+        match key_event.code {
+            KeyCode::Enter => {
+                if let TreePrompt::Create(text) | TreePrompt::Rename(text) = &self.prompt
+                    && text.trim().trim_end_matches('/').is_empty()
+                {
+                    self.prompt = TreePrompt::None;
+                    self.status = Some("A name is needed".to_string());
+                } else {
+                    self.apply_prompt(app_state);
+                }
+            }
+            KeyCode::Char(c) => {
+                if let TreePrompt::Create(text) | TreePrompt::Rename(text) = &mut self.prompt {
+                    text.push(c);
+                }
+            }
+            KeyCode::Backspace => {
+                if let TreePrompt::Create(text) | TreePrompt::Rename(text) = &mut self.prompt {
+                    text.pop();
+                }
+            }
+            KeyCode::Esc => self.prompt = TreePrompt::None,
+            _ => (),
+        }
+    }
+
+    pub fn handle_key_event(
+        &mut self,
+        key_event: KeyEvent,
+        app_state: &mut ApplicationState,
+    ) -> TreeAction {
+        if self.prompt != TreePrompt::None {
+            self.handle_prompt_key(key_event, app_state);
+            return TreeAction::None;
+        }
+
+        self.status = None;
+        match key_event.code {
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => {
+                self.selected = (self.selected + 1).min(self.rows.len().saturating_sub(1))
+            }
+            KeyCode::Right => self.set_expanded(&app_state.file_walker, true),
+            KeyCode::Left => self.set_expanded(&app_state.file_walker, false),
+            KeyCode::Enter => return self.open_selected(app_state),
+            KeyCode::Char('a') => self.prompt = TreePrompt::Create(String::new()),
+            KeyCode::Char('r') => {
+                if let Some(row) = self.selected_row() {
+                    let name = row.path.file_name().unwrap_or_default();
+                    self.prompt = TreePrompt::Rename(name.to_string_lossy().to_string());
+                }
+            }
+            KeyCode::Char('d') if self.selected_row().is_some() => {
+                self.prompt = TreePrompt::ConfirmDelete
+            }
+            KeyCode::Char('g') => self.refresh(&app_state.file_walker),
+            KeyCode::Char('q') | KeyCode::Esc => {
+                self.focused = false;
+                return TreeAction::Unfocus;
+            }
+            _ => (),
+        }
+        TreeAction::None
+    }
+
+    fn marker(&self, row: &TreeRow, app_state: &ApplicationState) -> &'static str {
+        let buffer = app_state
+            .buffers
+            .iter()
+            .find(|b| shows_file(b, &app_state.file_walker, &row.path));
+        match buffer {
+            Some(b) if b.modified => "● ",
+            Some(_) => "○ ",
+            None => "  ",
+        }
+    }
+
+    pub fn render(&self, frame: &mut ratatui::Frame, dest: Rect, app_state: &ApplicationState) {
+        let title = if self.focused {
+            "Files (a/r/d/Esc)"
+        } else {
+            "Files"
+        };
+        let block = Block::bordered().title(title);
+        let inner = block.inner(dest);
+        frame.render_widget(block, dest);
+
+        // keep the selected row visible
+        let height = inner.height.saturating_sub(1) as usize;
+        let skip = (self.selected + 1).saturating_sub(height);
+
+        let items: Vec<Line> = self
+            .rows
+            .iter()
+            .enumerate()
+            .skip(skip)
+            .take(height)
+            .map(|(id, row)| {
+                let name = row.path.file_name().unwrap_or_default().to_string_lossy();
+                let icon = if !row.is_dir {
+                    "  "
+                } else if self.expanded.contains(&row.path) {
+                    "▾ "
+                } else {
+                    "▸ "
+                };
+                let text = format!(
+                    "{}{}{}{}{}",
+                    self.marker(row, app_state),
+                    "  ".repeat(row.depth),
+                    icon,
+                    name,
+                    if row.is_dir { "/" } else { "" }
+                );
+                let mut line = Line::from(text);
+                if id == self.selected && self.focused {
+                    line = line.style(app_state.theme.style(Slot::TreeFocused));
+                } else if id == self.selected {
+                    line = line.style(app_state.theme.style(Slot::TreeSelected));
+                }
+                line
+            })
+            .collect();
+        frame.render_widget(List::new(items), inner);
+
+        let prompt = match &self.prompt {
+            TreePrompt::None => self.status.clone(),
+            TreePrompt::Create(name) => Some(format!("New: {}_", name)),
+            TreePrompt::Rename(name) => Some(format!("Rename: {}_", name)),
+            TreePrompt::ConfirmDelete => Some("Delete? (y/n)".to_string()),
+        };
+        if let Some(prompt) = prompt {
+            let y = inner.y + inner.height.saturating_sub(1);
+            frame.render_widget(
+                Paragraph::new(prompt).style(app_state.theme.style(Slot::Popup)),
+                Rect::new(inner.x, y, inner.width, 1),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    fn make_tree_dir(name: &str) -> TempDir {
+        let dir = TempDir::new(&format!("tree_{}", name));
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(dir.join("Cargo.toml"), "").unwrap();
+        dir
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, crossterm::event::KeyModifiers::NONE)
+    }
+
+    fn type_text(tree: &mut FileTree, app_state: &mut ApplicationState, text: &str) {
+        for c in text.chars() {
+            tree.handle_key_event(key(KeyCode::Char(c)), app_state);
+        }
+        tree.handle_key_event(key(KeyCode::Enter), app_state);
+    }
+
+    #[test]
+    pub fn rows_list_directories_first_and_expand_on_enter() {
+        let dir = make_tree_dir("expand");
+        let root = dir.path();
+        let mut app_state = ApplicationState {
+            file_walker: FileWalker::new(root),
+            ..Default::default()
+        };
+        let mut tree = FileTree::default();
+        tree.toggle(&app_state.file_walker);
+        assert_eq!(tree.rows.len(), 2);
+        assert!(tree.rows[0].is_dir);
+
+        tree.handle_key_event(key(KeyCode::Enter), &mut app_state);
+        assert_eq!(tree.rows.len(), 3);
+        assert_eq!(tree.rows[1].path, PathBuf::from("src/main.rs"));
+        assert_eq!(tree.rows[1].depth, 1);
+    }
+
+    #[test]
+    pub fn enter_on_file_opens_buffer() {
+        let dir = make_tree_dir("open");
+        let root = dir.path();
+        let mut app_state = ApplicationState {
+            file_walker: FileWalker::new(root),
+            ..Default::default()
+        };
+        let mut tree = FileTree::default();
+        tree.toggle(&app_state.file_walker);
+        tree.handle_key_event(key(KeyCode::Down), &mut app_state);
+        let action = tree.handle_key_event(key(KeyCode::Enter), &mut app_state);
+
+        assert_eq!(action, TreeAction::OpenedFile);
+        assert_eq!(app_state.buffers.len(), 1);
+        assert_eq!(app_state.buffers[0].display_name(), "Cargo.toml");
+    }
+
+    #[test]
+    pub fn can_create_rename_and_delete_files() {
+        let dir = make_tree_dir("edit");
+        let root = dir.path();
+        let mut app_state = ApplicationState {
+            file_walker: FileWalker::new(root),
+            ..Default::default()
+        };
+        let mut tree = FileTree::default();
+        tree.toggle(&app_state.file_walker);
+
+        // selected row is "src", so the file is created inside of it
+        tree.handle_key_event(key(KeyCode::Char('a')), &mut app_state);
+        type_text(&mut tree, &mut app_state, "lib.rs");
+        assert!(root.join("src/lib.rs").exists());
+
+        tree.handle_key_event(key(KeyCode::Char('a')), &mut app_state);
+        type_text(&mut tree, &mut app_state, "bin/");
+        assert!(root.join("src/bin").is_dir());
+
+        let lib_row = tree
+            .rows
+            .iter()
+            .position(|r| r.path == Path::new("src/lib.rs"))
+            .unwrap();
+        tree.selected = lib_row;
+        tree.handle_key_event(key(KeyCode::Char('r')), &mut app_state);
+        for _ in 0.."lib.rs".len() {
+            tree.handle_key_event(key(KeyCode::Backspace), &mut app_state);
+        }
+        type_text(&mut tree, &mut app_state, "util.rs");
+        assert!(root.join("src/util.rs").exists());
+        assert!(!root.join("src/lib.rs").exists());
+
+        let util_row = tree
+            .rows
+            .iter()
+            .position(|r| r.path == Path::new("src/util.rs"))
+            .unwrap();
+        tree.selected = util_row;
+        tree.handle_key_event(key(KeyCode::Char('d')), &mut app_state);
+        tree.handle_key_event(key(KeyCode::Char('n')), &mut app_state);
+        assert!(root.join("src/util.rs").exists());
+        tree.handle_key_event(key(KeyCode::Char('d')), &mut app_state);
+        tree.handle_key_event(key(KeyCode::Char('y')), &mut app_state);
+        assert!(!root.join("src/util.rs").exists());
+    }
+
+    #[test]
+    pub fn prompts_cancel_on_esc_and_need_a_name() {
+        let dir = make_tree_dir("prompts");
+        let root = dir.path();
+        let mut app_state = ApplicationState {
+            file_walker: FileWalker::new(root),
+            ..Default::default()
+        };
+        let mut tree = FileTree::default();
+        tree.toggle(&app_state.file_walker);
+
+        tree.handle_key_event(key(KeyCode::Char('a')), &mut app_state);
+        tree.handle_key_event(key(KeyCode::Char('x')), &mut app_state);
+        tree.handle_key_event(key(KeyCode::Esc), &mut app_state);
+        assert_eq!(tree.prompt, TreePrompt::None);
+        assert!(!root.join("src/x").exists());
+
+        tree.handle_key_event(key(KeyCode::Char('a')), &mut app_state);
+        type_text(&mut tree, &mut app_state, "  ");
+        assert_eq!(tree.prompt, TreePrompt::None);
+        assert_eq!(tree.status.as_deref(), Some("A name is needed"));
+
+        // selected row is "src"
+        tree.handle_key_event(key(KeyCode::Char('r')), &mut app_state);
+        for _ in 0.."src".len() {
+            tree.handle_key_event(key(KeyCode::Backspace), &mut app_state);
+        }
+        type_text(&mut tree, &mut app_state, "");
+        assert!(root.join("src").is_dir());
+        assert_eq!(tree.status.as_deref(), Some("A name is needed"));
+    }
+
+    #[test]
+    pub fn deleting_a_directory_marks_its_open_buffers() {
+        let dir = make_tree_dir("delete_dir");
+        let root = dir.path();
+        let mut app_state = ApplicationState {
+            file_walker: FileWalker::new(root),
+            ..Default::default()
+        };
+        app_state.open_buffer(BufferEntry::from_file(root.join("src/main.rs")).unwrap());
+        app_state.open_buffer(BufferEntry::from_file(root.join("Cargo.toml")).unwrap());
+        let mut tree = FileTree::default();
+        tree.toggle(&app_state.file_walker);
+
+        // selected row is "src"
+        tree.handle_key_event(key(KeyCode::Char('d')), &mut app_state);
+        tree.handle_key_event(key(KeyCode::Char('y')), &mut app_state);
+        assert!(!root.join("src").exists());
+        assert!(app_state.buffers[0].modified);
+        assert!(!app_state.buffers[1].modified);
+    }
+
+    #[test]
+    pub fn renaming_a_directory_moves_its_open_buffers() {
+        let dir = make_tree_dir("move_dir");
+        let root = dir.path();
+        let mut app_state = ApplicationState {
+            file_walker: FileWalker::new(root),
+            ..Default::default()
+        };
+        app_state.open_buffer(BufferEntry::from_file(root.join("src/main.rs")).unwrap());
+        let mut tree = FileTree::default();
+        tree.toggle(&app_state.file_walker);
+
+        // selected row is "src"
+        tree.handle_key_event(key(KeyCode::Char('r')), &mut app_state);
+        for _ in 0.."src".len() {
+            tree.handle_key_event(key(KeyCode::Backspace), &mut app_state);
+        }
+        type_text(&mut tree, &mut app_state, "code");
+        assert!(root.join("code/main.rs").exists());
+        assert_eq!(
+            app_state.buffers[0].path,
+            Some(absolute_path(&root.join("code/main.rs")))
+        );
+    }
+}
@@ -0,0 +1,473 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Names of the files that contribute ignore patterns, in the order they are
+/// applied. `.muignore` is read last so it can override the others.
+const IGNORE_FILES: [&str; 3] = [".gitignore", ".ignore", ".muignore"];
+
+/// Directories that are never walked, regardless of any ignore files.
+const ALWAYS_IGNORED: [&str; 1] = [".git"];
+
+/// A single pattern line of an ignore file.
+#[derive(Clone, Debug)]
+struct IgnorePattern {
+    /// Directory the pattern was defined in, relative to the walk root.
+    base: PathBuf,
+    glob: String,
+    negated: bool,
+    dir_only: bool,
+    /// Anchored patterns are matched against the path relative to `base`,
+    /// all others only against the file name.
+    anchored: bool,
+}
+
+impl IgnorePattern {
+    fn parse(line: &str, base: &Path) -> Option<IgnorePattern> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+
+        let anchored = line.contains('/');
+        let glob = line.strip_prefix('/').unwrap_or(line).to_string();
+        if glob.is_empty() {
+            return None;
+        }
+
+        Some(IgnorePattern {
+            base: base.to_path_buf(),
+            glob,
+            negated,
+            dir_only,
+            anchored,
+        })
+    }
+
+    /// Returns true if this pattern applies to `path` (relative to the walk root).
+    fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+
+        let Ok(relative) = path.strip_prefix(&self.base) else {
+            return false;
+        };
+
+        if self.anchored {
+            glob_match(&self.glob, &path_to_slashes(relative))
+        } else {
+            relative
+                .file_name()
+                .map(|name| glob_match(&self.glob, &name.to_string_lossy()))
+                .unwrap_or(false)
+        }
+    }
+}
+
+/// The set of ignore patterns that apply to one directory of the walk.
+#[derive(Clone, Debug, Default)]
+pub struct IgnoreRules {
+    patterns: Vec<IgnorePattern>,
+}
+
+impl IgnoreRules {
+    /// Add all patterns from `contents`, which were read from an ignore file
+    /// located in `base` (relative to the walk root).
+    pub fn add_patterns(&mut self, contents: &str, base: &Path) {
+        self.patterns.extend(
+            contents
+                .lines()
+                .filter_map(|l| IgnorePattern::parse(l, base)),
+        );
+    }
+
+    fn load_dir(&mut self, root: &Path, relative_dir: &Path, stamps: &mut Vec<Stamp>) {
+        for ignore_file in IGNORE_FILES {
+            let path = root.join(relative_dir).join(ignore_file);
+            if let Ok(contents) = fs::read_to_string(&path) {
+                stamps.push(Stamp::of(path));
+                self.add_patterns(&contents, relative_dir);
+            }
+        }
+    }
+
+    /// Returns true if `path` (relative to the walk root) is ignored. The last
+    /// matching pattern wins, so negated patterns can re-include files.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if is_dir
+            && path
+                .file_name()
+                .is_some_and(|name| ALWAYS_IGNORED.iter().any(|i| name == *i))
+        {
+            return true;
+        }
+
+        self.patterns
+            .iter()
+            .rev()
+            .find(|p| p.matches(path, is_dir))
+            .is_some_and(|p| !p.negated)
+    }
+}
+
+/// A file system object whose modification time is used to detect changes.
+#[derive(Clone, Debug, PartialEq)]
+struct Stamp {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl Stamp {
+    fn of(path: PathBuf) -> Stamp {
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+        Stamp { path, modified }
+    }
+
+    fn is_current(&self) -> bool {
+        fs::metadata(&self.path).and_then(|m| m.modified()).ok() == self.modified
+    }
+}
+
+struct WalkCache {
+    show_hidden: bool,
+    stamps: Vec<Stamp>,
+    files: Vec<PathBuf>,
+}
+
+/// One entry returned by `FileWalker::list_dir`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WalkEntry {
+    /// Path relative to the walk root.
+    pub path: PathBuf,
+    pub is_dir: bool,
+}
+
+/// Walks the files below a root directory, honoring `.gitignore`, `.ignore`
+/// and `.muignore` files as well as the hidden file setting.
+///
+/// The result of a full walk is cached and only recomputed once one of the
+/// walked directories or ignore files changed on disk.
+pub struct FileWalker {
+    root: PathBuf,
+    pub show_hidden: bool,
+    cache: Option<WalkCache>,
+}
+
+impl Default for FileWalker {
+    fn default() -> Self {
+        FileWalker::new(".")
+    }
+}
+
+impl FileWalker {
+    pub fn new(root: impl Into<PathBuf>) -> FileWalker {
+        FileWalker {
+            root: root.into(),
+            show_hidden: false,
+            cache: None,
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The path of `relative` (as returned by the walker) as seen from the
+    /// current working directory.
+    pub fn full_path(&self, relative: &Path) -> PathBuf {
+        if self.root == Path::new(".") {
+            relative.to_path_buf()
+        } else {
+            self.root.join(relative)
+        }
+    }
+
+    pub fn toggle_hidden(&mut self) {
+        self.show_hidden = !self.show_hidden;
+    }
+
+    fn cache_is_valid(&self) -> bool {
+        match &self.cache {
+            Some(cache) => {
+                cache.show_hidden == self.show_hidden && cache.stamps.iter().all(Stamp::is_current)
+            }
+            None => false,
+        }
+    }
+
+    /// All files below the root that are not ignored, relative to the root
+    /// and sorted by path.
+    pub fn files(&mut self) -> &[PathBuf] {
+        if !self.cache_is_valid() {
+            let mut stamps = Vec::new();
+            let mut files = Vec::new();
+            self.walk_dir(
+                Path::new(""),
+                IgnoreRules::default(),
+                &mut stamps,
+                &mut files,
+            );
+            files.sort();
+            self.cache = Some(WalkCache {
+                show_hidden: self.show_hidden,
+                stamps,
+                files,
+            });
+        }
+
+        &self.cache.as_ref().unwrap().files
+    }
+
+    fn walk_dir(
+        &self,
+        relative_dir: &Path,
+        mut rules: IgnoreRules,
+        stamps: &mut Vec<Stamp>,
+        files: &mut Vec<PathBuf>,
+    ) {
+        let dir = self.root.join(relative_dir);
+        stamps.push(Stamp::of(dir.clone()));
+        rules.load_dir(&self.root, relative_dir, stamps);
+
+        for entry in self.visible_entries(&dir, relative_dir, &rules) {
+            if entry.is_dir {
+                self.walk_dir(&entry.path, rules.clone(), stamps, files);
+            } else {
+                files.push(entry.path);
+            }
+        }
+    }
+
+    fn visible_entries(
+        &self,
+        dir: &Path,
+        relative_dir: &Path,
+        rules: &IgnoreRules,
+    ) -> Vec<WalkEntry> {
+        let Ok(read_dir) = fs::read_dir(dir) else {
+            return Vec::new();
+        };
+
+        let mut entries: Vec<WalkEntry> = read_dir
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name();
+                if !self.show_hidden && name.to_string_lossy().starts_with('.') {
+                    return None;
+                }
+                let is_dir = entry.file_type().ok()?.is_dir();
+                let path = relative_dir.join(name);
+                if rules.is_ignored(&path, is_dir) {
+                    return None;
+                }
+                Some(WalkEntry { path, is_dir })
+            })
+            .collect();
+
+        // directories first, then files, each sorted by name
+        entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then(a.path.cmp(&b.path)));
+        entries
+    }
+
+    /// The non-ignored entries of a single directory (relative to the root),
+    /// directories first. Used for views that expand the tree lazily.
+    pub fn list_dir(&self, relative_dir: &Path) -> Vec<WalkEntry> {
+        let mut rules = IgnoreRules::default();
+        let mut stamps = Vec::new();
+        let mut current = PathBuf::new();
+        rules.load_dir(&self.root, &current, &mut stamps);
+        for component in relative_dir.components() {
+            current.push(component);
+            rules.load_dir(&self.root, &current, &mut stamps);
+        }
+
+        self.visible_entries(&self.root.join(relative_dir), relative_dir, &rules)
+    }
+}
+
+fn path_to_slashes(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Match `text` against a gitignore style glob. `*` and `?` do not match `/`,
+/// `**` matches any number of path segments and `[...]` matches a character
+/// class.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    glob_match_from(&pattern, &text)
+}
+
+fn glob_match_from(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            // "**/" may also match zero directories
+            let rest = &pattern[2..];
+            let rest_after_slash = rest.strip_prefix(&['/']).unwrap_or(rest);
+            if glob_match_from(rest_after_slash, text) {
+                return true;
+            }
+            (0..text.len()).any(|i| {
+                glob_match_from(rest, &text[i + 1..]) || {
+                    text[i] == '/' && glob_match_from(rest_after_slash, &text[i + 1..])
+                }
+            })
+        }
+        Some('*') => {
+            let rest = &pattern[1..];
+            for i in 0..=text.len() {
+                if glob_match_from(rest, &text[i..]) {
+                    return true;
+                }
+                if i < text.len() && text[i] == '/' {
+                    break;
+                }
+            }
+            false
+        }
+        Some('?') => {
+            !text.is_empty() && text[0] != '/' && glob_match_from(&pattern[1..], &text[1..])
+        }
+        Some('[') => {
+            let Some(close) = pattern.iter().skip(1).position(|c| *c == ']') else {
+                return text.first() == Some(&'[') && glob_match_from(&pattern[1..], &text[1..]);
+            };
+            let Some(c) = text.first() else {
+                return false;
+            };
+            let class = &pattern[1..close + 1];
+            let (negated, class) = match class.first() {
+                Some('!') | Some('^') => (true, &class[1..]),
+                _ => (false, class),
+            };
+            let mut matched = false;
+            let mut i = 0;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == '-' {
+                    matched |= class[i] <= *c && *c <= class[i + 2];
+                    i += 3;
+                } else {
+                    matched |= class[i] == *c;
+                    i += 1;
+                }
+            }
+            matched != negated && glob_match_from(&pattern[close + 2..], &text[1..])
+        }
+        Some(p) => text.first() == Some(p) && glob_match_from(&pattern[1..], &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    fn rules(contents: &str) -> IgnoreRules {
+        let mut rules = IgnoreRules::default();
+        rules.add_patterns(contents, Path::new(""));
+        rules
+    }
+
+    #[test]
+    pub fn glob_star_does_not_cross_directories() {
+        assert!(glob_match("*.rs", "main.rs"));
+        assert!(!glob_match("*.rs", "src/main.rs"));
+        assert!(glob_match("src/*.rs", "src/main.rs"));
+    }
+
+    #[test]
+    pub fn glob_double_star_matches_any_depth() {
+        assert!(glob_match("**/foo", "foo"));
+        assert!(glob_match("**/foo", "a/b/foo"));
+        assert!(glob_match("a/**/b", "a/b"));
+        assert!(glob_match("a/**/b", "a/x/y/b"));
+        assert!(glob_match("a/**", "a/x/y"));
+    }
+
+    #[test]
+    pub fn glob_supports_question_mark_and_classes() {
+        assert!(glob_match("fo?", "foo"));
+        assert!(glob_match("[fb]oo", "boo"));
+        assert!(!glob_match("[!fb]oo", "boo"));
+        assert!(glob_match("file[0-9]", "file7"));
+    }
+
+    #[test]
+    pub fn unanchored_pattern_matches_at_any_depth() {
+        let r = rules("target\n*.log");
+        assert!(r.is_ignored(Path::new("target"), true));
+        assert!(r.is_ignored(Path::new("sub/target"), true));
+        assert!(r.is_ignored(Path::new("sub/debug.log"), false));
+        assert!(!r.is_ignored(Path::new("src/main.rs"), false));
+    }
+
+    #[test]
+    pub fn anchored_pattern_only_matches_from_base() {
+        let r = rules("/build\ndocs/*.html");
+        assert!(r.is_ignored(Path::new("build"), true));
+        assert!(!r.is_ignored(Path::new("sub/build"), true));
+        assert!(r.is_ignored(Path::new("docs/index.html"), false));
+    }
+
+    #[test]
+    pub fn dir_only_pattern_skips_files() {
+        let r = rules("out/");
+        assert!(r.is_ignored(Path::new("out"), true));
+        assert!(!r.is_ignored(Path::new("out"), false));
+    }
+
+    #[test]
+    pub fn negated_pattern_reincludes_file() {
+        let r = rules("*.log\n!keep.log\n# comment");
+        assert!(r.is_ignored(Path::new("debug.log"), false));
+        assert!(!r.is_ignored(Path::new("keep.log"), false));
+    }
+
+    #[test]
+    pub fn git_dir_is_always_ignored() {
+        let r = IgnoreRules::default();
+        assert!(r.is_ignored(Path::new(".git"), true));
+    }
+
+    #[test]
+    pub fn walker_honors_ignore_files_and_hidden_toggle() {
+        let dir = TempDir::new("walker");
+        let root = dir.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("target/debug")).unwrap();
+        fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        fs::write(root.join(".muignore"), "*.bak\n").unwrap();
+        fs::write(root.join("src/main.rs"), "").unwrap();
+        fs::write(root.join("src/main.rs.bak"), "").unwrap();
+        fs::write(root.join("target/debug/mu"), "").unwrap();
+        fs::write(root.join(".hidden"), "").unwrap();
+
+        let mut walker = FileWalker::new(root);
+        assert_eq!(walker.files(), &[PathBuf::from("src/main.rs")]);
+
+        walker.toggle_hidden();
+        assert!(walker.files().contains(&PathBuf::from(".hidden")));
+
+        // adding a file invalidates the cache
+        walker.toggle_hidden();
+        fs::write(root.join("src/lib.rs"), "").unwrap();
+        assert!(walker.files().contains(&PathBuf::from("src/lib.rs")));
+    }
+}
@@ -0,0 +1,114 @@
+/// Score how well `candidate` matches `query`.
+///
+/// All characters of the query have to appear in the candidate in order
+/// (case insensitive), otherwise `None` is returned. Higher scores are better:
+/// consecutive matches and matches at the start of a path segment or word are
+/// rewarded, long candidates are penalized slightly.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    if query.is_empty() {
+        return Some(0);
+    }
+
+    let candidate_chars: Vec<char> = candidate.chars().collect();
+    let mut score: i64 = 0;
+    let mut pos = 0;
+    let mut last_match: Option<usize> = None;
+
+    for q in query.chars().flat_map(char::to_lowercase) {
+        let found = candidate_chars[pos..]
+            .iter()
+            .position(|c| c.to_lowercase().eq(std::iter::once(q)))?;
+        let index = pos + found;
+
+        score += 10;
+        if last_match.is_some_and(|last| last + 1 == index) {
+            score += 15;
+        }
+        if index == 0
+            || matches!(
+                candidate_chars[index - 1],
+                '/' | '\\' | '_' | '-' | '.' | ' '
+            )
+        {
+            score += 10;
+        }
+
+        last_match = Some(index);
+        pos = index + 1;
+    }
+
+    Some(score - candidate_chars.len() as i64)
+}
+
+/// Filter `candidates` by `query` and return them sorted by descending score.
+/// `bonus` is added to the score of every match, e.g. to prefer recently used
+/// files. Candidates with the same score keep their original order.
+pub fn fuzzy_filter<'a, T>(
+    query: &str,
+    candidates: impl IntoIterator<Item = &'a T>,
+    key: impl Fn(&T) -> String,
+    bonus: impl Fn(&T) -> i64,
+) -> Vec<&'a T>
+where
+    T: 'a,
+{
+    let mut scored: Vec<(i64, &T)> = candidates
+        .into_iter()
+        .filter_map(|c| fuzzy_score(query, &key(c)).map(|s| (s + bonus(c), c)))
+        .collect();
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    scored.into_iter().map(|(_, c)| c).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn non_matching_candidate_yields_none() {
+        assert_eq!(fuzzy_score("xyz", "src/main.rs"), None);
+    }
+
+    #[test]
+    pub fn characters_must_appear_in_order() {
+        assert!(fuzzy_score("mrs", "src/main.rs").is_some());
+        assert!(fuzzy_score("srm", "main.rs").is_none());
+    }
+
+    #[test]
+    pub fn match_is_case_insensitive() {
+        assert!(fuzzy_score("README", "Readme.md").is_some());
+    }
+
+    #[test]
+    pub fn consecutive_match_beats_scattered_match() {
+        let consecutive = fuzzy_score("main", "src/main.rs").unwrap();
+        let scattered = fuzzy_score("main", "src/m_a_i_n.rs").unwrap();
+        assert!(consecutive > scattered);
+    }
+
+    #[test]
+    pub fn filter_sorts_by_score() {
+        let files = vec![
+            "src/normalmode.rs".to_string(),
+            "src/mode.rs".to_string(),
+            "Cargo.toml".to_string(),
+        ];
+        let result = fuzzy_filter("mode", &files, |f| f.clone(), |_| 0);
+        assert_eq!(result, vec![&files[1], &files[0]]);
+    }
+
+    #[test]
+    pub fn bonus_changes_ranking() {
+        let files = vec!["src/normalmode.rs".to_string(), "src/mode.rs".to_string()];
+        let result = fuzzy_filter(
+            "mode",
+            &files,
+            |f| f.clone(),
+            |f| {
+                if f == &files[0] { 100 } else { 0 }
+            },
+        );
+        assert_eq!(result, vec![&files[0], &files[1]]);
+    }
+}
@@ -0,0 +1,98 @@
+/// How lines are numbered in the gutter left of the text.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LineNumbers {
+    Off,
+    #[default]
+    Absolute,
+    /// The distance to the cursor line, for jumping up or down.
+    Relative,
+    /// Relative, except for the cursor line which shows its own number.
+    Hybrid,
+}
+
+impl LineNumbers {
+    pub fn parse(value: &str) -> Option<LineNumbers> {
+        match value {
+            "off" => Some(LineNumbers::Off),
+            "absolute" => Some(LineNumbers::Absolute),
+            "relative" => Some(LineNumbers::Relative),
+            "hybrid" => Some(LineNumbers::Hybrid),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LineNumbers::Off => "off",
+            LineNumbers::Absolute => "absolute",
+            LineNumbers::Relative => "relative",
+            LineNumbers::Hybrid => "hybrid",
+        }
+    }
+
+    /// The next numbering, for cycling through all of them.
+    pub fn next(&self) -> LineNumbers {
+        match self {
+            LineNumbers::Off => LineNumbers::Absolute,
+            LineNumbers::Absolute => LineNumbers::Relative,
+            LineNumbers::Relative => LineNumbers::Hybrid,
+            LineNumbers::Hybrid => LineNumbers::Off,
+        }
+    }
+
+    /// Columns taken by the gutter of a buffer with `num_lines` lines,
+    /// including a space between the numbers and the text.
+    pub fn gutter_width(&self, num_lines: usize) -> u16 {
+        if *self == LineNumbers::Off {
+            return 0;
+        }
+        let digits = num_lines.max(1).ilog10() + 1;
+        // keep the width stable for short files
+        digits.max(3) as u16 + 1
+    }
+
+    /// The number shown next to 0-based line `line`.
+    pub fn label(&self, line: usize, cursor_line: usize) -> String {
+        match self {
+            LineNumbers::Off => String::new(),
+            LineNumbers::Absolute => (line + 1).to_string(),
+            LineNumbers::Relative => line.abs_diff(cursor_line).to_string(),
+            LineNumbers::Hybrid if line == cursor_line => (line + 1).to_string(),
+            LineNumbers::Hybrid => line.abs_diff(cursor_line).to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn gutter_grows_with_line_count() {
+        assert_eq!(LineNumbers::Off.gutter_width(5000), 0);
+        assert_eq!(LineNumbers::Absolute.gutter_width(0), 4);
+        assert_eq!(LineNumbers::Absolute.gutter_width(999), 4);
+        assert_eq!(LineNumbers::Relative.gutter_width(1000), 5);
+        assert_eq!(LineNumbers::Hybrid.gutter_width(123456), 7);
+    }
+
+    #[test]
+    pub fn labels_follow_the_mode() {
+        assert_eq!(LineNumbers::Absolute.label(4, 2), "5");
+        assert_eq!(LineNumbers::Relative.label(4, 2), "2");
+        assert_eq!(LineNumbers::Relative.label(2, 2), "0");
+        assert_eq!(LineNumbers::Hybrid.label(2, 2), "3");
+        assert_eq!(LineNumbers::Hybrid.label(0, 2), "2");
+    }
+
+    #[test]
+    pub fn names_round_trip() {
+        let mut numbers = LineNumbers::Off;
+        for _ in 0..4 {
+            assert_eq!(LineNumbers::parse(numbers.name()), Some(numbers));
+            numbers = numbers.next();
+        }
+        assert_eq!(numbers, LineNumbers::Off);
+        assert_eq!(LineNumbers::parse("roman"), None);
+    }
+}
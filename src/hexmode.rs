@@ -0,0 +1,350 @@
+use crossterm::event::{KeyCode, KeyModifiers};
+use ratatui::{
+    layout::Rect,
+    text::{Line, Span},
+    widgets::Paragraph,
+};
+
+use crate::{
+    app::ApplicationState,
+    mode::EditorMode,
+    modeutil::{
+        refuse_read_only, render_mode_header, render_status_line, rotate_buffer, screen_areas,
+        without_sidebar,
+    },
+    theme::{Slot, Theme},
+};
+
+/// Bytes shown per row.
+const BYTES_PER_ROW: usize = 16;
+
+#[derive(Default, PartialEq)]
+enum HexPrompt {
+    #[default]
+    None,
+    Goto(String),
+    Search(String),
+}
+
+/// Shows binary buffers as offset, hex and ASCII columns. Typing hex digits
+/// overwrites the selected byte, one nibble at a time.
+#[derive(Default)]
+pub struct HexMode {
+    prompt: HexPrompt,
+    /// Set once the high nibble of the selected byte was typed.
+    low_nibble: bool,
+    last_search: Vec<u8>,
+}
+
+/// Parse an offset, in hex with a "0x" prefix or decimal otherwise.
+fn parse_offset(input: &str) -> Option<usize> {
+    let input = input.trim();
+    match input
+        .strip_prefix("0x")
+        .or_else(|| input.strip_prefix("0X"))
+    {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => input.parse().ok(),
+    }
+}
+
+/// Parse a search pattern: hex bytes like "de ad beef", or text in double
+/// quotes.
+fn parse_pattern(input: &str) -> Result<Vec<u8>, String> {
+    let input = input.trim();
+    if let Some(text) = input.strip_prefix('"') {
+        let text = text.strip_suffix('"').unwrap_or(text);
+        return Ok(text.as_bytes().to_vec());
+    }
+
+    let digits: Vec<char> = input.chars().filter(|c| !c.is_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) {
+        return Err("hex bytes need two digits each".to_string());
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let pair: String = pair.iter().collect();
+            u8::from_str_radix(&pair, 16).map_err(|_| format!("'{}' is not a hex byte", pair))
+        })
+        .collect()
+}
+
+/// Find `pattern` in `bytes`, starting at `from` and wrapping around at the end.
+fn find_pattern(bytes: &[u8], pattern: &[u8], from: usize) -> Option<usize> {
+    if pattern.is_empty() || pattern.len() > bytes.len() {
+        return None;
+    }
+    let starts = bytes.len() - pattern.len() + 1;
+    (from.min(starts)..starts)
+        .chain(0..from.min(starts))
+        .find(|&start| bytes[start..].starts_with(pattern))
+}
+
+impl HexMode {
+    /// Put the cursor on byte `offset` and scroll it into view.
+    fn goto_offset(&mut self, app_state: &mut ApplicationState, offset: usize) {
+        let height = (app_state.window_size.1 as usize).max(1);
+        let buffer = &mut app_state.buffers[app_state.current_buffer];
+        let len = buffer.binary.as_ref().map_or(0, Vec::len);
+
+        buffer.cursor_position = offset.min(len.saturating_sub(1));
+        let row = buffer.cursor_position / BYTES_PER_ROW;
+        if row < buffer.scroll_offset {
+            buffer.scroll_offset = row;
+        } else if row >= buffer.scroll_offset + height {
+            buffer.scroll_offset = row + 1 - height;
+        }
+        self.low_nibble = false;
+        app_state.pending_keys.clear();
+    }
+
+    fn move_cursor(&mut self, app_state: &mut ApplicationState, delta: isize) {
+        let cursor = app_state.buffers[app_state.current_buffer].cursor_position;
+        self.goto_offset(app_state, cursor.saturating_add_signed(delta));
+    }
+
+    /// Overwrite the next nibble of the selected byte with `digit`.
+    fn edit_nibble(&mut self, app_state: &mut ApplicationState, digit: u8) {
+        if refuse_read_only(app_state) {
+            return;
+        }
+        let buffer = &mut app_state.buffers[app_state.current_buffer];
+        let cursor = buffer.cursor_position;
+        let Some(byte) = buffer.binary.as_mut().and_then(|b| b.get_mut(cursor)) else {
+            return;
+        };
+
+        if self.low_nibble {
+            *byte = (*byte & 0xf0) | digit;
+            buffer.modified = true;
+            self.move_cursor(app_state, 1);
+        } else {
+            *byte = (*byte & 0x0f) | (digit << 4);
+            buffer.modified = true;
+            self.low_nibble = true;
+            app_state.pending_keys = format!("{:x}", digit);
+        }
+    }
+
+    fn search(&mut self, app_state: &mut ApplicationState, from: usize) {
+        let buffer = &app_state.buffers[app_state.current_buffer];
+        let bytes = buffer.binary.as_deref().unwrap_or_default();
+        match find_pattern(bytes, &self.last_search, from) {
+            Some(offset) => self.goto_offset(app_state, offset),
+            None => app_state.status_message = Some("Pattern not found".to_string()),
+        }
+    }
+
+    fn handle_prompt_key(&mut self, key: KeyCode, app_state: &mut ApplicationState) {
+        let (HexPrompt::Goto(input) | HexPrompt::Search(input)) = &mut self.prompt else {
+            return;
+        };
+
+        match key {
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Esc => self.prompt = HexPrompt::None,
+            KeyCode::Enter => match std::mem::take(&mut self.prompt) {
+                HexPrompt::Goto(input) => match parse_offset(&input) {
+                    Some(offset) => self.goto_offset(app_state, offset),
+                    None => app_state.status_message = Some(format!("Invalid offset '{}'", input)),
+                },
+                HexPrompt::Search(input) => match parse_pattern(&input) {
+                    Ok(pattern) => {
+                        self.last_search = pattern;
+                        let cursor = app_state.buffers[app_state.current_buffer].cursor_position;
+                        self.search(app_state, cursor);
+                    }
+                    Err(e) => app_state.status_message = Some(e),
+                },
+                HexPrompt::None => (),
+            },
+            _ => (),
+        }
+    }
+
+    fn render_row(&self, bytes: &[u8], row: usize, cursor: usize, theme: &Theme) -> Line<'static> {
+        let start = row * BYTES_PER_ROW;
+        let row_bytes = &bytes[start.min(bytes.len())..(start + BYTES_PER_ROW).min(bytes.len())];
+
+        let mut spans = vec![Span::styled(
+            format!("{:08x}  ", start),
+            theme.style(Slot::LineNumber),
+        )];
+        for i in 0..BYTES_PER_ROW {
+            let text = match row_bytes.get(i) {
+                Some(byte) => format!("{:02x}", byte),
+                None => "  ".to_string(),
+            };
+            let span = Span::raw(text);
+            spans.push(if start + i == cursor {
+                span.style(theme.style(Slot::HexCursor))
+            } else {
+                span
+            });
+            spans.push(Span::raw(if i == BYTES_PER_ROW / 2 - 1 {
+                "  "
+            } else {
+                " "
+            }));
+        }
+
+        spans.push(Span::raw(" |"));
+        for (i, byte) in row_bytes.iter().enumerate() {
+            let c = if byte.is_ascii_graphic() || *byte == b' ' {
+                *byte as char
+            } else {
+                '.'
+            };
+            let span = Span::raw(c.to_string());
+            spans.push(if start + i == cursor {
+                span.style(theme.style(Slot::HexCursor))
+            } else {
+                span
+            });
+        }
+        spans.push(Span::raw("|"));
+        Line::from(spans)
+    }
+
+    fn render_prompt(&self, frame: &mut ratatui::Frame, area: Rect, theme: &Theme) {
+        let text = match &self.prompt {
+            HexPrompt::None => return,
+            HexPrompt::Goto(input) => format!("Go to offset (0x.. for hex): {}_", input),
+            HexPrompt::Search(input) => format!("Search (hex bytes or \"text\"): {}_", input),
+        };
+        let line = Rect::new(area.x, area.bottom().saturating_sub(1), area.width, 1);
+        frame.render_widget(Paragraph::new(text).style(theme.style(Slot::Popup)), line);
+    }
+}
+
+impl EditorMode for HexMode {
+    fn mode_name(&self) -> &'static str {
+        "HEX"
+    }
+
+    /// True while the user types an offset or search pattern.
+    fn has_popup(&self) -> bool {
+        self.prompt != HexPrompt::None
+    }
+
+    fn handle_key_event(
+        &mut self,
+        key_event: crossterm::event::KeyEvent,
+        app_state: &mut ApplicationState,
+    ) {
+        if key_event.modifiers.contains(KeyModifiers::CONTROL) {
+            match key_event.code {
+                KeyCode::Left => rotate_buffer(app_state, -1),
+                KeyCode::Right => rotate_buffer(app_state, 1),
+                _ => (),
+            }
+            return;
+        }
+
+        if self.has_popup() {
+            self.handle_prompt_key(key_event.code, app_state);
+            return;
+        }
+
+        let page = app_state.window_size.1.max(1) as isize * BYTES_PER_ROW as isize;
+        let row = BYTES_PER_ROW as isize;
+        match key_event.code {
+            KeyCode::Left => self.move_cursor(app_state, -1),
+            KeyCode::Right => self.move_cursor(app_state, 1),
+            KeyCode::Up => self.move_cursor(app_state, -row),
+            KeyCode::Down => self.move_cursor(app_state, row),
+            KeyCode::PageUp => self.move_cursor(app_state, -page),
+            KeyCode::PageDown => self.move_cursor(app_state, page),
+            KeyCode::Char('g') => self.prompt = HexPrompt::Goto(String::new()),
+            KeyCode::Char('/') => self.prompt = HexPrompt::Search(String::new()),
+            KeyCode::Char('n') => {
+                let cursor = app_state.buffers[app_state.current_buffer].cursor_position;
+                self.search(app_state, cursor + 1);
+            }
+            KeyCode::Char(c) => {
+                if let Some(digit) = c.to_digit(16) {
+                    self.edit_nibble(app_state, digit as u8);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn render(&self, frame: &mut ratatui::Frame, app_state: &ApplicationState) {
+        let [header, body, status] = screen_areas(frame.area());
+        render_mode_header(frame, header, self.mode_name(), app_state);
+        render_status_line(frame, status, app_state);
+
+        let area = without_sidebar(body, app_state);
+        let buffer = &app_state.buffers[app_state.current_buffer];
+        let bytes = buffer.binary.as_deref().unwrap_or_default();
+        let rows = bytes.len().div_ceil(BYTES_PER_ROW).max(1);
+
+        let lines: Vec<Line> = (buffer.scroll_offset..rows)
+            .take(area.height as usize)
+            .map(|row| self.render_row(bytes, row, buffer.cursor_position, &app_state.theme))
+            .collect();
+        frame.render_widget(Paragraph::new(lines), area);
+        self.render_prompt(frame, area, &app_state.theme);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn parses_offsets() {
+        assert_eq!(parse_offset("0x1f"), Some(31));
+        assert_eq!(parse_offset(" 42 "), Some(42));
+        assert_eq!(parse_offset("zz"), None);
+    }
+
+    #[test]
+    pub fn parses_patterns() {
+        assert_eq!(
+            parse_pattern("de ad beef"),
+            Ok(vec![0xde, 0xad, 0xbe, 0xef])
+        );
+        assert_eq!(parse_pattern("\"ELF\""), Ok(b"ELF".to_vec()));
+        assert!(parse_pattern("abc").is_err());
+        assert!(parse_pattern("zz").is_err());
+    }
+
+    #[test]
+    pub fn search_wraps_around() {
+        let bytes = b"abcabc";
+        assert_eq!(find_pattern(bytes, b"bc", 0), Some(1));
+        assert_eq!(find_pattern(bytes, b"bc", 2), Some(4));
+        assert_eq!(find_pattern(bytes, b"bc", 5), Some(1));
+        assert_eq!(find_pattern(bytes, b"x", 0), None);
+    }
+
+    #[test]
+    pub fn hex_digits_overwrite_bytes() {
+        let mut app_state = ApplicationState {
+            window_size: (80, 10),
+            ..Default::default()
+        };
+        app_state.buffers.push(crate::bufferentry::BufferEntry {
+            binary: Some(vec![0x00, 0x11]),
+            ..Default::default()
+        });
+        let mut mode = HexMode::default();
+        for c in ['a', 'B', 'c'] {
+            mode.handle_key_event(
+                crossterm::event::KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE),
+                &mut app_state,
+            );
+        }
+
+        let buffer = &app_state.buffers[0];
+        assert_eq!(buffer.binary, Some(vec![0xab, 0xc1]));
+        assert_eq!(buffer.cursor_position, 1);
+        assert!(buffer.modified);
+    }
+}
@@ -0,0 +1,990 @@
+use std::{
+    cell::{Cell, RefCell},
+    ops::Range,
+    path::Path,
+};
+
+use crate::buffer::{Buffer, Changes, Listener};
+
+/// What a piece of highlighted text is, the renderer picks a style for it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scope {
+    Comment,
+    String,
+    Number,
+    Keyword,
+    Type,
+    /// Booleans and the like.
+    Constant,
+    Function,
+    /// Rust attributes, Python decorators and TOML table headers.
+    Attribute,
+    /// Shell variables.
+    Variable,
+    /// Keys in TOML and JSON.
+    Key,
+    Heading,
+    Emphasis,
+    Strong,
+    /// Inline code and code blocks in Markdown.
+    Code,
+    Link,
+}
+
+/// A highlighted range of characters in a line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    pub range: Range<usize>,
+    pub scope: Scope,
+}
+
+/// What is still open at the end of a line.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum State {
+    #[default]
+    Normal,
+    /// A block comment, nested `depth` levels deep.
+    Comment { depth: u8 },
+    /// A string opened by `Grammar::strings[rule]`.
+    String { rule: usize },
+    /// A fenced Markdown code block.
+    CodeBlock,
+}
+
+struct StringRule {
+    open: &'static str,
+    close: &'static str,
+    /// Backslash escapes the next character.
+    escapes: bool,
+    /// The string may continue on the next line.
+    multiline: bool,
+    /// Only a string if it looks like a character literal, so Rust
+    /// lifetimes are not taken for strings.
+    char_literal: bool,
+}
+
+const fn quoted(quote: &'static str, multiline: bool) -> StringRule {
+    StringRule {
+        open: quote,
+        close: quote,
+        escapes: true,
+        multiline,
+        char_literal: false,
+    }
+}
+
+/// How to highlight a language. Most languages only differ in their
+/// comments, strings and words, Markdown has its own rules.
+pub struct Grammar {
+    pub name: &'static str,
+    extensions: &'static [&'static str],
+    file_names: &'static [&'static str],
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    nested_comments: bool,
+    /// A line comment only starts at the beginning of a word.
+    comment_after_space: bool,
+    strings: &'static [StringRule],
+    keywords: &'static [&'static str],
+    constants: &'static [&'static str],
+    types: &'static [&'static str],
+    /// Words starting with an upper case letter are types.
+    capitalized_types: bool,
+    /// Characters other than letters, digits and '_' allowed in words.
+    word_chars: &'static [char],
+    attribute_prefix: Option<char>,
+    variable_prefix: Option<char>,
+    /// Words and strings followed by this are keys.
+    key_separator: Option<char>,
+    /// Lines starting with '[' are table headers.
+    table_headers: bool,
+    markdown: bool,
+}
+
+const PLAIN: Grammar = Grammar {
+    name: "",
+    extensions: &[],
+    file_names: &[],
+    line_comments: &[],
+    block_comment: None,
+    nested_comments: false,
+    comment_after_space: false,
+    strings: &[],
+    keywords: &[],
+    constants: &[],
+    types: &[],
+    capitalized_types: false,
+    word_chars: &[],
+    attribute_prefix: None,
+    variable_prefix: None,
+    key_separator: None,
+    table_headers: false,
+    markdown: false,
+};
+
+const RUST: Grammar = Grammar {
+    name: "Rust",
+    extensions: &["rs"],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    nested_comments: true,
+    strings: &[
+        StringRule {
+            open: "r#\"",
+            close: "\"#",
+            escapes: false,
+            multiline: true,
+            char_literal: false,
+        },
+        quoted("\"", true),
+        StringRule {
+            open: "'",
+            close: "'",
+            escapes: true,
+            multiline: false,
+            char_literal: true,
+        },
+    ],
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+        "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "type",
+        "unsafe", "use", "where", "while",
+    ],
+    constants: &["true", "false", "None"],
+    types: &[
+        "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32",
+        "i64", "i128", "isize", "f32", "f64",
+    ],
+    capitalized_types: true,
+    attribute_prefix: Some('#'),
+    ..PLAIN
+};
+
+const TOML: Grammar = Grammar {
+    name: "TOML",
+    extensions: &["toml"],
+    file_names: &["Cargo.lock"],
+    line_comments: &["#"],
+    strings: &[
+        StringRule {
+            open: "\"\"\"",
+            close: "\"\"\"",
+            escapes: true,
+            multiline: true,
+            char_literal: false,
+        },
+        StringRule {
+            open: "'''",
+            close: "'''",
+            escapes: false,
+            multiline: true,
+            char_literal: false,
+        },
+        quoted("\"", false),
+        StringRule {
+            open: "'",
+            close: "'",
+            escapes: false,
+            multiline: false,
+            char_literal: false,
+        },
+    ],
+    constants: &["true", "false", "inf", "nan"],
+    word_chars: &['-'],
+    key_separator: Some('='),
+    table_headers: true,
+    ..PLAIN
+};
+
+const MARKDOWN: Grammar = Grammar {
+    name: "Markdown",
+    extensions: &["md", "markdown"],
+    markdown: true,
+    ..PLAIN
+};
+
+const JSON: Grammar = Grammar {
+    name: "JSON",
+    extensions: &["json"],
+    strings: &[quoted("\"", false)],
+    constants: &["true", "false", "null"],
+    key_separator: Some(':'),
+    ..PLAIN
+};
+
+const SHELL: Grammar = Grammar {
+    name: "Shell",
+    extensions: &["sh", "bash", "zsh"],
+    file_names: &[".bashrc", ".bash_profile", ".profile", ".zshrc"],
+    line_comments: &["#"],
+    comment_after_space: true,
+    strings: &[
+        quoted("\"", true),
+        StringRule {
+            open: "'",
+            close: "'",
+            escapes: false,
+            multiline: true,
+            char_literal: false,
+        },
+    ],
+    keywords: &[
+        "if", "then", "else", "elif", "fi", "for", "while", "until", "do", "done", "case", "esac",
+        "in", "function", "return", "local", "export", "readonly", "shift", "exit", "break",
+        "continue",
+    ],
+    constants: &["true", "false"],
+    variable_prefix: Some('$'),
+    ..PLAIN
+};
+
+const PYTHON: Grammar = Grammar {
+    name: "Python",
+    extensions: &["py"],
+    line_comments: &["#"],
+    strings: &[
+        quoted("\"\"\"", true),
+        quoted("'''", true),
+        quoted("\"", false),
+        quoted("'", false),
+    ],
+    keywords: &[
+        "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+        "elif", "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is",
+        "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while", "with",
+        "yield", "self",
+    ],
+    constants: &["True", "False", "None"],
+    types: &[
+        "int", "float", "str", "bytes", "bool", "list", "dict", "set", "tuple", "object",
+    ],
+    capitalized_types: true,
+    attribute_prefix: Some('@'),
+    ..PLAIN
+};
+
+const GRAMMARS: [&Grammar; 6] = [&RUST, &TOML, &MARKDOWN, &JSON, &SHELL, &PYTHON];
+
+/// The grammar for a file, by its extension or name.
+pub fn grammar_for(path: &Path) -> Option<&'static Grammar> {
+    let name = path.file_name()?.to_str()?;
+    let extension = path.extension().and_then(|e| e.to_str());
+    GRAMMARS.into_iter().find(|g| {
+        g.file_names.contains(&name) || extension.is_some_and(|e| g.extensions.contains(&e))
+    })
+}
+
+/// The highlighted lines at the start of a buffer.
+struct HighlightedLine {
+    spans: Vec<Span>,
+    /// What the line left open for the next one.
+    end: State,
+}
+
+/// Highlights the lines of a buffer on demand. Each line is highlighted
+/// once and kept until it changes or starts in another state, as the state
+/// at the start of a line depends on all lines above.
+#[derive(Default)]
+pub struct Highlighter {
+    grammar: Option<&'static Grammar>,
+    lines: RefCell<Vec<HighlightedLine>>,
+    /// How many lines were highlighted, to see what an edit costs.
+    highlighted: Cell<usize>,
+}
+
+impl Highlighter {
+    pub fn new(grammar: Option<&'static Grammar>) -> Highlighter {
+        Highlighter {
+            grammar,
+            lines: RefCell::new(Vec::new()),
+            highlighted: Cell::new(0),
+        }
+    }
+
+    pub fn for_path(path: &Path) -> Highlighter {
+        Highlighter::new(grammar_for(path))
+    }
+
+    /// How many lines were highlighted so far.
+    #[cfg(test)]
+    pub fn lines_highlighted(&self) -> usize {
+        self.highlighted.get()
+    }
+
+    /// The name of the highlighted language, if any.
+    pub fn language(&self) -> Option<&'static str> {
+        self.grammar.map(|g| g.name)
+    }
+
+    /// The highlighted parts of line `index` of `buffer`.
+    pub fn spans(&self, buffer: &Buffer, index: usize) -> Vec<Span> {
+        let Some(grammar) = self.grammar else {
+            return Vec::new();
+        };
+        let mut lines = self.lines.borrow_mut();
+        let changes = buffer.take_changes(Listener::Highlighter);
+        // highlighting a large file up to the line shown would read all of
+        // it from disk
+        if buffer.is_lazy() {
+            return Vec::new();
+        }
+
+        if let Some(changes) = changes {
+            self.update(grammar, &mut lines, buffer, changes, index);
+        }
+        while lines.len() <= index {
+            if self.highlight_next(grammar, &mut lines, buffer).is_none() {
+                return Vec::new();
+            }
+        }
+        lines[index].spans.clone()
+    }
+
+    /// Highlight the line after `lines` and add it to them. Returns the
+    /// state at its end, `None` past the end of `buffer`.
+    fn highlight_next(
+        &self,
+        grammar: &Grammar,
+        lines: &mut Vec<HighlightedLine>,
+        buffer: &Buffer,
+    ) -> Option<State> {
+        let line = buffer.line_at(lines.len())?;
+        let state = lines.last().map_or(State::Normal, |l| l.end);
+        let (spans, end) = highlight_line(grammar, line, state);
+        lines.push(HighlightedLine { spans, end });
+        self.highlighted.set(self.highlighted.get() + 1);
+        Some(end)
+    }
+
+    /// Highlight the changed lines again, and the lines below them until one
+    /// ends in the same state as before: the cached lines after it only
+    /// moved. Stops at line `index`, the rest is highlighted on demand.
+    fn update(
+        &self,
+        grammar: &Grammar,
+        lines: &mut Vec<HighlightedLine>,
+        buffer: &Buffer,
+        changes: Changes,
+        index: usize,
+    ) {
+        let first = changes.lines.start;
+        if first >= lines.len() {
+            return;
+        }
+        let old = lines.split_off(first);
+        // where line `i` of the buffer was in `old` before the change
+        let before = |i: usize| i.checked_add_signed(-changes.moved)?.checked_sub(first);
+
+        while lines.len() <= index {
+            let i = lines.len();
+            let Some(end) = self.highlight_next(grammar, lines, buffer) else {
+                return;
+            };
+            if i + 1 >= changes.lines.end
+                && let Some(k) = before(i)
+                && old.get(k).is_some_and(|line| line.end == end)
+            {
+                lines.extend(old.into_iter().skip(k + 1));
+                return;
+            }
+        }
+    }
+}
+
+fn starts_with(line: &[char], at: usize, token: &str) -> bool {
+    let mut chars = line.iter().skip(at);
+    token.chars().all(|c| chars.next() == Some(&c))
+}
+
+fn token_length(token: &str) -> usize {
+    token.chars().count()
+}
+
+/// Highlight a line that starts in `state`. Returns the spans and the
+/// state at the end of the line.
+fn highlight_line(grammar: &Grammar, line: &[char], state: State) -> (Vec<Span>, State) {
+    if grammar.markdown {
+        return highlight_markdown(line, state);
+    }
+
+    let mut spans = Vec::new();
+    let mut state = state;
+    let mut i = 0;
+
+    // finish what the previous line left open
+    match state {
+        State::Comment { depth } => {
+            let (end, depth) = comment_end(grammar, line, 0, depth);
+            spans.push(Span {
+                range: 0..end,
+                scope: Scope::Comment,
+            });
+            state = if depth > 0 {
+                State::Comment { depth }
+            } else {
+                State::Normal
+            };
+            i = end;
+        }
+        State::String { rule } => {
+            let (end, closed) = string_end(&grammar.strings[rule], line, 0);
+            spans.push(Span {
+                range: 0..end,
+                scope: Scope::String,
+            });
+            if closed {
+                state = State::Normal;
+            }
+            i = end;
+        }
+        State::Normal | State::CodeBlock => (),
+    }
+
+    let first_word = line
+        .iter()
+        .position(|c| !c.is_whitespace())
+        .unwrap_or(line.len());
+    let is_word = |c: char| c.is_alphanumeric() || c == '_' || grammar.word_chars.contains(&c);
+    let is_key = |end: usize| {
+        grammar.key_separator.is_some()
+            && line[end..].iter().find(|c| !c.is_whitespace()) == grammar.key_separator.as_ref()
+    };
+
+    while i < line.len() && state == State::Normal {
+        let c = line[i];
+        let start = i;
+
+        let comment_allowed = !grammar.comment_after_space || i == 0 || line[i - 1].is_whitespace();
+        if comment_allowed
+            && grammar
+                .line_comments
+                .iter()
+                .any(|t| starts_with(line, i, t))
+        {
+            spans.push(Span {
+                range: i..line.len(),
+                scope: Scope::Comment,
+            });
+            break;
+        }
+
+        if let Some((open, _)) = grammar.block_comment
+            && starts_with(line, i, open)
+        {
+            let (end, depth) = comment_end(grammar, line, i + token_length(open), 1);
+            spans.push(Span {
+                range: start..end,
+                scope: Scope::Comment,
+            });
+            if depth > 0 {
+                state = State::Comment { depth };
+            }
+            i = end;
+            continue;
+        }
+
+        let string = grammar.strings.iter().enumerate().find(|(_, rule)| {
+            starts_with(line, i, rule.open)
+                && (!rule.char_literal
+                    || line.get(i + 1) == Some(&'\\')
+                    || line.get(i + 2) == Some(&'\''))
+        });
+        if let Some((index, rule)) = string {
+            let (end, closed) = string_end(rule, line, i + token_length(rule.open));
+            let scope = if closed && is_key(end) {
+                Scope::Key
+            } else {
+                Scope::String
+            };
+            spans.push(Span {
+                range: start..end,
+                scope,
+            });
+            if !closed && rule.multiline {
+                state = State::String { rule: index };
+            }
+            i = end;
+            continue;
+        }
+
+        if grammar.table_headers && c == '[' && i == first_word {
+            let end = line
+                .iter()
+                .rposition(|c| *c == ']')
+                .map_or(line.len(), |p| p + 1);
+            spans.push(Span {
+                range: start..end,
+                scope: Scope::Attribute,
+            });
+            i = end;
+            continue;
+        }
+
+        if grammar.attribute_prefix == Some(c) {
+            let end = attribute_end(line, i + 1);
+            if end > i + 1 {
+                spans.push(Span {
+                    range: start..end,
+                    scope: Scope::Attribute,
+                });
+                i = end;
+                continue;
+            }
+        }
+
+        if grammar.variable_prefix == Some(c) {
+            let end = variable_end(line, i + 1);
+            if end > i + 1 {
+                spans.push(Span {
+                    range: start..end,
+                    scope: Scope::Variable,
+                });
+                i = end;
+                continue;
+            }
+        }
+
+        if c.is_ascii_digit() && (i == 0 || !is_word(line[i - 1])) {
+            let end = number_end(line, i);
+            spans.push(Span {
+                range: start..end,
+                scope: Scope::Number,
+            });
+            i = end;
+            continue;
+        }
+
+        if c.is_alphabetic() || c == '_' {
+            let end = line[i..]
+                .iter()
+                .position(|c| !is_word(*c))
+                .map_or(line.len(), |p| i + p);
+            let word: String = line[start..end].iter().collect();
+            let next = line.get(end);
+            let scope = if is_key(end) {
+                Some(Scope::Key)
+            } else if grammar.keywords.contains(&word.as_str()) {
+                Some(Scope::Keyword)
+            } else if grammar.constants.contains(&word.as_str()) {
+                Some(Scope::Constant)
+            } else if grammar.types.contains(&word.as_str())
+                || (grammar.capitalized_types && c.is_uppercase())
+            {
+                Some(Scope::Type)
+            } else if next == Some(&'(')
+                || (next == Some(&'!') && line.get(end + 1).is_some_and(|c| "([{".contains(*c)))
+            {
+                Some(Scope::Function)
+            } else {
+                None
+            };
+            if let Some(scope) = scope {
+                spans.push(Span {
+                    range: start..end,
+                    scope,
+                });
+            }
+            i = end;
+            continue;
+        }
+
+        i += 1;
+    }
+    (spans, state)
+}
+
+/// Find the end of a block comment `depth` levels deep, starting at
+/// `from`. Returns the index after it and the depth left at the end of the
+/// line.
+fn comment_end(grammar: &Grammar, line: &[char], from: usize, mut depth: u8) -> (usize, u8) {
+    let Some((open, close)) = grammar.block_comment else {
+        return (line.len(), 0);
+    };
+    let mut i = from;
+    while i < line.len() {
+        if grammar.nested_comments && starts_with(line, i, open) {
+            depth = depth.saturating_add(1);
+            i += token_length(open);
+        } else if starts_with(line, i, close) {
+            depth -= 1;
+            i += token_length(close);
+            if depth == 0 {
+                return (i, 0);
+            }
+        } else {
+            i += 1;
+        }
+    }
+    (line.len(), depth)
+}
+
+/// Find the end of a string, starting at `from`. Returns the index after
+/// it and whether it was closed on this line.
+fn string_end(rule: &StringRule, line: &[char], from: usize) -> (usize, bool) {
+    let mut i = from;
+    while i < line.len() {
+        if rule.escapes && line[i] == '\\' {
+            i += 2;
+        } else if starts_with(line, i, rule.close) {
+            return (i + token_length(rule.close), true);
+        } else {
+            i += 1;
+        }
+    }
+    (line.len(), false)
+}
+
+/// The end of `#[...]`, `#![...]` or `@name.attr`, starting after the
+/// prefix.
+fn attribute_end(line: &[char], from: usize) -> usize {
+    let bracket = match (line.get(from), line.get(from + 1)) {
+        (Some('['), _) => from,
+        (Some('!'), Some('[')) => from + 1,
+        (Some(c), _) if c.is_alphabetic() || *c == '_' => {
+            return line[from..]
+                .iter()
+                .position(|c| !(c.is_alphanumeric() || *c == '_' || *c == '.'))
+                .map_or(line.len(), |p| from + p);
+        }
+        _ => return from,
+    };
+    let mut depth = 0;
+    for (i, c) in line.iter().enumerate().skip(bracket) {
+        match c {
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return i + 1;
+                }
+            }
+            _ => (),
+        }
+    }
+    line.len()
+}
+
+/// The end of `$name`, `${...}` or `$?`, starting after the '$'.
+fn variable_end(line: &[char], from: usize) -> usize {
+    match line.get(from) {
+        Some('{') => line[from..]
+            .iter()
+            .position(|c| *c == '}')
+            .map_or(line.len(), |p| from + p + 1),
+        Some(c) if c.is_alphabetic() || *c == '_' => line[from..]
+            .iter()
+            .position(|c| !(c.is_alphanumeric() || *c == '_'))
+            .map_or(line.len(), |p| from + p),
+        Some(c) if c.is_ascii_digit() || "?@#$!*-".contains(*c) => from + 1,
+        _ => from,
+    }
+}
+
+fn number_end(line: &[char], from: usize) -> usize {
+    let mut i = from;
+    while let Some(c) = line.get(i) {
+        let decimal_point = *c == '.' && line.get(i + 1).is_some_and(|c| c.is_ascii_digit());
+        if !(c.is_alphanumeric() || *c == '_' || decimal_point) {
+            break;
+        }
+        i += 1;
+    }
+    i
+}
+
+fn highlight_markdown(line: &[char], state: State) -> (Vec<Span>, State) {
+    let whole_line = |scope| {
+        vec![Span {
+            range: 0..line.len(),
+            scope,
+        }]
+    };
+    let indent = line.iter().take_while(|c| **c == ' ').count();
+    let fence =
+        indent < 4 && (starts_with(line, indent, "```") || starts_with(line, indent, "~~~"));
+
+    match state {
+        State::CodeBlock if fence => return (whole_line(Scope::Code), State::Normal),
+        State::CodeBlock => return (whole_line(Scope::Code), State::CodeBlock),
+        _ if fence => return (whole_line(Scope::Code), State::CodeBlock),
+        _ => (),
+    }
+
+    let hashes = line[indent..].iter().take_while(|c| **c == '#').count();
+    if (1..=6).contains(&hashes) && line.get(indent + hashes).is_none_or(|c| *c == ' ') {
+        return (whole_line(Scope::Heading), State::Normal);
+    }
+    if line.get(indent) == Some(&'>') {
+        return (whole_line(Scope::Comment), State::Normal);
+    }
+
+    let mut spans = Vec::new();
+    let digits = line[indent..]
+        .iter()
+        .take_while(|c| c.is_ascii_digit())
+        .count();
+    let marker = match line.get(indent + digits) {
+        Some('-' | '*' | '+') if digits == 0 => 1,
+        Some('.' | ')') if digits > 0 => digits + 1,
+        _ => 0,
+    };
+    let mut i = indent;
+    if marker > 0 && line.get(indent + marker) == Some(&' ') {
+        spans.push(Span {
+            range: indent..indent + marker,
+            scope: Scope::Keyword,
+        });
+        i += marker;
+    }
+
+    let find = |from: usize, token: &str| (from..line.len()).find(|i| starts_with(line, *i, token));
+    while i < line.len() {
+        let c = line[i];
+        let after_word = i > 0 && line[i - 1].is_alphanumeric();
+        let inline = if c == '`' {
+            find(i + 1, "`").map(|end| (end + 1, Scope::Code))
+        } else if starts_with(line, i, "**") || starts_with(line, i, "__") {
+            let token: String = line[i..i + 2].iter().collect();
+            find(i + 2, &token).map(|end| (end + 2, Scope::Strong))
+        } else if (c == '*' || c == '_')
+            && !after_word
+            && line.get(i + 1).is_some_and(|c| !c.is_whitespace())
+        {
+            find(i + 1, &c.to_string()).map(|end| (end + 1, Scope::Emphasis))
+        } else if c == '[' {
+            find(i + 1, "](")
+                .and_then(|middle| find(middle + 2, ")"))
+                .map(|end| (end + 1, Scope::Link))
+        } else {
+            None
+        };
+        match inline {
+            Some((end, scope)) => {
+                spans.push(Span {
+                    range: i..end,
+                    scope,
+                });
+                i = end;
+            }
+            None => i += 1,
+        }
+    }
+    (spans, State::Normal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    /// The highlighted words of a line, in order.
+    fn scopes(grammar: &Grammar, line: &str) -> Vec<(String, Scope)> {
+        let line = chars(line);
+        highlight_line(grammar, &line, State::Normal)
+            .0
+            .into_iter()
+            .map(|span| (line[span.range].iter().collect(), span.scope))
+            .collect()
+    }
+
+    fn scope(text: &str, scope: Scope) -> (String, Scope) {
+        (text.to_string(), scope)
+    }
+
+    #[test]
+    pub fn grammars_are_found_by_extension_and_name() {
+        let name = |path: &str| grammar_for(Path::new(path)).map(|g| g.name);
+        assert_eq!(name("src/main.rs"), Some("Rust"));
+        assert_eq!(name("Cargo.toml"), Some("TOML"));
+        assert_eq!(name("Cargo.lock"), Some("TOML"));
+        assert_eq!(name("Readme.md"), Some("Markdown"));
+        assert_eq!(name("/home/me/.bashrc"), Some("Shell"));
+        assert_eq!(name("notes.txt"), None);
+    }
+
+    #[test]
+    pub fn rust_words_strings_and_comments() {
+        assert_eq!(
+            scopes(
+                &RUST,
+                "pub fn main() -> Option<u8> { println!(\"hi {}\", 'x'); } // done"
+            ),
+            vec![
+                scope("pub", Scope::Keyword),
+                scope("fn", Scope::Keyword),
+                scope("main", Scope::Function),
+                scope("Option", Scope::Type),
+                scope("u8", Scope::Type),
+                scope("println", Scope::Function),
+                scope("\"hi {}\"", Scope::String),
+                scope("'x'", Scope::String),
+                scope("// done", Scope::Comment),
+            ]
+        );
+        assert_eq!(
+            scopes(&RUST, "#[derive(Debug)] struct A<'a>(&'a str, 0x1f);"),
+            vec![
+                scope("#[derive(Debug)]", Scope::Attribute),
+                scope("struct", Scope::Keyword),
+                scope("A", Scope::Type),
+                scope("str", Scope::Type),
+                scope("0x1f", Scope::Number),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn block_comments_continue_on_the_next_line() {
+        let (spans, state) =
+            highlight_line(&RUST, &chars("let a = 1; /* one /* two */"), State::Normal);
+        assert_eq!(spans.last().unwrap().range, 11..27);
+        assert_eq!(state, State::Comment { depth: 1 });
+
+        let (spans, state) = highlight_line(&RUST, &chars("still */ let"), state);
+        assert_eq!(
+            spans,
+            vec![
+                Span {
+                    range: 0..8,
+                    scope: Scope::Comment
+                },
+                Span {
+                    range: 9..12,
+                    scope: Scope::Keyword
+                },
+            ]
+        );
+        assert_eq!(state, State::Normal);
+    }
+
+    #[test]
+    pub fn python_triple_quoted_strings_span_lines() {
+        let (_, state) = highlight_line(&PYTHON, &chars("x = \"\"\"doc"), State::Normal);
+        assert_eq!(state, State::String { rule: 0 });
+        let (spans, state) = highlight_line(&PYTHON, &chars("end\"\"\" # note"), state);
+        assert_eq!(spans[0].range, 0..6);
+        assert_eq!(spans[1].scope, Scope::Comment);
+        assert_eq!(state, State::Normal);
+
+        assert_eq!(
+            scopes(&PYTHON, "@cache.wrap def f(self): return None"),
+            vec![
+                scope("@cache.wrap", Scope::Attribute),
+                scope("def", Scope::Keyword),
+                scope("f", Scope::Function),
+                scope("self", Scope::Keyword),
+                scope("return", Scope::Keyword),
+                scope("None", Scope::Constant),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn toml_and_json_keys() {
+        assert_eq!(
+            scopes(&TOML, "[dependencies.ratatui]"),
+            vec![scope("[dependencies.ratatui]", Scope::Attribute)]
+        );
+        assert_eq!(
+            scopes(&TOML, "default-features = false # no"),
+            vec![
+                scope("default-features", Scope::Key),
+                scope("false", Scope::Constant),
+                scope("# no", Scope::Comment),
+            ]
+        );
+        assert_eq!(
+            scopes(&JSON, "{\"name\": \"mu\", \"size\": 1.5, \"ok\": null}"),
+            vec![
+                scope("\"name\"", Scope::Key),
+                scope("\"mu\"", Scope::String),
+                scope("\"size\"", Scope::Key),
+                scope("1.5", Scope::Number),
+                scope("\"ok\"", Scope::Key),
+                scope("null", Scope::Constant),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn shell_variables_and_comments() {
+        assert_eq!(
+            scopes(&SHELL, "if [ -n \"$x\" ]; then echo ${HOME}#not; fi # yes"),
+            vec![
+                scope("if", Scope::Keyword),
+                scope("\"$x\"", Scope::String),
+                scope("then", Scope::Keyword),
+                scope("${HOME}", Scope::Variable),
+                scope("fi", Scope::Keyword),
+                scope("# yes", Scope::Comment),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn markdown_blocks_and_inline_markup() {
+        assert_eq!(
+            scopes(&MARKDOWN, "## Usage"),
+            vec![scope("## Usage", Scope::Heading)]
+        );
+        assert_eq!(
+            scopes(
+                &MARKDOWN,
+                "- run `mu` **now**, see [docs](x.md) or *this* snake_case"
+            ),
+            vec![
+                scope("-", Scope::Keyword),
+                scope("`mu`", Scope::Code),
+                scope("**now**", Scope::Strong),
+                scope("[docs](x.md)", Scope::Link),
+                scope("*this*", Scope::Emphasis),
+            ]
+        );
+
+        let (_, state) = highlight_line(&MARKDOWN, &chars("```rust"), State::Normal);
+        assert_eq!(state, State::CodeBlock);
+        let (spans, state) = highlight_line(&MARKDOWN, &chars("# not a heading"), state);
+        assert_eq!(spans[0].scope, Scope::Code);
+        let (_, state) = highlight_line(&MARKDOWN, &chars("```"), state);
+        assert_eq!(state, State::Normal);
+    }
+
+    #[test]
+    pub fn changed_lines_are_highlighted_again() {
+        let mut buffer = Buffer::from("let a = 1;\n/*\nlet b;\n*/".to_string());
+        let highlighter = Highlighter::new(Some(&RUST));
+        assert_eq!(highlighter.spans(&buffer, 2)[0].scope, Scope::Comment);
+
+        // closing the comment early changes the lines below it
+        buffer.line_at_mut(1).unwrap().extend("*/".chars());
+        assert_eq!(highlighter.spans(&buffer, 2)[0].scope, Scope::Keyword);
+        assert!(highlighter.spans(&buffer, 9).is_empty());
+    }
+
+    #[test]
+    pub fn edits_only_highlight_the_lines_they_affect() {
+        let text: String = (0..200).map(|i| format!("let a{} = {};\n", i, i)).collect();
+        let mut buffer = Buffer::from(text);
+        let highlighter = Highlighter::new(Some(&RUST));
+        highlighter.spans(&buffer, 150);
+        assert_eq!(highlighter.highlighted.get(), 151);
+
+        buffer.line_at_mut(10).unwrap().push(' ');
+        assert_eq!(highlighter.spans(&buffer, 150)[0].scope, Scope::Keyword);
+        assert_eq!(highlighter.highlighted.get(), 152);
+
+        // both halves of the broken line, the lines below only moved
+        buffer.break_line_at(20, 3);
+        assert_eq!(highlighter.spans(&buffer, 151)[0].scope, Scope::Keyword);
+        assert_eq!(highlighter.highlighted.get(), 154);
+
+        // an open comment changes everything below, up to the line asked for
+        buffer.line_at_mut(5).unwrap().extend("/*".chars());
+        assert_eq!(highlighter.spans(&buffer, 150)[0].scope, Scope::Comment);
+        assert_eq!(highlighter.highlighted.get(), 154 + 146);
+    }
+}
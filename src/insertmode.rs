@@ -3,7 +3,7 @@ use crossterm::event::{KeyCode, KeyModifiers};
 
 use crate::{
     mode::EditorMode,
-    modeutil::{self, rotate_buffer},
+    modeutil::{self, refuse_read_only, rotate_buffer},
 };
 
 #[derive(Default)]
@@ -19,8 +19,6 @@ impl EditorMode for InsertMode {
         key_event: crossterm::event::KeyEvent,
         app_state: &mut crate::app::ApplicationState,
     ) {
-        // Insert mode will just append letters to the current line:
-        let buffer = &mut app_state.buffers[app_state.current_buffer];
         if key_event.modifiers.contains(KeyModifiers::CONTROL) {
             match key_event.code {
                 KeyCode::Left => rotate_buffer(app_state, -1),
@@ -30,6 +28,18 @@ impl EditorMode for InsertMode {
 
             return;
         }
+
+        if matches!(
+            key_event.code,
+            KeyCode::Char(_) | KeyCode::Backspace | KeyCode::Enter
+        ) && refuse_read_only(app_state)
+        {
+            return;
+        }
+
+        // Insert mode will just append letters to the current line:
+        let buffer = &mut app_state.buffers[app_state.current_buffer];
+
         match key_event.code {
             KeyCode::Char(c) => {
                 buffer.add_character(c);
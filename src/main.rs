@@ -1,27 +1,133 @@
-use std::{env, io};
+use std::{
+    env,
+    fs::OpenOptions,
+    io::{self, IsTerminal, Write},
+};
 
 use app::App;
+use cmdline::CommandLine;
+use crossterm::{
+    cursor::Show,
+    event::{DisableFocusChange, DisableMouseCapture, EnableFocusChange, EnableMouseCapture},
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+};
+use ratatui::{Terminal, backend::CrosstermBackend};
 
 mod app;
 mod buffer;
 mod bufferentry;
+mod cmdline;
+mod columns;
+mod config;
+mod diff;
+mod diskstate;
+mod filetree;
+mod filewalker;
+mod fuzzy;
+mod gutter;
+mod hexmode;
+mod highlight;
 mod insertmode;
 mod mode;
 mod modeutil;
 mod navigationmode;
 mod normalmode;
+mod pathinput;
+mod recent;
+mod scrollbar;
 mod selectmode;
+mod session;
+mod splits;
+mod statusline;
+#[cfg(test)]
+mod testutil;
+mod theme;
 
 fn main() -> io::Result<()> {
-    let args: Vec<String> = env::args().collect();
-    let mut file_name: Option<String> = None;
+    let cmdline = match CommandLine::parse(env::args().skip(1)) {
+        Ok(cmdline) => cmdline,
+        Err(e) => {
+            eprintln!("mu: {}\n\n{}", e, cmdline::USAGE);
+            std::process::exit(2);
+        }
+    };
 
-    if args.len() > 1 {
-        file_name = Some(args[1].clone());
+    if cmdline.show_help {
+        println!("{}", cmdline::USAGE);
+        return Ok(());
+    }
+    if cmdline.show_version {
+        println!("mu {}", env!("CARGO_PKG_VERSION"));
+        return Ok(());
+    }
+
+    // read all of stdin before the terminal is taken over, keys are then
+    // read from the terminal itself
+    let piped = if cmdline.read_stdin {
+        let mut data = Vec::new();
+        io::Read::read_to_end(&mut io::stdin(), &mut data)?;
+        Some(data)
+    } else {
+        None
+    };
+    let write_stdout = cmdline.write_stdout;
+
+    let mut app = App::new();
+    if io::stdout().is_terminal() {
+        let mut terminal = ratatui::init();
+        // focus events are used to notice files that changed while mu was in the background
+        let _ = crossterm::execute!(io::stdout(), EnableFocusChange, EnableMouseCapture);
+        // ratatui's own hook restores the screen, but knows nothing of these
+        let panic_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let _ = crossterm::execute!(io::stdout(), DisableMouseCapture, DisableFocusChange);
+            panic_hook(info);
+        }));
+        let app_result = app.run(&mut terminal, cmdline, piped);
+        let _ = crossterm::execute!(io::stdout(), DisableMouseCapture, DisableFocusChange);
+        ratatui::restore();
+        app_result?;
+    } else {
+        run_on_tty(&mut app, cmdline, piped)?;
     }
 
-    let mut terminal = ratatui::init();
-    let app_result = App::new().run(&mut terminal, file_name);
-    ratatui::restore();
+    if write_stdout && let Some(output) = app.output() {
+        io::stdout().write_all(&output?)?;
+    }
+    Ok(())
+}
+
+/// Run mu on the controlling terminal, for when stdout is part of a pipeline.
+fn run_on_tty(app: &mut App, cmdline: CommandLine, piped: Option<Vec<u8>>) -> io::Result<()> {
+    let mut tty = OpenOptions::new().write(true).open("/dev/tty")?;
+    enable_raw_mode()?;
+    crossterm::execute!(
+        tty,
+        EnterAlternateScreen,
+        EnableFocusChange,
+        EnableMouseCapture
+    )?;
+
+    let panic_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = restore_tty();
+        panic_hook(info);
+    }));
+
+    let mut terminal = Terminal::new(CrosstermBackend::new(tty))?;
+    let app_result = app.run(&mut terminal, cmdline, piped);
+    restore_tty()?;
     app_result
 }
+
+fn restore_tty() -> io::Result<()> {
+    let mut tty = OpenOptions::new().write(true).open("/dev/tty")?;
+    disable_raw_mode()?;
+    crossterm::execute!(
+        tty,
+        DisableMouseCapture,
+        DisableFocusChange,
+        LeaveAlternateScreen,
+        Show
+    )
+}
@@ -1,4 +1,4 @@
-use crossterm::event::KeyEvent;
+use crossterm::event::{KeyEvent, MouseEvent};
 use ratatui::Frame;
 
 use crate::app::ApplicationState;
@@ -8,12 +8,27 @@ pub enum Mode {
     #[default]
     Normal,
     Insert,
+    #[allow(dead_code)]
     Navigate,
     Select,
+    Hex,
 }
 
 pub trait EditorMode {
     fn mode_name(&self) -> &'static str;
     fn handle_key_event(&mut self, key_event: KeyEvent, app_state: &mut ApplicationState);
     fn render(&self, frame: &mut Frame, app_state: &ApplicationState);
+    /// True while the mode shows a popup or prompt that takes all keys.
+    fn has_popup(&self) -> bool {
+        false
+    }
+    /// Look at a mouse event before the editor handles it. Returns true if
+    /// the mode used it up.
+    fn handle_mouse_event(
+        &mut self,
+        _mouse_event: MouseEvent,
+        _app_state: &mut ApplicationState,
+    ) -> bool {
+        false
+    }
 }
@@ -1,165 +1,798 @@
 use ratatui::{
-    layout::{Constraint, Layout},
+    layout::{Constraint, Flex, Layout, Rect},
     widgets::Block,
 };
 
 use ratatui::{style::Stylize, text::Line};
 
-use crate::app::ApplicationState;
-
-pub const TOP_BAR_HEIGHT: u16 = 4;
-
-pub fn render_mode_header(
-    frame: &mut ratatui::Frame,
-    dest: ratatui::layout::Rect,
-    mode: &str,
-    app_state: &crate::app::ApplicationState,
-) {
-    // renders a box on top with the current mode's name,
-    // the active buffer names and the current line number
+use crate::{
+    app::ApplicationState,
+    bufferentry::{BufferEntry, display_names},
+    columns::{
+        LINE_END, VisibleLine, Whitespace, char_width, display_column, index_at_column,
+        is_zero_width, show_whitespace, visible_part,
+    },
+    highlight::{self, Scope},
+    scrollbar::{self, MINIMAP_WIDTH, Marker, Scrollbar},
+    splits::View,
+    theme::{Slot, Theme},
+};
 
-    // as output: Select the first four buffers starting at the active one
-    // and show their name and modified flag. If there are more buffers, add
-    // an ellipsis, if fewer, just show as many as available
+/// A buffer name in the header: the buffer id, the columns it takes and the
+/// label.
+pub struct HeaderTab {
+    pub buffer: usize,
+    pub x: u16,
+    pub width: u16,
+    pub label: String,
+}
 
-    const MAX_BUFFERS_TO_SHOW: usize = 6;
+/// Marks buffers in the header that did not fit.
+const MORE_BUFFERS: &str = "...";
 
-    let num_to_skip = if app_state.buffers.len() > MAX_BUFFERS_TO_SHOW {
-        // if more than 4 buffers, make sure to always display
-        // at least 4 buffers.
-        if app_state.current_buffer > app_state.buffers.len() - MAX_BUFFERS_TO_SHOW {
-            app_state.buffers.len() - MAX_BUFFERS_TO_SHOW
-        } else {
-            app_state.current_buffer
-        }
-    } else {
-        0
-    };
-
-    let buffer_names: Vec<String> = app_state
+/// The buffer names shown in a header `width` columns wide next to the mode
+/// name. They scroll so that the current buffer is always shown.
+pub fn header_tabs(app_state: &ApplicationState, width: u16, mode: &str) -> Vec<HeaderTab> {
+    let labels: Vec<String> = app_state
         .buffers
         .iter()
-        .skip(num_to_skip)
-        .take(MAX_BUFFERS_TO_SHOW)
-        .map(|buffer| {
-            let output_string = format!(
-                "{}{}|",
+        .zip(display_names(&app_state.buffers))
+        .map(|(buffer, name)| {
+            format!(
+                "{}{}{}{}|",
                 if buffer.modified { "● " } else { "" },
-                buffer.name
-            );
-            output_string
+                if buffer.read_only { "🔒" } else { "" },
+                name,
+                if buffer.buffer.is_indexing() {
+                    " (indexing)"
+                } else {
+                    ""
+                }
+            )
         })
         .collect();
+    // the lock takes two columns
+    let widths: Vec<usize> = labels
+        .iter()
+        .map(|label| Line::raw(label.as_str()).width() + 1)
+        .collect();
+    let more = MORE_BUFFERS.len() + 1;
+    // inside the border, leaving room for the mode name
+    let available = (width as usize).saturating_sub(mode.len() + 4);
+    let needed = |first: usize, last: usize| {
+        widths[first..last].iter().sum::<usize>()
+            + if first > 0 { more } else { 0 }
+            + if last < widths.len() { more } else { 0 }
+    };
 
-    let mut pos = 1;
-    for (id, buffer_name) in buffer_names.iter().enumerate() {
-        let len = buffer_name.chars().count() as u16;
-        let mut the_widget = ratatui::widgets::Paragraph::new(buffer_name.clone())
+    let current = app_state.current_buffer.min(labels.len());
+    let (mut first, mut last) = (0, (current + 1).min(labels.len()));
+    while first < current && needed(first, last) > available {
+        first += 1;
+    }
+    while last < labels.len() && needed(first, last + 1) <= available {
+        last += 1;
+    }
+
+    // a name wider than the header is cut at the right border
+    let right = width.saturating_sub(1);
+    let mut x = if first > 0 { 1 + more as u16 } else { 1 };
+    (first..last)
+        .filter_map(|buffer| {
+            let tab_x = x;
+            x = x.saturating_add(widths[buffer] as u16);
+            let width = (widths[buffer] as u16 - 1).min(right.saturating_sub(tab_x));
+            (width > 0).then(|| HeaderTab {
+                buffer,
+                x: tab_x,
+                width,
+                label: clip_to_width(&labels[buffer], width as usize),
+            })
+        })
+        .collect()
+}
+
+/// The start of `text` that fits into `width` columns.
+fn clip_to_width(text: &str, width: usize) -> String {
+    let mut used = 0;
+    text.chars()
+        .take_while(|&c| {
+            used += char_width(c, used);
+            used <= width
+        })
+        .collect()
+}
+
+pub fn render_mode_header(
+    frame: &mut ratatui::Frame,
+    dest: ratatui::layout::Rect,
+    mode: &str,
+    app_state: &crate::app::ApplicationState,
+) {
+    // renders a box on top with the current mode's name and the names of
+    // the buffers with their modified flag
+
+    let tabs = header_tabs(app_state, dest.width, mode);
+    let mut more_at = Vec::new();
+    if tabs.first().is_some_and(|tab| tab.buffer > 0) {
+        more_at.push(1);
+    }
+    for (i, tab) in tabs.iter().enumerate() {
+        let mut the_widget = ratatui::widgets::Paragraph::new(tab.label.clone())
             .alignment(ratatui::layout::Alignment::Left);
-        if id == app_state.current_buffer {
-            the_widget = the_widget
-                .style(ratatui::style::Style::default().fg(ratatui::style::Color::Yellow));
+        if tab.buffer == app_state.current_buffer {
+            the_widget = the_widget.style(app_state.theme.style(Slot::ActiveBuffer));
         }
 
         frame.render_widget(
             the_widget,
-            ratatui::layout::Rect::new(pos, 1 as u16, len, 1),
+            ratatui::layout::Rect::new(tab.x, 1_u16, tab.width, 1),
         );
-        pos += len + 1;
+        if i + 1 == tabs.len() && tab.buffer + 1 < app_state.buffers.len() {
+            more_at.push(tab.x + tab.width + 1);
+        }
     }
-
-    if buffer_names.len() > 4 {
-        frame.render_widget(
-            ratatui::widgets::Paragraph::new("...").alignment(ratatui::layout::Alignment::Left),
-            ratatui::layout::Rect::new(pos, 1, 3, 1),
-        );
+    let right = dest.width.saturating_sub(1);
+    for x in more_at {
+        let width = (MORE_BUFFERS.len() as u16).min(right.saturating_sub(x));
+        if width > 0 {
+            frame.render_widget(
+                ratatui::widgets::Paragraph::new(MORE_BUFFERS),
+                ratatui::layout::Rect::new(x, 1, width, 1),
+            );
+        }
     }
 
-    // show current mode name:
-    let output_string = format!("{}", mode);
-    let len = output_string.len() as u16;
+    // show the mode name:
+    let output_string = mode.to_string();
+    let len = (output_string.len() as u16).min(right);
     frame.render_widget(
         ratatui::widgets::Paragraph::new(output_string)
             .alignment(ratatui::layout::Alignment::Right),
-        ratatui::layout::Rect::new(dest.width - (len + 1), 1, len, 1),
+        ratatui::layout::Rect::new(right - len, 1, len, 1),
     );
 
+    let mut block = Block::bordered();
+    if let Some(message) = &app_state.status_message {
+        block = block.title_bottom(message.as_str());
+    }
     frame.render_widget(
         ratatui::widgets::Paragraph::new("")
             .alignment(ratatui::layout::Alignment::Right)
-            .block(Block::bordered()),
+            .block(block),
         dest,
     );
 }
 
+/// Returns true if the current buffer is read-only and tells the user, for
+/// modes to check before editing.
+pub fn refuse_read_only(app_state: &mut ApplicationState) -> bool {
+    refuse_read_only_buffer(app_state, app_state.current_buffer)
+}
+
+/// Like [`refuse_read_only`], for the buffer `id`.
+pub fn refuse_read_only_buffer(app_state: &mut ApplicationState, id: usize) -> bool {
+    match app_state.buffers.get(id) {
+        Some(buffer) if buffer.read_only => {
+            app_state.status_message = Some(format!("{} is read-only", buffer.display_name()));
+            true
+        }
+        _ => false,
+    }
+}
+
 pub fn rotate_buffer(app_state: &mut ApplicationState, direction: i32) {
     let next_buffer_id = app_state.current_buffer as i32 + direction;
     app_state.current_buffer = (next_buffer_id % app_state.buffers.len() as i32) as usize;
 }
 
+pub fn popup_area(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
+    let vertical = Layout::vertical([Constraint::Percentage(percent_y)]).flex(Flex::Center);
+    let horizontal = Layout::horizontal([Constraint::Percentage(percent_x)]).flex(Flex::Center);
+    let [area] = vertical.areas(area);
+    let [area] = horizontal.areas(area);
+    area
+}
+
+/// The part of `dest` that is left for the buffer view once the file tree
+/// sidebar took its share.
+pub fn without_sidebar(dest: Rect, app_state: &ApplicationState) -> Rect {
+    let width = app_state.sidebar_width.min(dest.width);
+    Rect::new(dest.x + width, dest.y, dest.width - width, dest.height)
+}
+
+/// The part of `dest` occupied by the file tree sidebar.
+pub fn sidebar_area(dest: Rect, app_state: &ApplicationState) -> Rect {
+    Rect::new(
+        dest.x,
+        dest.y,
+        app_state.sidebar_width.min(dest.width),
+        dest.height,
+    )
+}
+
+/// Split the screen into the header, the body and the status line.
+pub fn screen_areas(area: Rect) -> [Rect; 3] {
+    Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Min(1),
+        Constraint::Length(1),
+    ])
+    .areas(area)
+}
+
 pub fn render(
     mode_name: &str,
     frame: &mut ratatui::Frame,
     app_state: &crate::app::ApplicationState,
 ) {
-    let layout = Layout::default()
-        .direction(ratatui::layout::Direction::Vertical)
-        .constraints([Constraint::Length(3), Constraint::Min(1)])
-        .split(frame.area());
-
-    let buffer = &app_state.buffers[app_state.current_buffer];
+    let [header, _, status] = screen_areas(frame.area());
 
     // show buffer name + modified flag:
-    render_mode_header(frame, layout[0], mode_name, app_state);
+    render_mode_header(frame, header, mode_name, app_state);
+    render_status_line(frame, status, app_state);
 
-    for (id, line) in buffer
-        .buffer
-        .lines
-        .iter()
-        .skip(buffer.scroll_offset)
-        .enumerate()
-    {
-        let line_width = if line.len() < frame.area().width as usize - 1 {
-            line.len() as u16
+    let region = text_region(frame.area(), app_state);
+    let separator_style = app_state.theme.style(Slot::Indicator);
+    for (id, area) in app_state.splits.areas(region) {
+        // the active view shows the current buffer at its own position
+        let active = id == app_state.splits.active;
+        let (buffer_id, position) = if active {
+            let buffer = &app_state.buffers[app_state.current_buffer];
+            (
+                app_state.current_buffer,
+                View::of(app_state.current_buffer, buffer),
+            )
         } else {
-            frame.area().width as u16 - 1
+            let view = &app_state.splits.views[id];
+            let position = match app_state.buffers.get(view.buffer) {
+                Some(buffer) => view.clamped_to(buffer),
+                None => view.clone(),
+            };
+            (view.buffer, position)
         };
+        if let Some(buffer) = app_state.buffers.get(buffer_id) {
+            render_view(frame, area, app_state, buffer, &position, active);
+        }
 
-        let line_as_string = line.iter().map(|c| c.to_string()).collect::<String>();
+        if area.right() < region.right() {
+            let separator = vec![Line::raw("│"); area.height as usize];
+            frame.render_widget(
+                ratatui::widgets::Paragraph::new(separator).style(separator_style),
+                Rect::new(area.right(), area.y, 1, area.height),
+            );
+        }
+        if area.bottom() < region.bottom() {
+            frame.render_widget(
+                ratatui::widgets::Paragraph::new("─".repeat(area.width as usize))
+                    .style(separator_style),
+                Rect::new(area.x, area.bottom(), area.width, 1),
+            );
+        }
+    }
+}
+
+/// The part of the screen between the header and the status line that is
+/// not taken by the file tree, where the views are shown.
+pub fn text_region(area: Rect, app_state: &ApplicationState) -> Rect {
+    let [_, body, _] = screen_areas(area);
+    without_sidebar(body, app_state)
+}
+
+/// Draw the configured status line segments into the row `area`.
+pub fn render_status_line(frame: &mut ratatui::Frame, area: Rect, app_state: &ApplicationState) {
+    let (left, right) = app_state.status_line.texts(app_state);
+    let style = app_state.theme.style(Slot::StatusLine);
+    frame.render_widget(
+        ratatui::widgets::Paragraph::new(format!(" {}", left)).style(style),
+        area,
+    );
+    frame.render_widget(
+        ratatui::widgets::Paragraph::new(format!("{} ", right))
+            .style(style)
+            .alignment(ratatui::layout::Alignment::Right),
+        area,
+    );
+}
+
+/// The parts of `view` showing the text of `buffer` and the scrollbar with
+/// the minimap. The line numbers take the rest on the left.
+pub fn view_areas(view: Rect, app_state: &ApplicationState, buffer: &BufferEntry) -> (Rect, Rect) {
+    let gutter = app_state
+        .line_numbers
+        .gutter_width(buffer.buffer.num_lines())
+        .min(view.width);
+    // the scrollbar only takes room if there is some left
+    let bar = app_state.scrollbar.width();
+    let bar = if view.width - gutter > bar { bar } else { 0 };
+    let text_area = Rect::new(
+        view.x + gutter,
+        view.y,
+        view.width - gutter - bar,
+        view.height,
+    );
+    (
+        text_area,
+        Rect::new(text_area.right(), view.y, bar, view.height),
+    )
+}
 
-        let line_y = 3 + id as u16;
+/// What a click into a view points at.
+#[derive(Debug, PartialEq)]
+pub enum ClickTarget {
+    /// A line and character in the text.
+    Text(usize, usize),
+    /// A line picked on the scrollbar or the minimap.
+    Line(usize),
+}
+
+/// What the click at `x`, `y` into `view` showing `buffer` at `position`
+/// points at, following the layout of `render_view`.
+pub fn click_target(
+    view: Rect,
+    app_state: &ApplicationState,
+    buffer: &BufferEntry,
+    position: &View,
+    x: u16,
+    y: u16,
+) -> ClickTarget {
+    let (text_area, bar) = view_areas(view, app_state, buffer);
+    let num_lines = buffer.buffer.num_lines();
+    let row = y.saturating_sub(view.y).min(view.height.saturating_sub(1));
+    if x >= bar.right().saturating_sub(1) && bar.width > 0 {
+        // with soft wrap the bar stands for screen rows
+        return ClickTarget::Line(match buffer.wrapped_rows() {
+            Some(rows) => {
+                let total = scrollbar::rows_before(&rows, num_lines);
+                scrollbar::line_at_row(&rows, scrollbar::line_of_row(row, total, view.height))
+            }
+            None => scrollbar::line_of_row(row, num_lines, view.height),
+        });
+    }
+    if x >= bar.x && bar.width > 0 {
+        let start = scrollbar::minimap_start(
+            position.scroll_offset,
+            view.height as usize,
+            num_lines,
+            view.height,
+        );
+        let line = scrollbar::minimap_lines(start, row).start;
+        return ClickTarget::Line(line.min(num_lines.saturating_sub(1)));
+    }
 
-        if line_y < frame.area().height as u16 {
+    let mut line_y = text_area.y;
+    for (id, line) in buffer.buffer.lines_from(position.scroll_offset).enumerate() {
+        let line_number = id + position.scroll_offset;
+        let wrapped = buffer.wrapped_line_in(line_number, text_area.width);
+        if y >= line_y + wrapped.rows.len() as u16 {
+            line_y += wrapped.rows.len() as u16;
+            if line_y >= text_area.bottom() {
+                break;
+            }
+            continue;
+        }
+        let row = (y.saturating_sub(line_y) as usize).min(wrapped.rows.len() - 1);
+        let start = wrapped.rows[row];
+        let end = wrapped.row_end(row, line.len());
+        let offset = if buffer.soft_wrap {
+            display_column(line, start)
+        } else {
+            position.horizontal_offset
+        };
+        let indent = wrapped.row_indent(row) as u16;
+        let column = x.saturating_sub(text_area.x + indent) as usize + offset;
+        let index = index_at_column(&line[..end], column).max(start);
+        // past the end of a wrapped row the cursor stays on that row
+        return ClickTarget::Text(
+            line_number,
+            if end < line.len() {
+                index.min(end - 1)
+            } else {
+                index
+            },
+        );
+    }
+    // below the text
+    let last = num_lines.saturating_sub(1);
+    let len = buffer.buffer.line_char_length(last).unwrap_or(0);
+    ClickTarget::Text(last, len)
+}
+
+/// Draw `buffer` at `position` into `view`, with the cursor if it is the
+/// active view.
+fn render_view(
+    frame: &mut ratatui::Frame,
+    view: Rect,
+    app_state: &ApplicationState,
+    buffer: &BufferEntry,
+    position: &View,
+    active: bool,
+) {
+    // the line numbers take the left part of the view, the scrollbar the
+    // right part
+    let (text_area, bar) = view_areas(view, app_state, buffer);
+    let gutter = text_area.x - view.x;
+    if bar.width > 0 {
+        render_scrollbar(frame, bar, app_state, buffer, position);
+    }
+
+    let indicator_style = app_state.theme.style(Slot::Indicator);
+    // every line takes at least one row, so no more lines than rows are read
+    let mut line_y = text_area.y;
+    for (id, line) in buffer
+        .buffer
+        .lines_from(position.scroll_offset)
+        .take(text_area.height as usize)
+        .enumerate()
+    {
+        if line_y >= text_area.bottom() {
+            break;
+        }
+        let line_number = id + position.scroll_offset;
+
+        if gutter > 0 {
+            let label = app_state
+                .line_numbers
+                .label(line_number, position.cursor_line);
+            let style = if line_number == position.cursor_line {
+                app_state.theme.style(Slot::CurrentLineNumber)
+            } else {
+                app_state.theme.style(Slot::LineNumber)
+            };
             frame.render_widget(
-                ratatui::widgets::Paragraph::new(line_as_string)
-                    .alignment(ratatui::layout::Alignment::Left),
-                ratatui::layout::Rect::new(0, line_y, line_width, 1),
+                ratatui::widgets::Paragraph::new(label)
+                    .style(style)
+                    .alignment(ratatui::layout::Alignment::Right),
+                Rect::new(view.x, line_y, gutter - 1, 1),
             );
         }
-        // render cursor:
-        let effective_line = id + buffer.scroll_offset;
-        if buffer.cursor_line == effective_line {
-            // get character under cursor
-            let char = line.iter().nth(buffer.cursor_position);
-            let cursor_char = if let Some(c) = char { c.clone() } else { '_' };
-
-            let mut cursor = cursor_char.to_string().rapid_blink();
-            if char.is_some() {
-                cursor = cursor.underlined();
-            }
 
-            let the_cusor = Line::from(vec![cursor]);
-            let cursor_y = (buffer.cursor_line - buffer.scroll_offset + 3) as u16;
+        let spans = buffer.highlighter.spans(&buffer.buffer, line_number);
+
+        // without soft wrap this is a single row
+        let wrapped = buffer.wrapped_line_in(line_number, text_area.width);
+        let cursor_row =
+            (line_number == position.cursor_line).then(|| wrapped.row_of(position.cursor_position));
+        for row in 0..wrapped.rows.len() {
+            if line_y >= text_area.bottom() {
+                break;
+            }
+            let start = wrapped.rows[row];
+            let end = wrapped.row_end(row, line.len());
+            let indent = (wrapped.row_indent(row) as u16).min(text_area.width);
+            let row_area = Rect::new(text_area.x + indent, line_y, text_area.width - indent, 1);
+            let offset = if buffer.soft_wrap {
+                display_column(line, start)
+            } else {
+                position.horizontal_offset
+            };
 
-            if buffer.cursor_position < frame.area().width as usize
-                && cursor_y < frame.area().height as u16
+            // the last column is left free for the continuation indicators
+            let text_width = (row_area.width as usize).saturating_sub(1);
+            let mut visible = visible_part(&line[..end], offset, text_width);
+            let marks = if buffer.show_whitespace {
+                show_whitespace(&mut visible, line)
+            } else {
+                Vec::new()
+            };
+            frame.render_widget(
+                ratatui::widgets::Paragraph::new(styled_text(
+                    &visible,
+                    &marks,
+                    &spans,
+                    &app_state.theme,
+                ))
+                .alignment(ratatui::layout::Alignment::Left),
+                row_area,
+            );
+            if buffer.show_whitespace
+                && end == line.len()
+                && let Some(column) = display_column(line, end).checked_sub(offset)
+                && column < text_width
             {
                 frame.render_widget(
-                    ratatui::widgets::Paragraph::new(the_cusor)
-                        .alignment(ratatui::layout::Alignment::Left),
-                    ratatui::layout::Rect::new(buffer.cursor_position as u16, cursor_y, 1, 1),
+                    ratatui::widgets::Paragraph::new(LINE_END.to_string())
+                        .style(app_state.theme.style(Slot::Whitespace)),
+                    Rect::new(row_area.x + column as u16, line_y, 1, 1),
                 );
             }
+            let right_edge = Rect::new(text_area.right().saturating_sub(1), line_y, 1, 1);
+            if buffer.soft_wrap {
+                if app_state.wrap_indicator && row + 1 < wrapped.rows.len() {
+                    frame.render_widget(
+                        ratatui::widgets::Paragraph::new("↪").style(indicator_style),
+                        right_edge,
+                    );
+                }
+            } else {
+                if visible.more_left {
+                    frame.render_widget(
+                        ratatui::widgets::Paragraph::new("<").style(indicator_style),
+                        Rect::new(text_area.x, line_y, 1, 1),
+                    );
+                }
+                if visible.more_right {
+                    frame.render_widget(
+                        ratatui::widgets::Paragraph::new(">").style(indicator_style),
+                        right_edge,
+                    );
+                }
+            }
+
+            if active && cursor_row == Some(row) {
+                render_cursor(
+                    frame,
+                    line,
+                    position.cursor_position,
+                    offset,
+                    row_area,
+                    app_state.theme.style(Slot::Cursor),
+                );
+            }
+            line_y += 1;
+        }
+    }
+}
+
+/// Draw the scrollbar at the right edge of `area`, with the minimap left of
+/// it if it is turned on.
+fn render_scrollbar(
+    frame: &mut ratatui::Frame,
+    area: Rect,
+    app_state: &ApplicationState,
+    buffer: &BufferEntry,
+    position: &View,
+) {
+    let theme = &app_state.theme;
+    let num_lines = buffer.buffer.num_lines();
+    let visible = area.height as usize;
+    // with soft wrap the bar stands for screen rows instead of lines
+    let wrapped_rows = buffer.wrapped_rows();
+    let row_of = |line| match &wrapped_rows {
+        Some(rows) => scrollbar::rows_before(rows, line),
+        None => line,
+    };
+    let total = row_of(num_lines);
+    let thumb = scrollbar::thumb(row_of(position.scroll_offset), visible, total, area.height);
+    let mut column: Vec<(char, Slot)> = (0..area.height)
+        .map(|row| match thumb.contains(&row) {
+            true => ('┃', Slot::ScrollbarThumb),
+            false => ('│', Slot::Scrollbar),
+        })
+        .collect();
+    let selection = buffer
+        .selection_start
+        .zip(buffer.selection_end)
+        .map(|(start, end)| (row_of(start.0), row_of(end.0)));
+    let cursor = row_of(position.cursor_line);
+    for (row, marker) in scrollbar::markers(cursor, selection, total, area.height) {
+        column[row as usize] = match marker {
+            Marker::Selection => ('▌', Slot::ScrollbarSelection),
+            Marker::Cursor => ('━', Slot::ScrollbarCursor),
+        };
+    }
+    let x = area.right() - 1;
+    for (row, (c, slot)) in column.into_iter().enumerate() {
+        frame.render_widget(
+            ratatui::widgets::Paragraph::new(c.to_string()).style(theme.style(slot)),
+            Rect::new(x, area.y + row as u16, 1, 1),
+        );
+    }
+
+    if app_state.scrollbar != Scrollbar::Minimap {
+        return;
+    }
+    let start = scrollbar::minimap_start(position.scroll_offset, visible, num_lines, area.height);
+    let shown = position.scroll_offset..position.scroll_offset + visible;
+    for row in 0..area.height {
+        let lines = scrollbar::minimap_lines(start, row);
+        if lines.start >= num_lines {
+            break;
+        }
+        let slot = match lines.start < shown.end && shown.start < lines.end {
+            true => Slot::MinimapView,
+            false => Slot::Minimap,
+        };
+        let text = scrollbar::minimap_row(
+            lines.filter_map(|line| buffer.buffer.line_at(line).map(|line| line.as_slice())),
+        );
+        frame.render_widget(
+            ratatui::widgets::Paragraph::new(text).style(theme.style(slot)),
+            Rect::new(area.x, area.y + row, MINIMAP_WIDTH, 1),
+        );
+    }
+}
+
+/// The visible part of a line, styled by what the highlighter found and
+/// by `marks` from `show_whitespace`, which may be empty.
+fn styled_text(
+    visible: &VisibleLine,
+    marks: &[Option<Whitespace>],
+    spans: &[highlight::Span],
+    theme: &Theme,
+) -> Line<'static> {
+    let scope_at = |index: &usize| {
+        spans
+            .iter()
+            .find(|span| span.range.contains(index))
+            .map(|span| span.scope)
+    };
+    let style_of = |(scope, mark): (Option<Scope>, Option<Whitespace>)| match mark {
+        Some(Whitespace::Shown) => theme.scope(scope).patch(theme.style(Slot::Whitespace)),
+        Some(Whitespace::Trailing) => theme.style(Slot::TrailingWhitespace),
+        None => theme.scope(scope),
+    };
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut style = (None, None);
+    for (i, (c, index)) in visible.text.chars().zip(&visible.indices).enumerate() {
+        let next_style = (scope_at(index), marks.get(i).copied().flatten());
+        if next_style != style && !text.is_empty() {
+            parts.push(ratatui::text::Span::styled(
+                std::mem::take(&mut text),
+                style_of(style),
+            ));
+        }
+        style = next_style;
+        text.push(c);
+    }
+    parts.push(ratatui::text::Span::styled(text, style_of(style)));
+    Line::from(parts)
+}
+
+/// Draw the cursor on the character at `position` of a line shown in
+/// `row` from column `offset` on.
+fn render_cursor(
+    frame: &mut ratatui::Frame,
+    line: &[char],
+    position: usize,
+    offset: usize,
+    row: Rect,
+    style: ratatui::style::Style,
+) {
+    // get character under cursor, tabs and zero-width characters are shown
+    // as a single space
+    let char = line.get(position);
+    let cursor_char = match char {
+        Some('\t') => ' ',
+        Some(c) if is_zero_width(*c) => ' ',
+        Some(c) => *c,
+        None => '_',
+    };
+
+    // the placeholder past the end of the line is not underlined
+    let mut cursor = ratatui::text::Span::styled(cursor_char.to_string(), style);
+    if char.is_none() {
+        cursor = cursor.not_underlined();
+    }
+
+    let the_cusor = Line::from(vec![cursor]);
+    let cursor_column = display_column(line, position);
+
+    if let Some(cursor_x) = cursor_column.checked_sub(offset)
+        && cursor_x < row.width as usize
+    {
+        let cursor_width = the_cusor.width().max(1) as u16;
+        frame.render_widget(
+            ratatui::widgets::Paragraph::new(the_cusor).alignment(ratatui::layout::Alignment::Left),
+            ratatui::layout::Rect::new(
+                row.x + cursor_x as u16,
+                row.y,
+                cursor_width.min(row.width - cursor_x as u16),
+                1,
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::{Terminal, backend::TestBackend};
+
+    use super::*;
+    use crate::highlight::Highlighter;
+
+    #[test]
+    pub fn header_keeps_the_current_buffer_visible() {
+        let mut state = ApplicationState {
+            buffers: (0..10)
+                .map(|i| BufferEntry {
+                    name: format!("buffer{}", i),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let shown = |state: &ApplicationState| {
+            let tabs = header_tabs(state, 60, "INSERT");
+            (
+                tabs.iter().map(|tab| tab.buffer).collect::<Vec<_>>(),
+                tabs[0].x,
+            )
+        };
+
+        assert_eq!(shown(&state), (vec![0, 1, 2, 3, 4], 1));
+        state.current_buffer = 9;
+        // room is left for the "..." in front
+        assert_eq!(shown(&state), (vec![5, 6, 7, 8, 9], 5));
+        state.current_buffer = 5;
+        assert_eq!(shown(&state), (vec![2, 3, 4, 5], 5));
+
+        // a name wider than the header is cut at the border
+        state.buffers[5].name = "a_really_long_buffer_name_here.rs".to_string();
+        let tabs = header_tabs(&state, 20, "INSERT");
+        assert_eq!(tabs.len(), 1);
+        assert_eq!((tabs[0].buffer, tabs[0].x, tabs[0].width), (5, 5, 14));
+        assert_eq!(tabs[0].label, "a_really_long_");
+        let mut terminal = Terminal::new(TestBackend::new(20, 10)).unwrap();
+        terminal.draw(|f| render("INSERT", f, &state)).unwrap();
+    }
+
+    #[test]
+    pub fn clicks_find_the_character_under_the_mouse() {
+        let buffer = BufferEntry::from_bytes("t", b"first line\n\tsecond\nthird".to_vec());
+        let mut state = ApplicationState {
+            buffers: vec![buffer],
+            line_numbers: crate::gutter::LineNumbers::Off,
+            ..Default::default()
+        };
+        let view = Rect::new(0, 3, 20, 10);
+        let click = |state: &ApplicationState, x, y| {
+            let position = View::of(0, &state.buffers[0]);
+            click_target(view, state, &state.buffers[0], &position, x, y)
+        };
+
+        assert_eq!(click(&state, 0, 3), ClickTarget::Text(0, 0));
+        // the tab takes the first four columns
+        assert_eq!(click(&state, 5, 4), ClickTarget::Text(1, 2));
+        assert_eq!(click(&state, 15, 4), ClickTarget::Text(1, 7));
+        assert_eq!(click(&state, 3, 9), ClickTarget::Text(2, 5));
+        assert_eq!(click(&state, 19, 12), ClickTarget::Line(2));
+
+        state.buffers[0] = BufferEntry::from_bytes("t", b"aaaa bbbbbb cc".to_vec());
+        state.buffers[0].soft_wrap = true;
+        let view = Rect::new(0, 3, 11, 10);
+        let position = View::of(0, &state.buffers[0]);
+        let click = |x, y| click_target(view, &state, &state.buffers[0], &position, x, y);
+        assert_eq!(click(1, 4), ClickTarget::Text(0, 6));
+        // past the end of a wrapped row
+        assert_eq!(click(8, 3), ClickTarget::Text(0, 4));
+    }
+
+    /// How many lines are highlighted per character typed on line `line`
+    /// of a Rust file with `lines` lines, while the end of it is shown.
+    fn highlighted_per_keystroke(lines: usize, line: usize) -> usize {
+        let text: String = (0..lines)
+            .map(|i| format!("let x{} = {};\n", i, i))
+            .collect();
+        let mut buffer = BufferEntry::from_bytes("bench", text.into_bytes());
+        buffer.highlighter = Highlighter::for_path(std::path::Path::new("bench.rs"));
+        let mut state = ApplicationState {
+            buffers: vec![buffer],
+            window_size: (120, 35),
+            ..Default::default()
+        };
+        state.buffers[0].goto_position(lines - 1, 0, 35);
+        let mut terminal = Terminal::new(TestBackend::new(120, 40)).unwrap();
+
+        // the first frame highlights everything above the view once
+        terminal.draw(|f| render("INSERT", f, &state)).unwrap();
+        let before = state.buffers[0].highlighter.lines_highlighted();
+        for _ in 0..100 {
+            // as if typed in another view
+            state.buffers[0].buffer.line_at_mut(line).unwrap().push('x');
+            state.fit_buffer_to_view();
+            terminal.draw(|f| render("INSERT", f, &state)).unwrap();
+        }
+        (state.buffers[0].highlighter.lines_highlighted() - before) / 100
+    }
+
+    #[test]
+    pub fn render_cost_does_not_grow_with_the_file() {
+        for lines in [1_000, 20_000] {
+            assert_eq!(highlighted_per_keystroke(lines, lines - 1), 1);
+            // far above the view, but every line below depends on it
+            assert_eq!(highlighted_per_keystroke(lines, 0), 1);
         }
     }
 }
@@ -28,8 +28,8 @@ impl EditorMode for NavigationMode {
         }
 
         let buffer = &mut app_state.buffers[app_state.current_buffer];
-        match key_event.code {
-            KeyCode::Char(c) => match c {
+        if let KeyCode::Char(c) = key_event.code {
+            match c {
                 's' => buffer.goto_line_start(),
                 'd' => buffer.skip_word_backward(),
                 'f' => buffer.move_cursor_left(),
@@ -42,8 +42,7 @@ impl EditorMode for NavigationMode {
                 'c' => buffer.move_cursor_page_up(app_state.window_size.1),
                 'n' => buffer.move_cursor_page_down(app_state.window_size.1),
                 _ => (),
-            },
-            _ => (),
+            }
         }
     }
 
@@ -1,17 +1,27 @@
-use std::io::Write;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 use crossterm::event::{KeyCode, KeyModifiers};
 use ratatui::{
     layout::{Constraint, Flex, Layout, Rect},
     style::Stylize,
+    text::Span,
     widgets::{Block, List, Paragraph, Wrap},
 };
 
 use crate::{
     app,
-    bufferentry::BufferEntry,
+    bufferentry::{BufferEntry, display_names},
+    fuzzy::fuzzy_filter,
     mode::EditorMode,
-    modeutil::{render_mode_header, rotate_buffer},
+    modeutil::{
+        popup_area, render_mode_header, render_status_line, rotate_buffer, screen_areas,
+        without_sidebar,
+    },
+    pathinput::{absolute_path, complete_path, display_path, missing_parent},
+    theme::Slot,
 };
 
 #[derive(Default, PartialEq)]
@@ -20,22 +30,39 @@ enum ActivePopup {
     None,
     RenameBuffer,
     OpenFile,
+    NewFile,
+    SaveAs,
+    RenameFile,
+    CreateDirectory,
+    ConfirmClose,
+    ConfirmQuit,
+    RecentFiles,
+    Buffers,
 }
 
 #[derive(Default)]
 pub struct NormalMode {
     active_popup: ActivePopup,
     fuzzy_open_search: String,
-    fuzzy_open_suggestion: String,
+    fuzzy_open_suggestions: Vec<String>,
+    path_input: String,
+    path_suggestions: Vec<String>,
+    /// Close the current buffer once it was saved successfully.
+    close_after_save: bool,
+    /// Where Save As writes to. The buffer only takes this path once it
+    /// was written there.
+    save_as_path: Option<PathBuf>,
+    recent_selection: usize,
+    buffer_search: String,
+    buffer_selection: usize,
 }
 
-fn popup_area(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
-    let vertical = Layout::vertical([Constraint::Percentage(percent_y)]).flex(Flex::Center);
-    let horizontal = Layout::horizontal([Constraint::Percentage(percent_x)]).flex(Flex::Center);
-    let [area] = vertical.areas(area);
-    let [area] = horizontal.areas(area);
-    area
-}
+/// Maximum number of matches shown in the fuzzy open popup.
+const MAX_FUZZY_SUGGESTIONS: usize = 10;
+
+/// Score added to the most recently used file in the fuzzy open popup, the
+/// older ones get one point less per position in the list.
+const RECENT_FILE_BONUS: i64 = 60;
 
 impl NormalMode {
     fn render_logo(&self, frame: &mut ratatui::Frame, dest: ratatui::layout::Rect) {
@@ -59,26 +86,36 @@ impl NormalMode {
         let lst = ratatui::widgets::List::new(vec![
             "<- ->: Change Buffer",
             "w: Write Buffer",
-            "n: New Buffer",
+            "s: Save Buffer As",
+            "m: Rename File on Disk",
+            "n: New File",
             "c: Close Buffer",
+            "q: Quit",
             "a: Name Buffer",
+            "l: Lock/unlock Buffer (read-only)",
+            "#: Cycle line numbers",
+            "z: Toggle soft wrap",
+            "v: Show/hide whitespace",
+            "t: Cycle themes",
             "o: Open File",
+            "r: Recent Files",
+            "b: Buffer list",
+            "CTRL-T: Toggle file tree",
+            "ALT-V/ALT-H: Split view side by side/stacked",
+            "ALT-X: Close view, ALT-+/-: Resize, ALT-Arrows: Move",
             "CTRL-Q: Enter Normal mode",
             "CTRL-W: Enter Select mode",
             "CTRL-E: Enter edit mode",
+            "ESC: Back to Normal mode, close popups",
         ])
         .block(Block::bordered().title("Keys"));
 
         frame.render_widget(lst, dest);
     }
 
-    fn render_default_view(
-        &self,
-        frame: &mut ratatui::Frame<'_>,
-        layout: std::rc::Rc<[ratatui::prelude::Rect]>,
-    ) {
-        self.render_logo(frame, layout[1]);
-        self.render_menu(frame, layout[2]);
+    fn render_default_view(&self, frame: &mut ratatui::Frame<'_>, logo: Rect, menu: Rect) {
+        self.render_logo(frame, logo);
+        self.render_menu(frame, menu);
     }
 
     fn render_rename_popup(
@@ -92,7 +129,9 @@ impl NormalMode {
             .wrap(Wrap { trim: true });
         frame.render_widget(paragraph, dest);
 
-        let block = Block::bordered().title("Current Buffer Name").on_blue();
+        let block = Block::bordered()
+            .title("Current Buffer Name (empty: use file name)")
+            .style(app_state.theme.style(Slot::Popup));
         let buffer_name = format!(
             "{}{}",
             app_state.buffers[app_state.current_buffer].name, "_"
@@ -113,22 +152,84 @@ impl NormalMode {
             .wrap(Wrap { trim: true });
         frame.render_widget(paragraph, dest);
 
-        let block = Block::bordered().title("Fuzzy open file..").on_blue();
+        let title = if app_state.file_walker.show_hidden {
+            "Fuzzy open file.. (Tab: hide hidden files)"
+        } else {
+            "Fuzzy open file.. (Tab: show hidden files)"
+        };
+        let block = Block::bordered()
+            .title(title)
+            .style(app_state.theme.style(Slot::Popup));
         let buffer_name = format!("{}{}", self.fuzzy_open_search, "_");
-        let buffer_suggestion = format!("{}", self.fuzzy_open_suggestion);
-        let area = popup_area(dest, 60, 30);
+        let area = popup_area(dest, 60, 60);
         frame.render_widget(block.clone(), area);
 
-        let lst = List::new([
-            buffer_name.fg(ratatui::style::Color::default()),
-            buffer_suggestion.fg(ratatui::style::Color::DarkGray),
-        ])
-        .block(block.clone());
+        let mut items = vec![buffer_name.fg(ratatui::style::Color::default())];
+        for (id, suggestion) in self.fuzzy_open_suggestions.iter().enumerate() {
+            let slot = if id == 0 {
+                Slot::SelectedSuggestion
+            } else {
+                Slot::Suggestion
+            };
+            items.push(Span::styled(
+                suggestion.clone(),
+                app_state.theme.style(slot),
+            ));
+        }
+
+        let lst = List::new(items).block(block.clone());
 
         frame.render_widget(lst, area);
         // frame.render_widget(Paragraph::new(buffer_suggestion.clone()).block(block), area);
     }
 
+    fn render_path_popup(
+        &self,
+        frame: &mut ratatui::Frame,
+        dest: Rect,
+        title: &str,
+        app_state: &app::ApplicationState,
+    ) {
+        let block = Block::bordered()
+            .title(title)
+            .style(app_state.theme.style(Slot::Popup));
+        let area = popup_area(dest, 60, 60);
+        frame.render_widget(block.clone(), area);
+
+        let mut items = vec![format!("{}_", self.path_input).fg(ratatui::style::Color::default())];
+        items.extend(
+            self.path_suggestions
+                .iter()
+                .map(|s| Span::styled(s.clone(), app_state.theme.style(Slot::Suggestion))),
+        );
+        frame.render_widget(List::new(items).block(block), area);
+    }
+
+    fn render_create_directory_popup(
+        &self,
+        frame: &mut ratatui::Frame,
+        dest: Rect,
+        app_state: &app::ApplicationState,
+    ) {
+        let block = Block::bordered()
+            .title("Save Buffer")
+            .style(app_state.theme.style(Slot::Popup));
+        let directory = self
+            .save_path(app_state)
+            .as_deref()
+            .and_then(missing_parent)
+            .unwrap_or_default();
+        let text = format!(
+            "Directory '{}' does not exist. Create it? (y/n)",
+            directory.to_string_lossy()
+        );
+        let area = popup_area(dest, 60, 20);
+        frame.render_widget(
+            Paragraph::new(text).wrap(Wrap { trim: true }).block(block),
+            area,
+        );
+    }
+
     fn rename_buffer(&mut self, _app_state: &app::ApplicationState) {
         if _app_state.buffers.is_empty() {
             return;
@@ -140,6 +241,13 @@ impl NormalMode {
         self.active_popup = ActivePopup::OpenFile;
     }
 
+    /// Show the fuzzy file finder, e.g. when mu was started with a directory.
+    pub fn show_open_file(&mut self) {
+        self.fuzzy_open_search.clear();
+        self.fuzzy_open_suggestions.clear();
+        self.active_popup = ActivePopup::OpenFile;
+    }
+
     fn handle_keys_open_file(
         &mut self,
         key_event: crossterm::event::KeyEvent,
@@ -147,14 +255,19 @@ impl NormalMode {
     ) {
         match key_event.code {
             KeyCode::Enter => {
-                if self.fuzzy_open_suggestion.is_empty() {
+                let Some(file_name) = self.fuzzy_open_suggestions.first() else {
                     return;
-                }
+                };
 
-                let mut buffer = BufferEntry::from_file(self.fuzzy_open_suggestion.clone());
-                buffer.name = self.fuzzy_open_suggestion.clone();
-                app_state.buffers.push(buffer);
-                app_state.current_buffer = app_state.buffers.len() - 1;
+                // the walker yields paths relative to its root
+                let path = app_state.file_walker.full_path(Path::new(file_name));
+                match BufferEntry::from_file(path.to_string_lossy().to_string()) {
+                    Ok(buffer) => app_state.open_buffer(buffer),
+                    Err(e) => {
+                        app_state.status_message =
+                            Some(format!("Could not open {}: {}", file_name, e))
+                    }
+                }
                 self.active_popup = ActivePopup::None;
             }
             KeyCode::Char(c) => {
@@ -165,10 +278,476 @@ impl NormalMode {
                 self.fuzzy_open_search.pop();
                 self.update_suggsestions(app_state);
             }
+            KeyCode::Tab => {
+                app_state.file_walker.toggle_hidden();
+                self.update_suggsestions(app_state);
+            }
+            _ => (),
+        }
+    }
+
+    fn handle_keys_recent_files(
+        &mut self,
+        key_event: crossterm::event::KeyEvent,
+        app_state: &mut app::ApplicationState,
+    ) {
+        let count = app_state.recent_files.files.len();
+        match key_event.code {
+            KeyCode::Up => self.recent_selection = self.recent_selection.saturating_sub(1),
+            KeyCode::Down if self.recent_selection + 1 < count => self.recent_selection += 1,
+            KeyCode::Enter => {
+                self.active_popup = ActivePopup::None;
+                let Some(recent) = app_state.recent_files.files.get(self.recent_selection) else {
+                    return;
+                };
+                let path = recent.path.clone();
+
+                if let Some(id) = app_state
+                    .buffers
+                    .iter()
+                    .position(|b| b.path.as_ref() == Some(&path))
+                {
+                    app_state.current_buffer = id;
+                    return;
+                }
+                match BufferEntry::from_file(&path) {
+                    Ok(buffer) => app_state.open_buffer(buffer),
+                    Err(e) => {
+                        app_state.status_message =
+                            Some(format!("Could not open {}: {}", display_path(&path), e))
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn render_recent_files_popup(
+        &self,
+        frame: &mut ratatui::Frame,
+        dest: Rect,
+        app_state: &app::ApplicationState,
+    ) {
+        let block = Block::bordered()
+            .title("Recent files (Enter: open)")
+            .style(app_state.theme.style(Slot::Popup));
+        let area = popup_area(dest, 60, 60);
+
+        // keep the selection in view
+        let visible = area.height.saturating_sub(2) as usize;
+        let skip = (self.recent_selection + 1).saturating_sub(visible);
+        let items: Vec<_> = app_state
+            .recent_files
+            .files
+            .iter()
+            .enumerate()
+            .skip(skip)
+            .map(|(id, f)| {
+                let slot = if id == self.recent_selection {
+                    Slot::SelectedSuggestion
+                } else {
+                    Slot::Suggestion
+                };
+                Span::styled(display_path(&f.path), app_state.theme.style(slot))
+            })
+            .collect();
+        frame.render_widget(List::new(items).block(block), area);
+    }
+
+    /// Show the open buffers, so the previously used one is selected.
+    fn show_buffers(&mut self, app_state: &app::ApplicationState) {
+        if app_state.buffers.is_empty() {
+            return;
+        }
+        self.buffer_search.clear();
+        self.buffer_selection = if app_state.buffers.len() > 1 { 1 } else { 0 };
+        self.active_popup = ActivePopup::Buffers;
+    }
+
+    /// The buffers listed in the buffer list, the most recently used first
+    /// and narrowed down by what was typed.
+    fn buffer_choices(&self, app_state: &app::ApplicationState) -> Vec<usize> {
+        let names = display_names(&app_state.buffers);
+        fuzzy_filter(
+            &self.buffer_search,
+            &app_state.buffers_by_use(),
+            |id| names[*id].clone(),
+            |_| 0,
+        )
+        .into_iter()
+        .copied()
+        .collect()
+    }
+
+    fn handle_keys_buffers(
+        &mut self,
+        key_event: crossterm::event::KeyEvent,
+        app_state: &mut app::ApplicationState,
+    ) {
+        let choices = self.buffer_choices(app_state);
+        match key_event.code {
+            KeyCode::Up => self.buffer_selection = self.buffer_selection.saturating_sub(1),
+            KeyCode::Down if self.buffer_selection + 1 < choices.len() => {
+                self.buffer_selection += 1
+            }
+            KeyCode::Enter => {
+                if let Some(id) = choices.get(self.buffer_selection) {
+                    app_state.current_buffer = *id;
+                    self.active_popup = ActivePopup::None;
+                }
+            }
+            KeyCode::Delete => {
+                let Some(id) = choices.get(self.buffer_selection).copied() else {
+                    return;
+                };
+                let current = app_state.current_buffer;
+                app_state.current_buffer = id;
+                // a buffer with unsaved changes asks first and stays current
+                self.close_buffer(app_state);
+                if self.active_popup != ActivePopup::Buffers {
+                    return;
+                }
+                app_state.current_buffer = if current > id {
+                    current - 1
+                } else {
+                    current.min(app_state.buffers.len().saturating_sub(1))
+                };
+                let count = self.buffer_choices(app_state).len();
+                if count == 0 {
+                    self.active_popup = ActivePopup::None;
+                }
+                self.buffer_selection = self.buffer_selection.min(count.saturating_sub(1));
+            }
+            KeyCode::Char(c) => {
+                self.buffer_search.push(c);
+                self.buffer_selection = 0;
+            }
+            KeyCode::Backspace => {
+                self.buffer_search.pop();
+                self.buffer_selection = 0;
+            }
+            _ => (),
+        }
+    }
+
+    fn render_buffers_popup(
+        &self,
+        frame: &mut ratatui::Frame,
+        dest: Rect,
+        app_state: &app::ApplicationState,
+    ) {
+        let block = Block::bordered()
+            .title("Buffers (Enter: switch, Del: close)")
+            .style(app_state.theme.style(Slot::Popup));
+        let area = popup_area(dest, 60, 60);
+
+        // keep the selection in view, below the search
+        let visible = area.height.saturating_sub(3) as usize;
+        let skip = (self.buffer_selection + 1).saturating_sub(visible);
+        let names = display_names(&app_state.buffers);
+        let mut items =
+            vec![format!("{}_", self.buffer_search).fg(ratatui::style::Color::default())];
+        items.extend(
+            self.buffer_choices(app_state)
+                .into_iter()
+                .enumerate()
+                .skip(skip)
+                .map(|(i, id)| {
+                    let buffer = &app_state.buffers[id];
+                    let slot = if i == self.buffer_selection {
+                        Slot::SelectedSuggestion
+                    } else {
+                        Slot::Suggestion
+                    };
+                    let text = format!(
+                        "{} {}{}",
+                        if buffer.modified { "●" } else { " " },
+                        names[id],
+                        if buffer.read_only { " 🔒" } else { "" }
+                    );
+                    Span::styled(text, app_state.theme.style(slot))
+                }),
+        );
+        frame.render_widget(List::new(items).block(block), area);
+    }
+
+    fn new_file(&mut self) {
+        self.path_input.clear();
+        self.path_suggestions.clear();
+        self.active_popup = ActivePopup::NewFile;
+    }
+
+    /// Open the file the user entered, or create a new buffer for it if it
+    /// does not exist yet. An empty path creates an untitled buffer.
+    fn finish_new_file(&mut self, app_state: &mut app::ApplicationState) {
+        let path = self.path_input.trim().to_string();
+        match BufferEntry::open_or_create(path.clone()) {
+            Ok(buffer) => app_state.open_buffer(buffer),
+            Err(e) => app_state.status_message = Some(format!("Could not open {}: {}", path, e)),
+        }
+        self.active_popup = ActivePopup::None;
+    }
+
+    /// Where the current buffer is saved to, the Save As path if one was
+    /// chosen.
+    fn save_path(&self, app_state: &app::ApplicationState) -> Option<PathBuf> {
+        let buffer = app_state.buffers.get(app_state.current_buffer)?;
+        self.save_as_path.clone().or_else(|| buffer.path.clone())
+    }
+
+    /// Save the current buffer. Untitled buffers ask for a path first and
+    /// missing parent directories are only created after confirmation.
+    fn write_buffer(&mut self, app_state: &mut app::ApplicationState) {
+        let save_path = self.save_path(app_state);
+        let Some(buffer) = app_state.buffers.get_mut(app_state.current_buffer) else {
+            return;
+        };
+        // writing a copy elsewhere is fine for read-only and changed files
+        let save_as = self.save_as_path.is_some();
+
+        if buffer.read_only && !save_as {
+            app_state.status_message = Some(format!("{} is read-only", buffer.display_name()));
+            self.close_after_save = false;
+            return;
+        }
+
+        if buffer.is_untitled() && !save_as {
+            self.path_input.clear();
+            self.path_suggestions.clear();
+            self.active_popup = ActivePopup::SaveAs;
+            return;
+        }
+
+        if buffer.changed_on_disk() && !save_as {
+            app_state.external_change = Some(app_state.current_buffer);
+            self.close_after_save = false;
+            return;
+        }
+
+        if save_path.as_deref().and_then(missing_parent).is_some() {
+            self.active_popup = ActivePopup::CreateDirectory;
+            return;
+        }
+
+        self.active_popup = ActivePopup::None;
+        let written = match self.save_as_path.take() {
+            Some(path) => buffer.save_as(&path),
+            None => buffer.write_to_file(),
+        };
+        match written {
+            Ok(()) => {
+                app_state.status_message = Some(format!("Wrote {}", buffer.display_name()));
+                if std::mem::take(&mut self.close_after_save) {
+                    close_buffer(app_state);
+                }
+            }
+            Err(e) => {
+                app_state.status_message =
+                    Some(format!("Could not write {}: {}", buffer.display_name(), e));
+                self.close_after_save = false;
+            }
+        }
+    }
+
+    /// Close the current buffer, asking first if it has unsaved changes.
+    fn close_buffer(&mut self, app_state: &mut app::ApplicationState) {
+        match app_state.buffers.get(app_state.current_buffer) {
+            Some(buffer) if buffer.modified => self.active_popup = ActivePopup::ConfirmClose,
+            Some(_) => close_buffer(app_state),
+            None => (),
+        }
+    }
+
+    /// Quit mu, asking first if any buffer has unsaved changes.
+    fn quit(&mut self, app_state: &mut app::ApplicationState) {
+        let unsaved = app_state.buffers.iter().enumerate().any(|(id, b)| {
+            b.modified && !(app_state.write_stdout && id == app_state.current_buffer)
+        });
+        if unsaved {
+            self.active_popup = ActivePopup::ConfirmQuit;
+        } else {
+            app_state.exit_requested = true;
+        }
+    }
+
+    fn handle_keys_confirm_close(
+        &mut self,
+        key_event: crossterm::event::KeyEvent,
+        app_state: &mut app::ApplicationState,
+    ) {
+        self.active_popup = ActivePopup::None;
+        match key_event.code {
+            KeyCode::Char('s') => {
+                // saving may need more input first, e.g. a path
+                self.close_after_save = true;
+                self.write_buffer(app_state);
+            }
+            KeyCode::Char('d') => close_buffer(app_state),
+            _ => (),
+        }
+    }
+
+    fn handle_keys_confirm_quit(
+        &mut self,
+        key_event: crossterm::event::KeyEvent,
+        app_state: &mut app::ApplicationState,
+    ) {
+        self.active_popup = ActivePopup::None;
+        match key_event.code {
+            KeyCode::Char('a') => {
+                let problems = save_all(app_state);
+                if problems.is_empty() {
+                    app_state.exit_requested = true;
+                } else {
+                    app_state.status_message = Some(format!("Not saved: {}", problems.join(", ")));
+                }
+            }
+            KeyCode::Char('d') => app_state.exit_requested = true,
+            _ => (),
+        }
+    }
+
+    fn render_confirm_close_popup(
+        &self,
+        frame: &mut ratatui::Frame,
+        dest: Rect,
+        app_state: &app::ApplicationState,
+    ) {
+        let text = format!(
+            "{} has unsaved changes.\n\ns: save and close\nd: discard changes and close\nc: cancel",
+            app_state.buffers[app_state.current_buffer].display_name()
+        );
+        let block = Block::bordered()
+            .title("Close Buffer")
+            .style(app_state.theme.style(Slot::Popup));
+        frame.render_widget(
+            Paragraph::new(text).wrap(Wrap { trim: false }).block(block),
+            popup_area(dest, 60, 50),
+        );
+    }
+
+    fn render_confirm_quit_popup(
+        &self,
+        frame: &mut ratatui::Frame,
+        dest: Rect,
+        app_state: &app::ApplicationState,
+    ) {
+        let mut lines = vec!["These buffers have unsaved changes:".to_string()];
+        lines.extend(
+            display_names(&app_state.buffers)
+                .into_iter()
+                .zip(app_state.buffers.iter())
+                .filter(|(_, b)| b.modified)
+                .map(|(name, _)| format!("  ● {}", name)),
+        );
+        lines.push(String::new());
+        lines.push("a: save all and quit".to_string());
+        lines.push("d: discard all changes and quit".to_string());
+        lines.push("c: cancel".to_string());
+
+        let block = Block::bordered()
+            .title("Quit")
+            .style(app_state.theme.style(Slot::Popup));
+        frame.render_widget(
+            Paragraph::new(lines.join("\n")).block(block),
+            popup_area(dest, 60, 80),
+        );
+    }
+
+    /// Ask for a path, prefilled with the path of the current buffer.
+    fn ask_for_path(&mut self, app_state: &app::ApplicationState, popup: ActivePopup) {
+        let Some(buffer) = app_state.buffers.get(app_state.current_buffer) else {
+            return;
+        };
+        self.path_input = buffer.path.as_deref().map(display_path).unwrap_or_default();
+        self.path_suggestions.clear();
+        self.active_popup = popup;
+    }
+
+    fn rename_file(&mut self, app_state: &mut app::ApplicationState, path: &str) {
+        let buffer = &mut app_state.buffers[app_state.current_buffer];
+        app_state.status_message = Some(match buffer.rename_file(Path::new(path)) {
+            Ok(()) => format!("Renamed to {}", path),
+            Err(e) => format!("Could not rename to {}: {}", path, e),
+        });
+        self.active_popup = ActivePopup::None;
+    }
+
+    fn handle_keys_path(
+        &mut self,
+        key_event: crossterm::event::KeyEvent,
+        app_state: &mut app::ApplicationState,
+    ) {
+        match key_event.code {
+            KeyCode::Enter => match self.active_popup {
+                ActivePopup::NewFile => self.finish_new_file(app_state),
+                ActivePopup::SaveAs | ActivePopup::RenameFile => {
+                    let path = self.path_input.trim().to_string();
+                    if path.is_empty() || path.ends_with('/') {
+                        return;
+                    }
+                    if self.active_popup == ActivePopup::RenameFile {
+                        self.rename_file(app_state, &path);
+                    } else if app_state.buffers[app_state.current_buffer]
+                        .would_replace(Path::new(&path))
+                    {
+                        app_state.status_message = Some(format!("{} already exists", path));
+                    } else {
+                        self.save_as_path = Some(PathBuf::from(&path));
+                        self.write_buffer(app_state);
+                    }
+                }
+                _ => (),
+            },
+            KeyCode::Tab => {
+                let (completed, suggestions) = complete_path(&self.path_input);
+                self.path_input = completed;
+                self.path_suggestions = suggestions;
+            }
+            KeyCode::Char(c) => {
+                self.path_input.push(c);
+                self.path_suggestions.clear();
+            }
+            KeyCode::Backspace => {
+                self.path_input.pop();
+                self.path_suggestions.clear();
+            }
             _ => (),
         }
     }
 
+    fn handle_keys_create_directory(
+        &mut self,
+        key_event: crossterm::event::KeyEvent,
+        app_state: &mut app::ApplicationState,
+    ) {
+        let directory = self
+            .save_path(app_state)
+            .as_deref()
+            .and_then(missing_parent);
+        self.active_popup = ActivePopup::None;
+
+        match (key_event.code, directory) {
+            (KeyCode::Char('y'), Some(directory)) => {
+                if let Err(e) = std::fs::create_dir_all(&directory) {
+                    app_state.status_message = Some(format!(
+                        "Could not create {}: {}",
+                        directory.to_string_lossy(),
+                        e
+                    ));
+                    self.save_as_path = None;
+                    return;
+                }
+                self.write_buffer(app_state);
+            }
+            _ => {
+                app_state.status_message = Some("Buffer not saved".to_string());
+                self.close_after_save = false;
+                self.save_as_path = None;
+            }
+        }
+    }
+
     fn handle_keys_rename(
         &mut self,
         key_event: crossterm::event::KeyEvent,
@@ -176,13 +755,11 @@ impl NormalMode {
     ) {
         match key_event.code {
             KeyCode::Enter => {
+                // an empty name falls back to the file name
                 let buffer = &mut app_state.buffers[app_state.current_buffer];
                 buffer.name = buffer.name.trim().to_string();
-                if buffer.name.is_empty() {
-                    return;
-                }
                 self.active_popup = ActivePopup::None;
-                self.fuzzy_open_suggestion = String::new();
+                self.fuzzy_open_suggestions.clear();
                 self.fuzzy_open_search = String::new();
             }
             KeyCode::Char(c) => {
@@ -206,11 +783,31 @@ impl NormalMode {
     ) {
         match key_event.code {
             KeyCode::Char(c) => match c {
-                'n' => new_buffer(app_state),
-                'c' => close_buffer(app_state),
+                'n' => self.new_file(),
+                'c' => self.close_buffer(app_state),
+                'q' => self.quit(app_state),
                 'a' => self.rename_buffer(app_state),
-                'w' => write_buffer(app_state),
+                'w' => self.write_buffer(app_state),
+                's' => self.ask_for_path(app_state, ActivePopup::SaveAs),
+                'm' => self.ask_for_path(app_state, ActivePopup::RenameFile),
                 'o' => self.open_file(app_state),
+                'l' => toggle_read_only(app_state),
+                'z' => toggle_soft_wrap(app_state),
+                'v' => toggle_whitespace(app_state),
+                't' => {
+                    app_state.theme = app_state.theme.next();
+                    app_state.status_message = Some(format!("Theme: {}", app_state.theme.name));
+                }
+                '#' => {
+                    app_state.line_numbers = app_state.line_numbers.next();
+                    app_state.status_message =
+                        Some(format!("Line numbers: {}", app_state.line_numbers.name()));
+                }
+                'b' => self.show_buffers(app_state),
+                'r' => {
+                    self.recent_selection = 0;
+                    self.active_popup = ActivePopup::RecentFiles;
+                }
                 _ => (),
             },
             KeyCode::Left => rotate_buffer(app_state, -1),
@@ -225,35 +822,36 @@ impl NormalMode {
 
     fn update_suggsestions(&mut self, app_state: &mut app::ApplicationState) {
         if self.fuzzy_open_search.is_empty() {
-            self.fuzzy_open_suggestion = String::new();
+            self.fuzzy_open_suggestions.clear();
             return;
         }
 
-        // Check the CWD for files that start with the typed in buffer name
-        for file in std::fs::read_dir(".").unwrap() {
-            let file_name = file.unwrap().file_name().to_string_lossy().to_string();
-            if file_name.starts_with(self.fuzzy_open_search.as_str()) {
-                self.fuzzy_open_suggestion = file_name;
-            }
-        }
-    }
-}
-
-fn write_buffer(app_state: &mut app::ApplicationState) {
-    let buffer = &mut app_state.buffers[app_state.current_buffer];
-    buffer.modified = false;
-
-    // persists buffer as it is to file
-    let mut file = std::fs::File::create(&buffer.name).unwrap();
+        // recently used files rank higher, the most recent one the most
+        let root = absolute_path(app_state.file_walker.root());
+        let recent: HashMap<PathBuf, i64> = app_state
+            .recent_files
+            .files
+            .iter()
+            .enumerate()
+            .filter_map(|(rank, f)| {
+                let relative = f.path.strip_prefix(&root).ok()?;
+                Some((relative.to_path_buf(), RECENT_FILE_BONUS - rank as i64))
+            })
+            .collect();
 
-    // convert all lines to strings:
-    for line in buffer.buffer.lines.iter() {
-        let line_as_string = line.iter().map(|c| c.to_string()).collect::<String>();
-        file.write_all(line_as_string.as_bytes()).unwrap();
-        file.write_all(b"\n").unwrap();
+        // Rank all files below the CWD that survive the ignore rules
+        let files = app_state.file_walker.files();
+        self.fuzzy_open_suggestions = fuzzy_filter(
+            &self.fuzzy_open_search,
+            files,
+            |f| f.to_string_lossy().to_string(),
+            |f| recent.get(f).copied().unwrap_or(0),
+        )
+        .into_iter()
+        .take(MAX_FUZZY_SUGGESTIONS)
+        .map(|f| f.to_string_lossy().to_string())
+        .collect();
     }
-
-    file.flush().unwrap();
 }
 
 impl EditorMode for NormalMode {
@@ -261,6 +859,19 @@ impl EditorMode for NormalMode {
         "NORMAL"
     }
 
+    fn has_popup(&self) -> bool {
+        self.active_popup != ActivePopup::None
+    }
+
+    fn handle_mouse_event(
+        &mut self,
+        _mouse_event: crossterm::event::MouseEvent,
+        _app_state: &mut crate::app::ApplicationState,
+    ) -> bool {
+        // a click on another buffer would change what the popup acts on
+        self.active_popup != ActivePopup::None
+    }
+
     fn handle_key_event(
         &mut self,
         key_event: crossterm::event::KeyEvent,
@@ -270,52 +881,355 @@ impl EditorMode for NormalMode {
             return;
         }
 
+        if key_event.code == KeyCode::Esc {
+            self.active_popup = ActivePopup::None;
+            self.close_after_save = false;
+            self.save_as_path = None;
+            return;
+        }
+
         match self.active_popup {
             ActivePopup::None => self.handle_keys_default(key_event, app_state),
             ActivePopup::RenameBuffer => self.handle_keys_rename(key_event, app_state),
             ActivePopup::OpenFile => self.handle_keys_open_file(key_event, app_state),
+            ActivePopup::NewFile | ActivePopup::SaveAs | ActivePopup::RenameFile => {
+                self.handle_keys_path(key_event, app_state)
+            }
+            ActivePopup::CreateDirectory => self.handle_keys_create_directory(key_event, app_state),
+            ActivePopup::ConfirmClose => self.handle_keys_confirm_close(key_event, app_state),
+            ActivePopup::ConfirmQuit => self.handle_keys_confirm_quit(key_event, app_state),
+            ActivePopup::RecentFiles => self.handle_keys_recent_files(key_event, app_state),
+            ActivePopup::Buffers => self.handle_keys_buffers(key_event, app_state),
         }
     }
 
     fn render(&self, frame: &mut ratatui::Frame, app_state: &crate::app::ApplicationState) {
-        // do a horizontal layout containing the logo and the list of keys
-        let layout = ratatui::layout::Layout::default()
-            .direction(ratatui::layout::Direction::Vertical)
-            .constraints([
-                ratatui::layout::Constraint::Length(3),
-                ratatui::layout::Constraint::Length(5),
-                ratatui::layout::Constraint::Min(1),
-            ])
-            .split(frame.area());
-
-        render_mode_header(frame, layout[0], self.mode_name(), app_state);
+        // the header and status line span the whole width, even if the file
+        // tree is shown
+        let [header, body, status] = screen_areas(frame.area());
+        render_mode_header(frame, header, self.mode_name(), app_state);
+        render_status_line(frame, status, app_state);
+
+        // the logo above the list of keys or the popup
+        let [logo, content] = Layout::vertical([Constraint::Length(5), Constraint::Min(1)])
+            .areas(without_sidebar(body, app_state));
 
         match self.active_popup {
             ActivePopup::None => {
-                self.render_default_view(frame, layout);
+                self.render_default_view(frame, logo, content);
             }
             ActivePopup::RenameBuffer => {
-                self.render_rename_popup(frame, layout[2], app_state);
+                self.render_rename_popup(frame, content, app_state);
             }
             ActivePopup::OpenFile => {
-                self.render_open_file_popup(frame, layout[2], app_state);
+                self.render_open_file_popup(frame, content, app_state);
+            }
+            ActivePopup::NewFile => {
+                self.render_path_popup(frame, content, "New file path (Tab completes)", app_state);
+            }
+            ActivePopup::SaveAs => {
+                self.render_path_popup(frame, content, "Save buffer as (Tab completes)", app_state);
+            }
+            ActivePopup::RenameFile => {
+                self.render_path_popup(
+                    frame,
+                    content,
+                    "Rename file on disk (Tab completes)",
+                    app_state,
+                );
+            }
+            ActivePopup::CreateDirectory => {
+                self.render_create_directory_popup(frame, content, app_state);
+            }
+            ActivePopup::ConfirmClose => {
+                self.render_confirm_close_popup(frame, content, app_state);
+            }
+            ActivePopup::ConfirmQuit => {
+                self.render_confirm_quit_popup(frame, content, app_state);
+            }
+            ActivePopup::RecentFiles => {
+                self.render_recent_files_popup(frame, content, app_state);
+            }
+            ActivePopup::Buffers => {
+                self.render_buffers_popup(frame, content, app_state);
             }
         }
     }
 }
 
 fn close_buffer(app_state: &mut app::ApplicationState) {
-    if app_state.buffers[app_state.current_buffer].modified {
-        // ask user if they want to save changes
+    if app_state.buffers.is_empty() {
+        return;
     }
 
+    app_state.remember_position(app_state.current_buffer);
     app_state.buffers.remove(app_state.current_buffer);
+    app_state.splits.buffer_removed(app_state.current_buffer);
     app_state.current_buffer = app_state.current_buffer.saturating_sub(1);
 }
 
-fn new_buffer(app_state: &mut app::ApplicationState) {
-    let mut buff = BufferEntry::default();
-    buff.name = "untitled".to_string();
-    app_state.buffers.push(buff);
-    app_state.current_buffer = app_state.buffers.len() - 1;
+fn toggle_read_only(app_state: &mut app::ApplicationState) {
+    let Some(buffer) = app_state.buffers.get_mut(app_state.current_buffer) else {
+        return;
+    };
+    buffer.read_only = !buffer.read_only;
+    app_state.status_message = Some(format!(
+        "{} is {}",
+        buffer.display_name(),
+        if buffer.read_only {
+            "read-only"
+        } else {
+            "editable"
+        }
+    ));
+}
+
+fn toggle_soft_wrap(app_state: &mut app::ApplicationState) {
+    let Some(buffer) = app_state.buffers.get_mut(app_state.current_buffer) else {
+        return;
+    };
+    buffer.soft_wrap = !buffer.soft_wrap;
+    app_state.status_message = Some(format!(
+        "Soft wrap {}",
+        if buffer.soft_wrap { "on" } else { "off" }
+    ));
+}
+
+fn toggle_whitespace(app_state: &mut app::ApplicationState) {
+    let Some(buffer) = app_state.buffers.get_mut(app_state.current_buffer) else {
+        return;
+    };
+    buffer.show_whitespace = !buffer.show_whitespace;
+    app_state.status_message = Some(format!(
+        "Whitespace {}",
+        if buffer.show_whitespace {
+            "shown"
+        } else {
+            "hidden"
+        }
+    ));
+}
+
+/// Save every modified buffer. Returns a description of each buffer that
+/// could not be saved.
+fn save_all(app_state: &mut app::ApplicationState) -> Vec<String> {
+    let names = display_names(&app_state.buffers);
+    let mut problems = Vec::new();
+
+    for (buffer, name) in app_state.buffers.iter_mut().zip(names) {
+        if !buffer.modified {
+            continue;
+        }
+
+        let problem = if buffer.is_untitled() {
+            Some("has no file name")
+        } else if buffer.read_only {
+            Some("is read-only")
+        } else if buffer.changed_on_disk() {
+            Some("changed on disk")
+        } else if buffer.path.as_deref().and_then(missing_parent).is_some() {
+            Some("directory does not exist")
+        } else {
+            buffer.write_to_file().err().map(|_| "could not be written")
+        };
+
+        if let Some(problem) = problem {
+            problems.push(format!("{} {}", name, problem));
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::KeyEvent;
+
+    use super::*;
+    use crate::testutil::TempDir;
+
+    fn key(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    fn modified_buffer() -> BufferEntry {
+        let mut buffer = BufferEntry::default();
+        buffer.add_character('a');
+        buffer
+    }
+
+    /// Buffers with the given names, used in the given order.
+    fn state_with_buffers(names: &[&str], used: &[usize]) -> app::ApplicationState {
+        let mut app_state = app::ApplicationState::default();
+        for name in names {
+            app_state.buffers.push(BufferEntry {
+                name: name.to_string(),
+                ..Default::default()
+            });
+        }
+        for id in used {
+            app_state.current_buffer = *id;
+            app_state.touch_current_buffer();
+        }
+        app_state
+    }
+
+    #[test]
+    pub fn buffer_list_is_ordered_by_use_and_filtered() {
+        let mut app_state = state_with_buffers(&["one", "two", "three"], &[0, 2, 1]);
+        let mut mode = NormalMode::default();
+        let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+
+        // the previous buffer is selected
+        mode.handle_key_event(key('b'), &mut app_state);
+        mode.handle_key_event(enter, &mut app_state);
+        assert_eq!(app_state.current_buffer, 2);
+
+        mode.handle_key_event(key('b'), &mut app_state);
+        mode.handle_key_event(key('n'), &mut app_state);
+        mode.handle_key_event(enter, &mut app_state);
+        assert_eq!(app_state.current_buffer, 0);
+    }
+
+    #[test]
+    pub fn buffer_list_closes_buffers() {
+        let mut app_state = state_with_buffers(&["one", "two", "three"], &[0, 1, 2]);
+        app_state.buffers[0].modified = true;
+        let mut mode = NormalMode::default();
+        let delete = KeyEvent::new(KeyCode::Delete, KeyModifiers::NONE);
+
+        mode.handle_key_event(key('b'), &mut app_state);
+        mode.handle_key_event(delete, &mut app_state);
+        assert_eq!(display_names(&app_state.buffers), vec!["one", "three"]);
+        assert_eq!(app_state.current_buffer, 1);
+
+        // unsaved changes are not thrown away without asking
+        mode.handle_key_event(delete, &mut app_state);
+        assert!(mode.active_popup == ActivePopup::ConfirmClose);
+        assert_eq!(app_state.buffers.len(), 2);
+    }
+
+    #[test]
+    pub fn closing_modified_buffer_asks_first() {
+        let mut app_state = app::ApplicationState::default();
+        app_state.buffers.push(modified_buffer());
+        let mut mode = NormalMode::default();
+
+        mode.handle_key_event(key('c'), &mut app_state);
+        assert_eq!(app_state.buffers.len(), 1);
+        mode.handle_key_event(key('c'), &mut app_state);
+        assert_eq!(app_state.buffers.len(), 1);
+
+        mode.handle_key_event(key('c'), &mut app_state);
+        mode.handle_key_event(key('d'), &mut app_state);
+        assert!(app_state.buffers.is_empty());
+    }
+
+    #[test]
+    pub fn failed_save_does_not_close_later() {
+        let mut app_state = app::ApplicationState::default();
+        app_state.buffers.push(modified_buffer());
+        app_state.buffers[0].read_only = true;
+        let mut mode = NormalMode::default();
+
+        mode.handle_key_event(key('c'), &mut app_state);
+        mode.handle_key_event(key('s'), &mut app_state);
+        assert!(!mode.close_after_save);
+        assert_eq!(app_state.buffers.len(), 1);
+    }
+
+    #[test]
+    pub fn quit_asks_only_with_unsaved_changes() {
+        let mut app_state = app::ApplicationState::default();
+        app_state.buffers.push(BufferEntry::default());
+        let mut mode = NormalMode::default();
+        mode.handle_key_event(key('q'), &mut app_state);
+        assert!(app_state.exit_requested);
+
+        let mut app_state = app::ApplicationState::default();
+        app_state.buffers.push(modified_buffer());
+        mode.handle_key_event(key('q'), &mut app_state);
+        assert!(!app_state.exit_requested);
+        mode.handle_key_event(key('d'), &mut app_state);
+        assert!(app_state.exit_requested);
+    }
+
+    #[test]
+    pub fn lock_toggles_read_only() {
+        let mut app_state = app::ApplicationState::default();
+        app_state.buffers.push(BufferEntry::default());
+        let mut mode = NormalMode::default();
+
+        mode.handle_key_event(key('l'), &mut app_state);
+        assert!(app_state.buffers[0].read_only);
+        mode.handle_key_event(key('w'), &mut app_state);
+        assert_eq!(
+            app_state.status_message,
+            Some("untitled is read-only".to_string())
+        );
+        mode.handle_key_event(key('l'), &mut app_state);
+        assert!(!app_state.buffers[0].read_only);
+    }
+
+    #[test]
+    pub fn save_as_does_not_replace_existing_files() {
+        let dir = TempDir::new("save_as");
+        let path = dir.join("precious.txt");
+        std::fs::write(&path, "precious\n").unwrap();
+        let mut app_state = app::ApplicationState::default();
+        app_state.buffers.push(modified_buffer());
+        let mut mode = NormalMode::default();
+
+        mode.handle_key_event(key('w'), &mut app_state);
+        assert!(mode.active_popup == ActivePopup::SaveAs);
+        mode.path_input = path.to_string_lossy().to_string();
+        mode.handle_key_event(
+            KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE),
+            &mut app_state,
+        );
+        assert!(mode.active_popup == ActivePopup::SaveAs);
+        assert!(app_state.buffers[0].is_untitled());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "precious\n");
+    }
+
+    #[test]
+    pub fn save_as_takes_the_path_only_once_written() {
+        let dir = TempDir::new("save_as_dir");
+        let path = dir.join("new/file.txt");
+        let mut app_state = app::ApplicationState::default();
+        app_state.buffers.push(modified_buffer());
+        let mut mode = NormalMode::default();
+        let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+
+        mode.handle_key_event(key('w'), &mut app_state);
+        mode.path_input = path.to_string_lossy().to_string();
+        mode.handle_key_event(enter, &mut app_state);
+        assert!(mode.active_popup == ActivePopup::CreateDirectory);
+        mode.handle_key_event(key('n'), &mut app_state);
+        assert!(app_state.buffers[0].is_untitled());
+        assert!(!dir.join("new").exists());
+
+        mode.handle_key_event(key('w'), &mut app_state);
+        mode.path_input = path.to_string_lossy().to_string();
+        mode.handle_key_event(enter, &mut app_state);
+        mode.handle_key_event(key('y'), &mut app_state);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a\n");
+        assert!(!app_state.buffers[0].is_untitled());
+        assert!(!app_state.buffers[0].modified);
+    }
+
+    #[test]
+    pub fn save_all_reports_untitled_buffers() {
+        let mut app_state = app::ApplicationState::default();
+        app_state.buffers.push(modified_buffer());
+        let mut mode = NormalMode::default();
+
+        mode.handle_key_event(key('q'), &mut app_state);
+        mode.handle_key_event(key('a'), &mut app_state);
+        assert!(!app_state.exit_requested);
+        assert_eq!(
+            app_state.status_message,
+            Some("Not saved: untitled has no file name".to_string())
+        );
+    }
 }
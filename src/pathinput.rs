@@ -0,0 +1,176 @@
+use std::path::{Component, Path, PathBuf};
+
+/// Complete `input` against the file system.
+///
+/// Returns the input extended by the longest common prefix of all entries
+/// that match it, together with the names of these entries. If exactly one
+/// directory matches, a trailing '/' is added so completion can continue in
+/// that directory. Hidden entries are only offered if the input asks for them.
+pub fn complete_path(input: &str) -> (String, Vec<String>) {
+    let (dir_part, prefix) = match input.rfind('/') {
+        Some(pos) => input.split_at(pos + 1),
+        None => ("", input),
+    };
+    let dir = if dir_part.is_empty() { "." } else { dir_part };
+
+    let Ok(read_dir) = std::fs::read_dir(dir) else {
+        return (input.to_string(), Vec::new());
+    };
+
+    let mut matches: Vec<(String, bool)> = read_dir
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            let is_dir = e.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            (name.starts_with(prefix) && (prefix.starts_with('.') || !name.starts_with('.')))
+                .then_some((name, is_dir))
+        })
+        .collect();
+    matches.sort();
+
+    let completed = match matches.as_slice() {
+        [] => prefix.to_string(),
+        [(name, true)] => format!("{}/", name),
+        [(name, false)] => name.clone(),
+        [(first, _), rest @ ..] => rest.iter().fold(first.clone(), |common, (name, _)| {
+            common
+                .chars()
+                .zip(name.chars())
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| a)
+                .collect()
+        }),
+    };
+
+    let names = matches
+        .into_iter()
+        .map(|(name, is_dir)| if is_dir { format!("{}/", name) } else { name })
+        .collect();
+    (format!("{}{}", dir_part, completed), names)
+}
+
+/// The parent directory of `path` if it does not exist yet.
+pub fn missing_parent(path: &Path) -> Option<PathBuf> {
+    path.parent()
+        .filter(|p| !p.as_os_str().is_empty() && !p.exists())
+        .map(Path::to_path_buf)
+}
+
+/// Make `path` absolute, with symlinks resolved for the part of it that
+/// already exists. Components that do not exist yet are appended as given,
+/// after resolving "." and ".." lexically.
+pub fn absolute_path(path: &Path) -> PathBuf {
+    if let Ok(canonical) = path.canonicalize() {
+        return canonical;
+    }
+
+    let joined = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir().unwrap_or_default().join(path)
+    };
+
+    let mut normalized = PathBuf::new();
+    for component in joined.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            c => normalized.push(c),
+        }
+    }
+
+    // canonicalize the longest prefix that exists
+    let mut missing = Vec::new();
+    let mut existing = normalized.as_path();
+    while let Some(parent) = existing.parent() {
+        missing.push(existing.file_name().unwrap_or_default().to_owned());
+        existing = parent;
+        if let Ok(canonical) = existing.canonicalize() {
+            return missing.iter().rev().fold(canonical, |p, c| p.join(c));
+        }
+    }
+    normalized
+}
+
+/// `path` relative to the current working directory if it is below it,
+/// for showing it to the user.
+pub fn display_path(path: &Path) -> String {
+    let cwd = std::env::current_dir()
+        .map(|d| absolute_path(&d))
+        .unwrap_or_default();
+    path.strip_prefix(&cwd)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::testutil::TempDir;
+
+    /// The directory and its path with a trailing slash, ready to complete.
+    fn make_dir(name: &str) -> (TempDir, String) {
+        let dir = TempDir::new(&format!("complete_{}", name));
+        let root = dir.path();
+        fs::create_dir_all(root.join("source")).unwrap();
+        fs::write(root.join("settings.toml"), "").unwrap();
+        fs::write(root.join("readme.md"), "").unwrap();
+        fs::write(root.join(".secret"), "").unwrap();
+        let path = format!("{}/", root.to_string_lossy());
+        (dir, path)
+    }
+
+    #[test]
+    pub fn completes_common_prefix() {
+        let (_dir, dir) = make_dir("prefix");
+        let (completed, names) = complete_path(&format!("{}s", dir));
+        assert_eq!(completed, format!("{}s", dir));
+        assert_eq!(names, vec!["settings.toml", "source/"]);
+
+        let (completed, _) = complete_path(&format!("{}se", dir));
+        assert_eq!(completed, format!("{}settings.toml", dir));
+    }
+
+    #[test]
+    pub fn single_directory_gets_trailing_slash() {
+        let (_dir, dir) = make_dir("slash");
+        let (completed, _) = complete_path(&format!("{}so", dir));
+        assert_eq!(completed, format!("{}source/", dir));
+    }
+
+    #[test]
+    pub fn hidden_files_need_leading_dot() {
+        let (_dir, dir) = make_dir("hidden");
+        let (_, names) = complete_path(&dir);
+        assert!(!names.contains(&".secret".to_string()));
+        let (completed, _) = complete_path(&format!("{}.s", dir));
+        assert_eq!(completed, format!("{}.secret", dir));
+    }
+
+    #[test]
+    pub fn absolute_path_handles_missing_files() {
+        let tmp = std::env::temp_dir().canonicalize().unwrap();
+        let relative = absolute_path(Path::new("Cargo.toml"));
+        assert!(relative.is_absolute());
+        assert!(relative.ends_with("Cargo.toml"));
+
+        let missing = std::env::temp_dir().join("mu_missing/../mu_missing2/new.txt");
+        assert_eq!(absolute_path(&missing), tmp.join("mu_missing2/new.txt"));
+    }
+
+    #[test]
+    pub fn missing_parent_is_reported() {
+        assert_eq!(missing_parent(Path::new("file.txt")), None);
+        assert_eq!(missing_parent(Path::new("/")), None);
+        let missing = std::env::temp_dir().join("mu_does_not_exist/sub/file.txt");
+        assert_eq!(
+            missing_parent(&missing),
+            Some(std::env::temp_dir().join("mu_does_not_exist/sub"))
+        );
+    }
+}
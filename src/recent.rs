@@ -0,0 +1,142 @@
+use std::path::{Path, PathBuf};
+
+use crate::config::data_dir;
+
+/// How many files the list remembers.
+pub const MAX_RECENT_FILES: usize = 50;
+
+/// A recently opened file and where the cursor was when it was closed.
+#[derive(Debug, PartialEq)]
+pub struct RecentFile {
+    pub path: PathBuf,
+    pub cursor_line: usize,
+    pub cursor_position: usize,
+}
+
+/// The most recently used files, newest first. Shared by all sessions and
+/// stored as one "<line> <column> <path>" entry per line.
+#[derive(Debug, Default, PartialEq)]
+pub struct RecentFiles {
+    pub files: Vec<RecentFile>,
+}
+
+/// Where the list of recent files is stored.
+pub fn recent_files_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("recent"))
+}
+
+impl RecentFiles {
+    /// Parse the stored list. Broken lines are skipped, losing an entry is
+    /// not worth an error.
+    pub fn parse(contents: &str) -> RecentFiles {
+        let files = contents
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.splitn(3, ' ');
+                let cursor_line = parts.next()?.parse().ok()?;
+                let cursor_position = parts.next()?.parse().ok()?;
+                let path = parts.next().filter(|p| !p.is_empty())?;
+                Some(RecentFile {
+                    path: PathBuf::from(path),
+                    cursor_line,
+                    cursor_position,
+                })
+            })
+            .take(MAX_RECENT_FILES)
+            .collect();
+        RecentFiles { files }
+    }
+
+    pub fn to_text(&self) -> String {
+        self.files
+            .iter()
+            .map(|f| {
+                format!(
+                    "{} {} {}\n",
+                    f.cursor_line,
+                    f.cursor_position,
+                    f.path.to_string_lossy()
+                )
+            })
+            .collect()
+    }
+
+    /// Load the list from `path`. A missing or unreadable file yields an
+    /// empty list.
+    pub fn load(path: &Path) -> RecentFiles {
+        std::fs::read_to_string(path)
+            .map(|contents| RecentFiles::parse(&contents))
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, self.to_text())
+    }
+
+    /// Move `path` to the front of the list, remembering the cursor position.
+    pub fn remember(&mut self, path: &Path, cursor_line: usize, cursor_position: usize) {
+        self.files.retain(|f| f.path != path);
+        self.files.insert(
+            0,
+            RecentFile {
+                path: path.to_path_buf(),
+                cursor_line,
+                cursor_position,
+            },
+        );
+        self.files.truncate(MAX_RECENT_FILES);
+    }
+
+    /// The cursor position `path` was last left at.
+    pub fn position(&self, path: &Path) -> Option<(usize, usize)> {
+        self.files
+            .iter()
+            .find(|f| f.path == path)
+            .map(|f| (f.cursor_line, f.cursor_position))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn remember_moves_file_to_front() {
+        let mut recent = RecentFiles::default();
+        recent.remember(Path::new("/a.rs"), 1, 2);
+        recent.remember(Path::new("/b.rs"), 0, 0);
+        recent.remember(Path::new("/a.rs"), 7, 3);
+
+        assert_eq!(recent.files.len(), 2);
+        assert_eq!(recent.files[0].path, PathBuf::from("/a.rs"));
+        assert_eq!(recent.position(Path::new("/a.rs")), Some((7, 3)));
+        assert_eq!(recent.position(Path::new("/c.rs")), None);
+    }
+
+    #[test]
+    pub fn list_is_limited() {
+        let mut recent = RecentFiles::default();
+        for i in 0..MAX_RECENT_FILES + 5 {
+            recent.remember(Path::new(&format!("/{}.rs", i)), 0, 0);
+        }
+        assert_eq!(recent.files.len(), MAX_RECENT_FILES);
+        assert_eq!(
+            recent.files[0].path,
+            PathBuf::from(format!("/{}.rs", MAX_RECENT_FILES + 4))
+        );
+    }
+
+    #[test]
+    pub fn text_round_trip_skips_broken_lines() {
+        let mut recent = RecentFiles::default();
+        recent.remember(Path::new("/my notes/todo.txt"), 3, 1);
+        recent.remember(Path::new("/a.rs"), 10, 0);
+        assert_eq!(RecentFiles::parse(&recent.to_text()), recent);
+
+        let parsed = RecentFiles::parse("1 2 /a.rs\ngarbage\n1 /b.rs\n");
+        assert_eq!(parsed.files.len(), 1);
+    }
+}
@@ -0,0 +1,231 @@
+use std::ops::Range;
+
+use crate::columns::char_width;
+
+/// Columns of the minimap.
+pub const MINIMAP_WIDTH: u16 = 10;
+/// Lines shown in a row of the minimap, one per row of braille dots.
+const MINIMAP_LINES_PER_ROW: usize = 4;
+/// Text columns covered by a column of braille dots.
+const MINIMAP_COLUMNS_PER_DOT: usize = 4;
+
+/// What is shown at the right edge of a view.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Scrollbar {
+    Off,
+    #[default]
+    On,
+    /// The scrollbar with a zoomed-out outline of the text next to it.
+    Minimap,
+}
+
+impl Scrollbar {
+    pub fn parse(value: &str) -> Option<Scrollbar> {
+        match value {
+            "off" => Some(Scrollbar::Off),
+            "on" => Some(Scrollbar::On),
+            "minimap" => Some(Scrollbar::Minimap),
+            _ => None,
+        }
+    }
+
+    /// Columns taken at the right edge of a view.
+    pub fn width(&self) -> u16 {
+        match self {
+            Scrollbar::Off => 0,
+            Scrollbar::On => 1,
+            Scrollbar::Minimap => 1 + MINIMAP_WIDTH,
+        }
+    }
+}
+
+/// Something shown on the scrollbar at the row of its line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Marker {
+    Selection,
+    Cursor,
+}
+
+/// The row of a bar with `height` rows that stands for `line`.
+pub fn row_of_line(line: usize, num_lines: usize, height: u16) -> u16 {
+    let row = line * height as usize / num_lines.max(1);
+    row.min(height.saturating_sub(1) as usize) as u16
+}
+
+/// The first line shown at `row` of a bar, so that `row_of_line` leads
+/// back to the row.
+pub fn line_of_row(row: u16, num_lines: usize, height: u16) -> usize {
+    let height = height.max(1) as usize;
+    ((row as usize * num_lines).div_ceil(height)).min(num_lines.saturating_sub(1))
+}
+
+/// The screen row line `line` starts at, with `rows` the screen rows of
+/// each line.
+pub fn rows_before(rows: &[u32], line: usize) -> usize {
+    rows.iter().take(line).map(|&r| r as usize).sum()
+}
+
+/// The line shown at screen row `row`, with `rows` the screen rows of each
+/// line.
+pub fn line_at_row(rows: &[u32], row: usize) -> usize {
+    let mut end = 0;
+    for (line, &r) in rows.iter().enumerate() {
+        end += r as usize;
+        if end > row {
+            return line;
+        }
+    }
+    rows.len().saturating_sub(1)
+}
+
+/// The rows of the thumb, which stands for the `visible` lines from
+/// `first` on.
+pub fn thumb(first: usize, visible: usize, num_lines: usize, height: u16) -> Range<u16> {
+    if num_lines <= visible {
+        return 0..height;
+    }
+    let size = (visible * height as usize / num_lines).max(1) as u16;
+    let start = row_of_line(first, num_lines, height).min(height.saturating_sub(size));
+    start..start + size
+}
+
+/// The markers of the cursor and the selected lines, in the order they are
+/// drawn.
+pub fn markers(
+    cursor_line: usize,
+    selection: Option<(usize, usize)>,
+    num_lines: usize,
+    height: u16,
+) -> Vec<(u16, Marker)> {
+    let mut markers = Vec::new();
+    if let Some((start, end)) = selection {
+        let rows = row_of_line(start.min(end), num_lines, height)
+            ..=row_of_line(start.max(end), num_lines, height);
+        markers.extend(rows.map(|row| (row, Marker::Selection)));
+    }
+    markers.push((row_of_line(cursor_line, num_lines, height), Marker::Cursor));
+    markers
+}
+
+/// The first line shown in a minimap of `height` rows. Long files scroll
+/// through the minimap along with the view.
+pub fn minimap_start(first: usize, visible: usize, num_lines: usize, height: u16) -> usize {
+    let capacity = height as usize * MINIMAP_LINES_PER_ROW;
+    if num_lines <= capacity {
+        return 0;
+    }
+    let last_first = num_lines.saturating_sub(visible).max(1);
+    (num_lines - capacity) * first.min(last_first) / last_first
+}
+
+/// All lines shown in a minimap of `height` rows, see `minimap_start`.
+pub fn minimap_range(first: usize, visible: usize, num_lines: usize, height: u16) -> Range<usize> {
+    let start = minimap_start(first, visible, num_lines, height);
+    start..start + height as usize * MINIMAP_LINES_PER_ROW
+}
+
+/// The lines shown in the minimap row `row`, with the minimap starting at
+/// line `start`.
+pub fn minimap_lines(start: usize, row: u16) -> Range<usize> {
+    let first = start + row as usize * MINIMAP_LINES_PER_ROW;
+    first..first + MINIMAP_LINES_PER_ROW
+}
+
+/// A row of the minimap for up to four lines, with a braille dot for every
+/// group of columns that holds text.
+pub fn minimap_row<'a>(lines: impl IntoIterator<Item = &'a [char]>) -> String {
+    // the bits of the dots in a braille cell, by row and column
+    const DOTS: [[u8; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+    let mut cells = [0u8; MINIMAP_WIDTH as usize];
+    for (y, line) in lines.into_iter().take(MINIMAP_LINES_PER_ROW).enumerate() {
+        let mut column = 0;
+        for c in line {
+            let dot = column / MINIMAP_COLUMNS_PER_DOT;
+            if dot >= cells.len() * 2 {
+                break;
+            }
+            if !c.is_whitespace() {
+                cells[dot / 2] |= DOTS[y][dot % 2];
+            }
+            column += char_width(*c, column);
+        }
+    }
+    cells
+        .iter()
+        .map(|bits| char::from_u32(0x2800 + *bits as u32).unwrap())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn thumb_shows_the_visible_part() {
+        assert_eq!(thumb(0, 10, 5, 10), 0..10);
+        assert_eq!(thumb(0, 10, 100, 10), 0..1);
+        assert_eq!(thumb(50, 10, 100, 10), 5..6);
+        assert_eq!(thumb(90, 10, 100, 10), 9..10);
+        // a thumb never leaves the bar
+        assert_eq!(thumb(95, 50, 100, 10), 5..10);
+    }
+
+    #[test]
+    pub fn markers_are_scaled_to_the_bar() {
+        assert_eq!(
+            markers(99, Some((40, 20)), 100, 10),
+            vec![
+                (2, Marker::Selection),
+                (3, Marker::Selection),
+                (4, Marker::Selection),
+                (9, Marker::Cursor)
+            ]
+        );
+        assert_eq!(markers(0, None, 0, 10), vec![(0, Marker::Cursor)]);
+    }
+
+    #[test]
+    pub fn rows_lead_back_to_lines() {
+        for row in 0..30 {
+            assert_eq!(row_of_line(line_of_row(row, 100, 30), 100, 30), row);
+        }
+        assert_eq!(line_of_row(9, 5, 10), 4);
+        assert_eq!(line_of_row(3, 0, 10), 0);
+    }
+
+    #[test]
+    pub fn minimap_scrolls_with_the_view() {
+        // 40 lines fit into 10 rows
+        assert_eq!(minimap_start(30, 10, 40, 10), 0);
+        assert_eq!(minimap_start(0, 10, 1000, 10), 0);
+        assert_eq!(minimap_start(990, 10, 1000, 10), 960);
+        assert_eq!(minimap_start(495, 10, 1000, 10), 480);
+        assert_eq!(minimap_lines(480, 2), 488..492);
+        assert_eq!(minimap_range(495, 10, 1000, 10), 480..520);
+    }
+
+    #[test]
+    pub fn wrapped_lines_take_several_rows() {
+        let rows = [1, 3, 1, 2];
+        assert_eq!(rows_before(&rows, 0), 0);
+        assert_eq!(rows_before(&rows, 2), 4);
+        assert_eq!(rows_before(&rows, 4), 7);
+        assert_eq!(line_at_row(&rows, 0), 0);
+        assert_eq!(line_at_row(&rows, 3), 1);
+        assert_eq!(line_at_row(&rows, 4), 2);
+        assert_eq!(line_at_row(&rows, 99), 3);
+    }
+
+    #[test]
+    pub fn minimap_rows_outline_the_text() {
+        let lines: Vec<Vec<char>> = ["fn main() {", "    x", "", "}"]
+            .iter()
+            .map(|l| l.chars().collect())
+            .collect();
+        let row = minimap_row(lines.iter().map(|l| l.as_slice()));
+        // columns 0-7 of all lines in the first cell, 8-15 in the second
+        assert_eq!(row.chars().next(), Some('\u{2859}'));
+        assert_eq!(row.chars().nth(1), Some('\u{2801}'));
+        assert_eq!(row.chars().count(), MINIMAP_WIDTH as usize);
+    }
+}
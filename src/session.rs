@@ -0,0 +1,215 @@
+use std::path::{Path, PathBuf};
+
+use crate::config::data_dir;
+
+/// An open buffer as stored in a session.
+#[derive(Debug, Default, PartialEq)]
+pub struct SessionBuffer {
+    pub path: PathBuf,
+    /// The custom buffer label, empty if the file name is shown.
+    pub name: String,
+    pub cursor_line: usize,
+    pub cursor_position: usize,
+    pub scroll_offset: usize,
+}
+
+/// What was open when mu was closed, so it can be restored on the next
+/// start. Stored as a "key = value" file like the configuration.
+#[derive(Debug, Default, PartialEq)]
+pub struct Session {
+    pub buffers: Vec<SessionBuffer>,
+    pub current_buffer: usize,
+    pub mode: String,
+    pub file_tree: bool,
+}
+
+/// FNV-1a, used instead of `DefaultHasher` because session file names must
+/// stay the same across builds.
+fn stable_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// The file name of the session belonging to the project in `directory`.
+fn project_file_name(directory: &Path) -> String {
+    let name = directory
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    format!(
+        "{}-{:016x}",
+        name,
+        stable_hash(directory.as_os_str().as_encoded_bytes())
+    )
+}
+
+/// Where the session for the project in `directory` is stored.
+pub fn project_session_path(directory: &Path) -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("sessions").join(project_file_name(directory)))
+}
+
+/// Where the session saved under `name` is stored.
+pub fn named_session_path(name: &str) -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("sessions").join("named").join(name))
+}
+
+fn parse_number(number: usize, value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .map_err(|_| format!("line {}: expected a number, got '{}'", number + 1, value))
+}
+
+impl Session {
+    pub fn parse(contents: &str) -> Result<Session, String> {
+        let mut session = Session::default();
+
+        for (number, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+
+            let Some((key, raw_value)) = line.split_once('=') else {
+                return Err(format!("line {}: expected 'key = value'", number + 1));
+            };
+            // paths and names are kept as written, they may start or end
+            // with spaces
+            let raw_value = raw_value.strip_prefix(' ').unwrap_or(raw_value);
+            let (key, value) = (key.trim(), raw_value.trim());
+
+            match key {
+                "current_buffer" => session.current_buffer = parse_number(number, value)?,
+                "mode" => session.mode = value.to_string(),
+                "file_tree" => session.file_tree = value == "true",
+                // "buffer = <line> <column> <scroll offset> <path>"
+                "buffer" => {
+                    let mut parts = raw_value.splitn(4, ' ');
+                    let mut next_number = || parse_number(number, parts.next().unwrap_or(""));
+                    let cursor_line = next_number()?;
+                    let cursor_position = next_number()?;
+                    let scroll_offset = next_number()?;
+                    let path = parts
+                        .next()
+                        .ok_or_else(|| format!("line {}: buffer without a path", number + 1))?;
+                    session.buffers.push(SessionBuffer {
+                        path: PathBuf::from(path),
+                        name: String::new(),
+                        cursor_line,
+                        cursor_position,
+                        scroll_offset,
+                    });
+                }
+                // the label of the buffer above
+                "name" => match session.buffers.last_mut() {
+                    Some(buffer) => buffer.name = raw_value.to_string(),
+                    None => return Err(format!("line {}: name without a buffer", number + 1)),
+                },
+                _ => return Err(format!("line {}: unknown entry '{}'", number + 1, key)),
+            }
+        }
+
+        Ok(session)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "# mu session\ncurrent_buffer = {}\nmode = {}\nfile_tree = {}\n",
+            self.current_buffer, self.mode, self.file_tree
+        );
+        for buffer in &self.buffers {
+            text.push_str(&format!(
+                "buffer = {} {} {} {}\n",
+                buffer.cursor_line,
+                buffer.cursor_position,
+                buffer.scroll_offset,
+                buffer.path.to_string_lossy()
+            ));
+            if !buffer.name.is_empty() {
+                text.push_str(&format!("name = {}\n", buffer.name));
+            }
+        }
+        text
+    }
+
+    /// Load the session stored at `path`, `None` if there is none.
+    pub fn load(path: &Path) -> Result<Option<Session>, String> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => Session::parse(&contents)
+                .map(Some)
+                .map_err(|e| format!("{}: {}", path.to_string_lossy(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("{}: {}", path.to_string_lossy(), e)),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, self.to_text())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    fn session() -> Session {
+        Session {
+            buffers: vec![
+                SessionBuffer {
+                    path: PathBuf::from("/src/main.rs"),
+                    cursor_line: 12,
+                    cursor_position: 4,
+                    scroll_offset: 3,
+                    ..Default::default()
+                },
+                SessionBuffer {
+                    path: PathBuf::from("/my notes/ todo.txt "),
+                    name: " todo list ".to_string(),
+                    ..Default::default()
+                },
+            ],
+            current_buffer: 1,
+            mode: "INSERT".to_string(),
+            file_tree: true,
+        }
+    }
+
+    #[test]
+    pub fn text_round_trip() {
+        let session = session();
+        assert_eq!(Session::parse(&session.to_text()), Ok(session));
+    }
+
+    #[test]
+    pub fn rejects_broken_entries() {
+        assert!(Session::parse("buffer = 1 2 /a.rs").is_err());
+        assert!(Session::parse("name = orphan").is_err());
+        assert!(Session::parse("frobnicate = 1").is_err());
+        assert!(Session::parse("current_buffer = one").is_err());
+    }
+
+    #[test]
+    pub fn save_and_load() {
+        let dir = TempDir::new("session");
+        let path = dir.join("sessions/session");
+        assert_eq!(Session::load(&path), Ok(None));
+        session().save(&path).unwrap();
+        assert_eq!(Session::load(&path), Ok(Some(session())));
+    }
+
+    #[test]
+    pub fn projects_get_distinct_stable_names() {
+        assert_eq!(
+            project_file_name(Path::new("/home/me/mu")),
+            project_file_name(Path::new("/home/me/mu"))
+        );
+        assert_ne!(
+            project_file_name(Path::new("/home/me/mu")),
+            project_file_name(Path::new("/home/you/mu"))
+        );
+        assert!(project_file_name(Path::new("/home/me/mu")).starts_with("mu-"));
+    }
+}
@@ -0,0 +1,471 @@
+use ratatui::layout::Rect;
+
+use crate::bufferentry::BufferEntry;
+
+/// How much a resize moves the border between two views, in percent.
+const RESIZE_STEP: u16 = 5;
+const MIN_PERCENT: u16 = 10;
+const MAX_PERCENT: u16 = 90;
+
+/// How a split divides its area.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SplitDirection {
+    /// Views side by side.
+    Vertical,
+    /// Views on top of each other.
+    Horizontal,
+}
+
+/// Where to look for the next view.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Side {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// Which buffer a view shows and where in it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct View {
+    pub buffer: usize,
+    pub cursor_line: usize,
+    pub cursor_position: usize,
+    pub scroll_offset: usize,
+    pub horizontal_offset: usize,
+}
+
+impl View {
+    /// A view of the buffer `id` at the position stored in the buffer.
+    pub fn of(id: usize, buffer: &BufferEntry) -> View {
+        View {
+            buffer: id,
+            cursor_line: buffer.cursor_line,
+            cursor_position: buffer.cursor_position,
+            scroll_offset: buffer.scroll_offset,
+            horizontal_offset: buffer.horizontal_offset,
+        }
+    }
+
+    /// This position moved into `buffer`, after another view may have
+    /// removed lines from it.
+    pub fn clamped_to(&self, buffer: &BufferEntry) -> View {
+        // the hex view only overwrites bytes, the size stays the same
+        if buffer.is_binary() {
+            return self.clone();
+        }
+        let cursor_line = self
+            .cursor_line
+            .min(buffer.buffer.num_lines().saturating_sub(1));
+        let line_length = buffer.buffer.line_char_length(cursor_line).unwrap_or(0);
+        View {
+            buffer: self.buffer,
+            cursor_line,
+            cursor_position: self.cursor_position.min(line_length),
+            scroll_offset: self.scroll_offset.min(cursor_line),
+            horizontal_offset: self.horizontal_offset,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Node {
+    View(usize),
+    Split {
+        direction: SplitDirection,
+        /// The share of the first view, in percent.
+        percent: u16,
+        first: Box<Node>,
+        second: Box<Node>,
+    },
+}
+
+impl Node {
+    fn first_view(&self) -> usize {
+        match self {
+            Node::View(id) => *id,
+            Node::Split { first, .. } => first.first_view(),
+        }
+    }
+
+    fn contains(&self, id: usize) -> bool {
+        match self {
+            Node::View(view) => *view == id,
+            Node::Split { first, second, .. } => first.contains(id) || second.contains(id),
+        }
+    }
+
+    /// Remove the view `id`, the split it was part of is replaced by the
+    /// other side. Returns the first view of that side.
+    fn remove(self, id: usize) -> (Node, Option<usize>) {
+        match self {
+            Node::Split { first, second, .. } if *first == Node::View(id) => {
+                let next = second.first_view();
+                (*second, Some(next))
+            }
+            Node::Split { first, second, .. } if *second == Node::View(id) => {
+                let next = first.first_view();
+                (*first, Some(next))
+            }
+            Node::Split {
+                direction,
+                percent,
+                first,
+                second,
+            } => {
+                let (first, from_first) = first.remove(id);
+                let (second, from_second) = second.remove(id);
+                let split = Node::Split {
+                    direction,
+                    percent,
+                    first: Box::new(first),
+                    second: Box::new(second),
+                };
+                (split, from_first.or(from_second))
+            }
+            view => (view, None),
+        }
+    }
+
+    /// Close the gap in the view ids left by removing `id`.
+    fn renumber(&mut self, removed: usize) {
+        match self {
+            Node::View(id) if *id > removed => *id -= 1,
+            Node::View(_) => (),
+            Node::Split { first, second, .. } => {
+                first.renumber(removed);
+                second.renumber(removed);
+            }
+        }
+    }
+
+    /// Move the border of the innermost split around `id`, so that the
+    /// view gets `grow` percent more room.
+    fn resize(&mut self, id: usize, grow: i16) -> bool {
+        let Node::Split {
+            percent,
+            first,
+            second,
+            ..
+        } = self
+        else {
+            return false;
+        };
+        if first.resize(id, grow) || second.resize(id, grow) {
+            return true;
+        }
+        let change = if first.contains(id) {
+            grow
+        } else if second.contains(id) {
+            -grow
+        } else {
+            return false;
+        };
+        *percent = percent
+            .saturating_add_signed(change)
+            .clamp(MIN_PERCENT, MAX_PERCENT);
+        true
+    }
+
+    fn areas(&self, area: Rect, areas: &mut Vec<(usize, Rect)>) {
+        match self {
+            Node::View(id) => areas.push((*id, area)),
+            Node::Split {
+                direction,
+                percent,
+                first,
+                second,
+            } => {
+                let (first_area, second_area) = divide(area, *direction, *percent);
+                first.areas(first_area, areas);
+                second.areas(second_area, areas);
+            }
+        }
+    }
+}
+
+/// Divide `area` in two, leaving a row or column between them for a
+/// separator.
+fn divide(area: Rect, direction: SplitDirection, percent: u16) -> (Rect, Rect) {
+    let size = match direction {
+        SplitDirection::Vertical => area.width,
+        SplitDirection::Horizontal => area.height,
+    };
+    let available = size.saturating_sub(1);
+    let first = (available as u32 * percent as u32 / 100) as u16;
+    let second = available - first;
+    match direction {
+        SplitDirection::Vertical => (
+            Rect {
+                width: first,
+                ..area
+            },
+            Rect {
+                x: area.x + first + 1,
+                width: second,
+                ..area
+            },
+        ),
+        SplitDirection::Horizontal => (
+            Rect {
+                height: first,
+                ..area
+            },
+            Rect {
+                y: area.y + first + 1,
+                height: second,
+                ..area
+            },
+        ),
+    }
+}
+
+/// The screen divided into views. The position of the active view is kept
+/// in its `BufferEntry`, so editing does not need to know about views, the
+/// others remember theirs in `views`.
+#[derive(Debug)]
+pub struct Splits {
+    pub views: Vec<View>,
+    pub active: usize,
+    root: Node,
+}
+
+impl Default for Splits {
+    fn default() -> Splits {
+        Splits {
+            views: vec![View::default()],
+            active: 0,
+            root: Node::View(0),
+        }
+    }
+}
+
+impl Splits {
+    /// Split the active view in two. The new view shows `view` and is
+    /// placed right of or below the active one. Returns its id.
+    pub fn split(&mut self, direction: SplitDirection, view: View) -> usize {
+        let id = self.views.len();
+        self.views.push(view);
+        let active = self.active;
+        let root = std::mem::replace(&mut self.root, Node::View(0));
+        self.root = split_node(root, active, direction, id);
+        id
+    }
+
+    /// Close the view `id`. Returns the view that got its room, or None if
+    /// it is the last one.
+    pub fn close(&mut self, id: usize) -> Option<usize> {
+        if self.views.len() < 2 || id >= self.views.len() {
+            return None;
+        }
+        let root = std::mem::replace(&mut self.root, Node::View(0));
+        let (mut root, next) = root.remove(id);
+        root.renumber(id);
+        self.root = root;
+        self.views.remove(id);
+        if self.active > id {
+            self.active -= 1;
+        }
+        next.map(|next| if next > id { next - 1 } else { next })
+    }
+
+    /// Give the view `id` more (or less) room.
+    pub fn resize(&mut self, id: usize, grow: bool) {
+        let step = RESIZE_STEP as i16;
+        self.root.resize(id, if grow { step } else { -step });
+    }
+
+    /// The area of each view when the views share `area`.
+    pub fn areas(&self, area: Rect) -> Vec<(usize, Rect)> {
+        let mut areas = Vec::new();
+        self.root.areas(area, &mut areas);
+        areas
+    }
+
+    pub fn area_of(&self, area: Rect, id: usize) -> Option<Rect> {
+        self.areas(area)
+            .into_iter()
+            .find(|(view, _)| *view == id)
+            .map(|(_, area)| area)
+    }
+
+    /// The closest view on `side` of the view `id`.
+    pub fn neighbour(&self, area: Rect, id: usize, side: Side) -> Option<usize> {
+        let areas = self.areas(area);
+        let from = areas.iter().find(|(view, _)| *view == id)?.1;
+        let overlaps_rows = |r: &Rect| r.y < from.bottom() && from.y < r.bottom();
+        let overlaps_columns = |r: &Rect| r.x < from.right() && from.x < r.right();
+        areas
+            .iter()
+            .filter_map(|(view, r)| {
+                let distance = match side {
+                    Side::Left if overlaps_rows(r) => from.x.checked_sub(r.right())?,
+                    Side::Right if overlaps_rows(r) => r.x.checked_sub(from.right())?,
+                    Side::Up if overlaps_columns(r) => from.y.checked_sub(r.bottom())?,
+                    Side::Down if overlaps_columns(r) => r.y.checked_sub(from.bottom())?,
+                    _ => return None,
+                };
+                Some((distance, *view))
+            })
+            .min()
+            .map(|(_, view)| view)
+    }
+
+    /// Keep the views pointing at the right buffers after the buffer
+    /// `index` was closed. Views that showed it show the one before it.
+    pub fn buffer_removed(&mut self, index: usize) {
+        for view in &mut self.views {
+            if view.buffer == index {
+                *view = View {
+                    buffer: index.saturating_sub(1),
+                    ..Default::default()
+                };
+            } else if view.buffer > index {
+                view.buffer -= 1;
+            }
+        }
+    }
+}
+
+fn split_node(node: Node, id: usize, direction: SplitDirection, new: usize) -> Node {
+    match node {
+        Node::View(view) if view == id => Node::Split {
+            direction,
+            percent: 50,
+            first: Box::new(Node::View(view)),
+            second: Box::new(Node::View(new)),
+        },
+        Node::Split {
+            direction: split_direction,
+            percent,
+            first,
+            second,
+        } => Node::Split {
+            direction: split_direction,
+            percent,
+            first: Box::new(split_node(*first, id, direction, new)),
+            second: Box::new(split_node(*second, id, direction, new)),
+        },
+        view => view,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCREEN: Rect = Rect {
+        x: 0,
+        y: 0,
+        width: 81,
+        height: 41,
+    };
+
+    fn view_of(buffer: usize) -> View {
+        View {
+            buffer,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    pub fn a_single_view_fills_the_screen() {
+        let splits = Splits::default();
+        assert_eq!(splits.areas(SCREEN), vec![(0, SCREEN)]);
+    }
+
+    #[test]
+    pub fn splits_share_the_screen() {
+        let mut splits = Splits::default();
+        let right = splits.split(SplitDirection::Vertical, view_of(1));
+        splits.active = right;
+        let below = splits.split(SplitDirection::Horizontal, view_of(2));
+
+        assert_eq!(
+            splits.areas(SCREEN),
+            vec![
+                (0, Rect::new(0, 0, 40, 41)),
+                (right, Rect::new(41, 0, 40, 20)),
+                (below, Rect::new(41, 21, 40, 20)),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn neighbours_are_found_by_side() {
+        let mut splits = Splits::default();
+        let right = splits.split(SplitDirection::Vertical, view_of(1));
+        splits.active = right;
+        let below = splits.split(SplitDirection::Horizontal, view_of(2));
+
+        assert_eq!(splits.neighbour(SCREEN, 0, Side::Right), Some(right));
+        assert_eq!(splits.neighbour(SCREEN, below, Side::Left), Some(0));
+        assert_eq!(splits.neighbour(SCREEN, below, Side::Up), Some(right));
+        assert_eq!(splits.neighbour(SCREEN, right, Side::Down), Some(below));
+        assert_eq!(splits.neighbour(SCREEN, 0, Side::Left), None);
+    }
+
+    #[test]
+    pub fn closing_a_view_gives_its_room_to_the_other_side() {
+        let mut splits = Splits::default();
+        let right = splits.split(SplitDirection::Vertical, view_of(1));
+        splits.active = right;
+        let below = splits.split(SplitDirection::Horizontal, view_of(2));
+
+        assert_eq!(splits.close(right), Some(below - 1));
+        assert_eq!(splits.views, vec![view_of(0), view_of(2)]);
+        assert_eq!(
+            splits.areas(SCREEN),
+            vec![(0, Rect::new(0, 0, 40, 41)), (1, Rect::new(41, 0, 40, 41))]
+        );
+
+        assert_eq!(splits.close(0), Some(0));
+        assert_eq!(splits.areas(SCREEN), vec![(0, SCREEN)]);
+        assert_eq!(splits.close(0), None);
+    }
+
+    #[test]
+    pub fn resizing_moves_the_closest_border() {
+        let mut splits = Splits::default();
+        let right = splits.split(SplitDirection::Vertical, view_of(1));
+        splits.resize(right, true);
+        assert_eq!(
+            splits.area_of(SCREEN, right),
+            Some(Rect::new(37, 0, 44, 41))
+        );
+        for _ in 0..20 {
+            splits.resize(0, false);
+        }
+        assert_eq!(splits.area_of(SCREEN, 0), Some(Rect::new(0, 0, 8, 41)));
+    }
+
+    #[test]
+    pub fn views_follow_closed_buffers() {
+        let mut splits = Splits::default();
+        splits.split(SplitDirection::Vertical, view_of(2));
+        splits.split(SplitDirection::Vertical, view_of(1));
+        splits.buffer_removed(1);
+        let buffers: Vec<usize> = splits.views.iter().map(|v| v.buffer).collect();
+        assert_eq!(buffers, vec![0, 1, 0]);
+    }
+
+    #[test]
+    pub fn views_are_clamped_to_shorter_buffers() {
+        let buffer = BufferEntry::from_bytes("t", b"one\ntwo\n".to_vec());
+        let view = View {
+            cursor_line: 7,
+            cursor_position: 9,
+            scroll_offset: 5,
+            ..Default::default()
+        };
+        let clamped = view.clamped_to(&buffer);
+        assert_eq!(
+            (
+                clamped.cursor_line,
+                clamped.cursor_position,
+                clamped.scroll_offset
+            ),
+            (1, 3, 1)
+        );
+    }
+}
@@ -0,0 +1,200 @@
+use crate::{app::ApplicationState, bufferentry::BufferEntry};
+
+/// A piece of information about the current buffer in the status line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Segment {
+    /// Line and column of the cursor, the offset in binary files.
+    Position,
+    /// How far through the file the cursor is.
+    Percent,
+    FileType,
+    Encoding,
+    LineEnding,
+    /// Modified and read-only.
+    Flags,
+    /// The number of selected characters.
+    Selection,
+    /// Keys typed so far of a sequence that is not complete yet.
+    Keys,
+}
+
+const SEGMENTS: [(&str, Segment); 8] = [
+    ("position", Segment::Position),
+    ("percent", Segment::Percent),
+    ("filetype", Segment::FileType),
+    ("encoding", Segment::Encoding),
+    ("line_ending", Segment::LineEnding),
+    ("flags", Segment::Flags),
+    ("selection", Segment::Selection),
+    ("keys", Segment::Keys),
+];
+
+const DEFAULT: &str = "flags selection keys | position percent filetype encoding line_ending";
+
+/// The segments shown on the left and on the right of the status line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatusLine {
+    pub left: Vec<Segment>,
+    pub right: Vec<Segment>,
+}
+
+impl Default for StatusLine {
+    fn default() -> StatusLine {
+        StatusLine::parse(DEFAULT).unwrap()
+    }
+}
+
+impl StatusLine {
+    /// Parse segment names separated by spaces. Segments after a '|' are
+    /// shown on the right.
+    pub fn parse(value: &str) -> Result<StatusLine, String> {
+        let (left, right) = value.split_once('|').unwrap_or((value, ""));
+        let segments = |names: &str| {
+            names
+                .split_whitespace()
+                .map(|name| {
+                    SEGMENTS
+                        .iter()
+                        .find(|(segment, _)| *segment == name)
+                        .map(|(_, segment)| *segment)
+                        .ok_or_else(|| format!("unknown status line segment '{}'", name))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(StatusLine {
+            left: segments(left)?,
+            right: segments(right)?,
+        })
+    }
+
+    /// The text of the left and the right side for the current buffer.
+    /// Segments with nothing to say are left out.
+    pub fn texts(&self, app_state: &ApplicationState) -> (String, String) {
+        let Some(buffer) = app_state.buffers.get(app_state.current_buffer) else {
+            return (String::new(), String::new());
+        };
+        let join = |segments: &[Segment]| {
+            segments
+                .iter()
+                .filter_map(|segment| segment_text(*segment, buffer, app_state))
+                .collect::<Vec<_>>()
+                .join("  ")
+        };
+        (join(&self.left), join(&self.right))
+    }
+}
+
+fn segment_text(
+    segment: Segment,
+    buffer: &BufferEntry,
+    app_state: &ApplicationState,
+) -> Option<String> {
+    let binary = buffer.binary.as_ref();
+    match segment {
+        Segment::Position => Some(match binary {
+            Some(_) => format!("0x{:08x}", buffer.cursor_position),
+            None => format!("{}:{}", buffer.cursor_line + 1, buffer.cursor_position + 1),
+        }),
+        Segment::Percent => {
+            let (position, len) = match binary {
+                Some(bytes) => (buffer.cursor_position + 1, bytes.len()),
+                None => (buffer.cursor_line + 1, buffer.buffer.num_lines()),
+            };
+            Some(format!("{}%", position * 100 / len.max(1)))
+        }
+        Segment::FileType => match binary {
+            Some(_) => Some("binary".to_string()),
+            None => Some(buffer.highlighter.language().unwrap_or("text").to_string()),
+        },
+        Segment::Encoding => {
+            let bom = buffer
+                .buffer
+                .line_at(0)
+                .is_some_and(|line| line.first() == Some(&'\u{feff}'));
+            match (binary, bom) {
+                (Some(_), _) => None,
+                (None, true) => Some("UTF-8 BOM".to_string()),
+                (None, false) => Some("UTF-8".to_string()),
+            }
+        }
+        Segment::LineEnding => binary
+            .is_none()
+            .then(|| buffer.line_ending.name().to_string()),
+        Segment::Flags => {
+            let flags: Vec<&str> = [(buffer.modified, "[+]"), (buffer.read_only, "[RO]")]
+                .into_iter()
+                .filter_map(|(set, flag)| set.then_some(flag))
+                .collect();
+            (!flags.is_empty()).then(|| flags.join(" "))
+        }
+        Segment::Selection => buffer
+            .selection_len()
+            .filter(|len| *len > 0)
+            .map(|len| format!("{} selected", len)),
+        Segment::Keys => {
+            (!app_state.pending_keys.is_empty()).then(|| app_state.pending_keys.clone())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::Buffer;
+
+    fn state_with(buffer: BufferEntry) -> ApplicationState {
+        ApplicationState {
+            buffers: vec![buffer],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    pub fn parses_left_and_right_segments() {
+        let status_line = StatusLine::parse("flags | position  percent").unwrap();
+        assert_eq!(status_line.left, vec![Segment::Flags]);
+        assert_eq!(status_line.right, vec![Segment::Position, Segment::Percent]);
+        assert_eq!(
+            StatusLine::parse("position").unwrap().right,
+            Vec::<Segment>::new()
+        );
+        assert!(StatusLine::parse("position | weather").is_err());
+    }
+
+    #[test]
+    pub fn describes_the_current_buffer() {
+        let mut buffer = BufferEntry::from_bytes("", b"one\r\ntwo\r\nthree\r\nfour\r\n".to_vec());
+        buffer.cursor_line = 1;
+        buffer.cursor_position = 2;
+        buffer.modified = true;
+        buffer.selection_start = Some((0, 1));
+        buffer.selection_end = Some((1, 2));
+        let state = state_with(buffer);
+
+        let (left, right) = StatusLine::default().texts(&state);
+        assert_eq!(left, "[+]  5 selected");
+        assert_eq!(right, "2:3  50%  text  UTF-8  CRLF");
+    }
+
+    #[test]
+    pub fn binary_buffers_show_offsets() {
+        let mut buffer = BufferEntry::from_bytes("", vec![0; 32]);
+        buffer.cursor_position = 15;
+        buffer.read_only = true;
+        let mut state = state_with(buffer);
+        state.pending_keys = "a".to_string();
+
+        let (left, right) = StatusLine::default().texts(&state);
+        assert_eq!(left, "[RO]  a");
+        assert_eq!(right, "0x0000000f  50%  binary");
+    }
+
+    #[test]
+    pub fn byte_order_marks_are_shown() {
+        let mut buffer = BufferEntry::from_bytes("", Vec::new());
+        buffer.buffer = Buffer::from("\u{feff}text".to_string());
+        let state = state_with(buffer);
+        let status_line = StatusLine::parse("| encoding").unwrap();
+        assert_eq!(status_line.texts(&state).1, "UTF-8 BOM");
+    }
+}
@@ -0,0 +1,584 @@
+use std::{path::PathBuf, str::FromStr};
+
+use ratatui::style::{Color, Modifier, Style};
+
+use crate::{config::config_dir, highlight::Scope};
+
+/// The parts of the screen a theme gives a style.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Slot {
+    /// The name of the current buffer in the header.
+    ActiveBuffer,
+    Popup,
+    /// Entries of suggestion lists in popups.
+    Suggestion,
+    SelectedSuggestion,
+    Cursor,
+    LineNumber,
+    CurrentLineNumber,
+    /// View separators and the markers for long and wrapped lines.
+    Indicator,
+    /// The selected entry of the file tree, when the tree is not focused.
+    TreeSelected,
+    TreeFocused,
+    HexCursor,
+    StatusLine,
+    /// Tabs, spaces and invisible characters when whitespace is shown.
+    Whitespace,
+    TrailingWhitespace,
+    /// The track of the scrollbar.
+    Scrollbar,
+    /// The part of the scrollbar that stands for the visible lines.
+    ScrollbarThumb,
+    ScrollbarCursor,
+    ScrollbarSelection,
+    Minimap,
+    /// The rows of the minimap with lines that are visible in the view.
+    MinimapView,
+    Syntax(Scope),
+}
+
+const SLOTS: [(&str, Slot); 35] = [
+    ("active_buffer", Slot::ActiveBuffer),
+    ("popup", Slot::Popup),
+    ("suggestion", Slot::Suggestion),
+    ("selected_suggestion", Slot::SelectedSuggestion),
+    ("cursor", Slot::Cursor),
+    ("line_number", Slot::LineNumber),
+    ("current_line_number", Slot::CurrentLineNumber),
+    ("indicator", Slot::Indicator),
+    ("tree_selected", Slot::TreeSelected),
+    ("tree_focused", Slot::TreeFocused),
+    ("hex_cursor", Slot::HexCursor),
+    ("status_line", Slot::StatusLine),
+    ("whitespace", Slot::Whitespace),
+    ("trailing_whitespace", Slot::TrailingWhitespace),
+    ("scrollbar", Slot::Scrollbar),
+    ("scrollbar_thumb", Slot::ScrollbarThumb),
+    ("scrollbar_cursor", Slot::ScrollbarCursor),
+    ("scrollbar_selection", Slot::ScrollbarSelection),
+    ("minimap", Slot::Minimap),
+    ("minimap_view", Slot::MinimapView),
+    ("comment", Slot::Syntax(Scope::Comment)),
+    ("string", Slot::Syntax(Scope::String)),
+    ("number", Slot::Syntax(Scope::Number)),
+    ("keyword", Slot::Syntax(Scope::Keyword)),
+    ("type", Slot::Syntax(Scope::Type)),
+    ("constant", Slot::Syntax(Scope::Constant)),
+    ("function", Slot::Syntax(Scope::Function)),
+    ("attribute", Slot::Syntax(Scope::Attribute)),
+    ("variable", Slot::Syntax(Scope::Variable)),
+    ("key", Slot::Syntax(Scope::Key)),
+    ("heading", Slot::Syntax(Scope::Heading)),
+    ("emphasis", Slot::Syntax(Scope::Emphasis)),
+    ("strong", Slot::Syntax(Scope::Strong)),
+    ("code", Slot::Syntax(Scope::Code)),
+    ("link", Slot::Syntax(Scope::Link)),
+];
+
+impl Slot {
+    fn index(self) -> usize {
+        SLOTS.iter().position(|(_, slot)| *slot == self).unwrap()
+    }
+}
+
+/// The built-in themes, in the order they are cycled through. They are
+/// written like theme files, so they double as examples.
+const BUILTIN: [(&str, &str); 4] = [
+    ("dark", DARK),
+    ("light", LIGHT),
+    ("gruvbox", GRUVBOX),
+    ("mono", MONO),
+];
+
+const DARK: &str = "
+base = none
+active_buffer = yellow
+popup = on blue
+suggestion = darkgray
+selected_suggestion = white
+cursor = rapid_blink underlined
+line_number = darkgray
+current_line_number = yellow bold
+indicator = darkgray
+tree_selected = yellow
+tree_focused = black on yellow
+hex_cursor = reversed
+status_line = white on darkgray
+whitespace = darkgray
+trailing_whitespace = on red
+scrollbar = darkgray
+scrollbar_thumb = gray
+scrollbar_cursor = yellow
+scrollbar_selection = blue
+minimap = darkgray
+minimap_view = white
+comment = darkgray
+string = green
+number = magenta
+keyword = blue bold
+type = cyan
+constant = magenta
+function = lightblue
+attribute = lightmagenta
+variable = lightcyan
+key = lightcyan
+heading = yellow bold
+emphasis = italic
+strong = bold
+code = green
+link = cyan underlined
+";
+
+const LIGHT: &str = "
+active_buffer = blue bold
+popup = black on gray
+selected_suggestion = black bold
+current_line_number = blue bold
+tree_selected = blue
+tree_focused = white on blue
+status_line = black on gray
+trailing_whitespace = on lightred
+scrollbar = gray
+scrollbar_thumb = darkgray
+scrollbar_cursor = blue
+minimap = gray
+minimap_view = black
+comment = darkgray italic
+keyword = magenta bold
+type = blue
+number = red
+constant = red
+function = blue
+attribute = magenta
+variable = cyan
+key = blue
+heading = blue bold
+link = blue underlined
+";
+
+const GRUVBOX: &str = "
+active_buffer = #fabd2f bold
+popup = #ebdbb2 on #504945
+suggestion = #928374
+selected_suggestion = #fbf1c7 bold
+line_number = #665c54
+current_line_number = #fabd2f bold
+indicator = #665c54
+tree_selected = #fabd2f
+tree_focused = #282828 on #fabd2f
+status_line = #ebdbb2 on #3c3836
+whitespace = #665c54
+trailing_whitespace = on #cc241d
+scrollbar = #504945
+scrollbar_thumb = #a89984
+scrollbar_cursor = #fabd2f
+scrollbar_selection = #83a598
+minimap = #665c54
+minimap_view = #ebdbb2
+comment = #928374 italic
+string = #b8bb26
+number = #d3869b
+keyword = #fb4934 bold
+type = #fabd2f
+constant = #d3869b
+function = #8ec07c
+attribute = #fe8019
+variable = #83a598
+key = #83a598
+heading = #fabd2f bold
+code = #b8bb26
+link = #83a598 underlined
+";
+
+const MONO: &str = "
+base = none
+active_buffer = bold underlined
+popup = reversed
+suggestion = dim
+selected_suggestion = bold
+cursor = rapid_blink underlined
+line_number = dim
+current_line_number = bold
+indicator = dim
+tree_selected = underlined
+tree_focused = reversed
+hex_cursor = reversed
+status_line = reversed
+whitespace = dim
+trailing_whitespace = reversed
+scrollbar = dim
+scrollbar_thumb = bold
+scrollbar_cursor = reversed
+scrollbar_selection = bold
+minimap = dim
+minimap_view = bold
+comment = dim italic
+keyword = bold
+heading = bold underlined
+emphasis = italic
+strong = bold
+code = dim
+link = underlined
+";
+
+/// How many colors the terminal can show.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorSupport {
+    /// Only modifiers, colors are dropped.
+    None,
+    /// The 16 ANSI colors.
+    Basic,
+    /// The 256 color palette.
+    Palette,
+    TrueColor,
+}
+
+impl ColorSupport {
+    /// Guess the color support from the environment, the way most terminal
+    /// programs do.
+    pub fn detect() -> ColorSupport {
+        let var = |name| std::env::var(name).unwrap_or_default();
+        ColorSupport::from_env(&var("NO_COLOR"), &var("COLORTERM"), &var("TERM"))
+    }
+
+    fn from_env(no_color: &str, colorterm: &str, term: &str) -> ColorSupport {
+        if !no_color.is_empty() || term == "dumb" {
+            ColorSupport::None
+        } else if colorterm == "truecolor" || colorterm == "24bit" {
+            ColorSupport::TrueColor
+        } else if term.contains("256color") {
+            ColorSupport::Palette
+        } else {
+            ColorSupport::Basic
+        }
+    }
+}
+
+/// Named styles for the user interface and the syntax highlighting.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub name: String,
+    support: ColorSupport,
+    styles: [Style; SLOTS.len()],
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme::load("dark", ColorSupport::TrueColor).unwrap()
+    }
+}
+
+impl Theme {
+    pub fn style(&self, slot: Slot) -> Style {
+        self.styles[slot.index()]
+    }
+
+    /// The style of highlighted text, plain text has no style.
+    pub fn scope(&self, scope: Option<Scope>) -> Style {
+        scope.map_or_else(Style::default, |scope| self.style(Slot::Syntax(scope)))
+    }
+
+    /// Parse a theme file. Slots it does not set are taken from the theme
+    /// named by `base`, the dark theme if there is none.
+    pub fn parse(name: &str, contents: &str) -> Result<Theme, String> {
+        let mut styles = [Style::default(); SLOTS.len()];
+        let mut base = Some(DARK);
+        let mut own = Vec::new();
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("line {}: expected 'slot = style'", number + 1));
+            };
+            let (key, value) = (key.trim(), value.trim());
+
+            if key == "base" {
+                base = match value {
+                    "none" => None,
+                    _ => Some(builtin(value).ok_or_else(|| {
+                        format!("line {}: unknown base theme '{}'", number + 1, value)
+                    })?),
+                };
+                continue;
+            }
+            let Some((_, slot)) = SLOTS.iter().find(|(slot, _)| *slot == key) else {
+                return Err(format!("line {}: unknown style '{}'", number + 1, key));
+            };
+            let style = parse_style(value).map_err(|e| format!("line {}: {}", number + 1, e))?;
+            own.push((slot.index(), style));
+        }
+
+        if let Some(base) = base {
+            styles = Theme::parse("", base)?.styles;
+        }
+        for (index, style) in own {
+            styles[index] = style;
+        }
+        Ok(Theme {
+            name: name.to_string(),
+            support: ColorSupport::TrueColor,
+            styles,
+        })
+    }
+
+    /// Load a built-in theme or one from the themes directory of the
+    /// configuration, with its colors reduced to what the terminal shows.
+    pub fn load(name: &str, support: ColorSupport) -> Result<Theme, String> {
+        let theme = match builtin(name) {
+            Some(contents) => Theme::parse(name, contents)?,
+            None => {
+                let path = if name.contains('/') {
+                    PathBuf::from(name)
+                } else {
+                    config_dir()
+                        .ok_or("No configuration directory")?
+                        .join("themes")
+                        .join(name)
+                };
+                let contents = std::fs::read_to_string(&path)
+                    .map_err(|e| format!("{}: {}", path.to_string_lossy(), e))?;
+                Theme::parse(name, &contents)
+                    .map_err(|e| format!("{}: {}", path.to_string_lossy(), e))?
+            }
+        };
+        Ok(theme.degraded(support))
+    }
+
+    /// The built-in theme after this one.
+    pub fn next(&self) -> Theme {
+        let current = BUILTIN.iter().position(|(name, _)| *name == self.name);
+        let next = current.map_or(0, |i| (i + 1) % BUILTIN.len());
+        Theme::load(BUILTIN[next].0, self.support).unwrap()
+    }
+
+    fn degraded(mut self, support: ColorSupport) -> Theme {
+        self.support = support;
+        for style in &mut self.styles {
+            style.fg = style.fg.and_then(|c| degrade(c, support));
+            style.bg = style.bg.and_then(|c| degrade(c, support));
+        }
+        self
+    }
+}
+
+fn builtin(name: &str) -> Option<&'static str> {
+    BUILTIN
+        .iter()
+        .find(|(builtin, _)| *builtin == name)
+        .map(|(_, contents)| *contents)
+}
+
+/// Parse a style like "yellow on blue bold". Colors are names, palette
+/// indexes or "#rrggbb".
+fn parse_style(value: &str) -> Result<Style, String> {
+    let mut style = Style::default();
+    let mut words = value.split_whitespace();
+    while let Some(word) = words.next() {
+        let modifier = match word {
+            "default" => continue,
+            "on" => {
+                let color = words.next().ok_or("expected a color after 'on'")?;
+                style = style.bg(parse_color(color)?);
+                continue;
+            }
+            "bold" => Modifier::BOLD,
+            "dim" => Modifier::DIM,
+            "italic" => Modifier::ITALIC,
+            "underlined" => Modifier::UNDERLINED,
+            "blink" => Modifier::SLOW_BLINK,
+            "rapid_blink" => Modifier::RAPID_BLINK,
+            "reversed" => Modifier::REVERSED,
+            "crossed_out" => Modifier::CROSSED_OUT,
+            _ => {
+                style = style.fg(parse_color(word)?);
+                continue;
+            }
+        };
+        style = style.add_modifier(modifier);
+    }
+    Ok(style)
+}
+
+fn parse_color(word: &str) -> Result<Color, String> {
+    Color::from_str(word).map_err(|_| format!("unknown color or modifier '{}'", word))
+}
+
+/// The 16 ANSI colors with the values xterm uses for them.
+const ANSI: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// The levels of the 6x6x6 color cube in the 256 color palette.
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Replace `color` by the closest one the terminal can show.
+fn degrade(color: Color, support: ColorSupport) -> Option<Color> {
+    match (support, color) {
+        (ColorSupport::None, _) => None,
+        (ColorSupport::Palette, Color::Rgb(r, g, b)) => {
+            Some(Color::Indexed(palette_index((r, g, b))))
+        }
+        (ColorSupport::Basic, Color::Rgb(r, g, b)) => Some(nearest_ansi((r, g, b))),
+        (ColorSupport::Basic, Color::Indexed(index)) => Some(nearest_ansi(palette_rgb(index))),
+        _ => Some(color),
+    }
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+fn nearest_ansi(rgb: (u8, u8, u8)) -> Color {
+    ANSI.iter()
+        .min_by_key(|(_, ansi)| distance(rgb, *ansi))
+        .map(|(color, _)| *color)
+        .unwrap()
+}
+
+fn palette_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..16 => ANSI[index as usize].1,
+        16..232 => {
+            let i = index - 16;
+            (
+                CUBE[i as usize / 36],
+                CUBE[i as usize / 6 % 6],
+                CUBE[i as usize % 6],
+            )
+        }
+        _ => {
+            let level = 8 + 10 * (index - 232);
+            (level, level, level)
+        }
+    }
+}
+
+/// The closest color of the cube or the gray ramp, the first 16 colors are
+/// left out as terminals often change them.
+fn palette_index(rgb: (u8, u8, u8)) -> u8 {
+    let level = |v: u8| {
+        (0..CUBE.len())
+            .min_by_key(|&i| (CUBE[i] as i32 - v as i32).abs())
+            .unwrap() as u8
+    };
+    let cube = 16 + 36 * level(rgb.0) + 6 * level(rgb.1) + level(rgb.2);
+    let average = (rgb.0 as u32 + rgb.1 as u32 + rgb.2 as u32) / 3;
+    let gray = 232 + (average.saturating_sub(3) / 10).min(23) as u8;
+    if distance(rgb, palette_rgb(gray)) < distance(rgb, palette_rgb(cube)) {
+        gray
+    } else {
+        cube
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn parses_styles() {
+        assert_eq!(
+            parse_style("yellow on #102030 bold").unwrap(),
+            Style::default()
+                .fg(Color::Yellow)
+                .bg(Color::Rgb(0x10, 0x20, 0x30))
+                .add_modifier(Modifier::BOLD)
+        );
+        assert_eq!(
+            parse_style("208").unwrap(),
+            Style::default().fg(Color::Indexed(208))
+        );
+        assert!(parse_style("sparkly").is_err());
+        assert!(parse_style("red on").is_err());
+    }
+
+    #[test]
+    pub fn theme_files_extend_a_base_theme() {
+        let theme = Theme::parse("mine", "# mine\nbase = light\nkeyword = red\n").unwrap();
+        assert_eq!(
+            theme.style(Slot::Syntax(Scope::Keyword)),
+            Style::default().fg(Color::Red)
+        );
+        assert_eq!(
+            theme.style(Slot::TreeFocused),
+            Theme::load("light", ColorSupport::TrueColor)
+                .unwrap()
+                .style(Slot::TreeFocused)
+        );
+        assert!(Theme::parse("bad", "sparkle = red").is_err());
+        assert!(Theme::parse("bad", "base = nope").is_err());
+    }
+
+    #[test]
+    pub fn builtin_themes_load_and_cycle() {
+        let mut theme = Theme::default();
+        for (name, _) in BUILTIN.iter().skip(1) {
+            theme = theme.next();
+            assert_eq!(theme.name, *name);
+        }
+        assert_eq!(theme.next().name, "dark");
+    }
+
+    #[test]
+    pub fn colors_are_reduced_to_what_the_terminal_shows() {
+        let red = Color::Rgb(255, 0, 0);
+        assert_eq!(degrade(red, ColorSupport::TrueColor), Some(red));
+        assert_eq!(
+            degrade(red, ColorSupport::Palette),
+            Some(Color::Indexed(196))
+        );
+        assert_eq!(
+            degrade(Color::Rgb(0x28, 0x28, 0x28), ColorSupport::Palette),
+            Some(Color::Indexed(235))
+        );
+        assert_eq!(degrade(red, ColorSupport::Basic), Some(Color::LightRed));
+        assert_eq!(
+            degrade(Color::Indexed(4), ColorSupport::Basic),
+            Some(Color::Blue)
+        );
+        assert_eq!(degrade(Color::Yellow, ColorSupport::None), None);
+
+        let theme = Theme::load("gruvbox", ColorSupport::None).unwrap();
+        assert_eq!(
+            theme.style(Slot::Syntax(Scope::Keyword)),
+            Style::default().add_modifier(Modifier::BOLD)
+        );
+    }
+
+    #[test]
+    pub fn color_support_follows_the_environment() {
+        assert_eq!(
+            ColorSupport::from_env("", "truecolor", "xterm-256color"),
+            ColorSupport::TrueColor
+        );
+        assert_eq!(
+            ColorSupport::from_env("", "", "xterm-256color"),
+            ColorSupport::Palette
+        );
+        assert_eq!(ColorSupport::from_env("", "", "xterm"), ColorSupport::Basic);
+        assert_eq!(
+            ColorSupport::from_env("1", "truecolor", "xterm"),
+            ColorSupport::None
+        );
+    }
+}